@@ -0,0 +1,35 @@
+//! # Function call overhead
+//!
+//! Times a loop of calls to a small function while an increasing number of unrelated
+//! functions is defined. Calls share the function table of their caller, so the time per
+//! call should stay flat as the table grows
+//!
+//! Run with `cargo bench --bench calls`
+
+use stck::{api, cache::NoCache, internals::RuntimeContext};
+use std::fmt::Write;
+use std::time::Instant;
+
+const CALLS: usize = 100_000;
+
+fn program(defined: usize) -> String {
+    let mut src = String::from("(include <std>)\n");
+    for i in 0..defined {
+        writeln!(src, "(fn) [ x ] unused-{i} {{ x }}").unwrap();
+    }
+    writeln!(src, "(fn) [ n ] step {{ n 1 - }}").unwrap();
+    writeln!(src, "{CALLS} (while) {{ dup 0 = not }} {{ step }}").unwrap();
+    src
+}
+
+fn main() {
+    for defined in [0, 10, 100, 1000] {
+        let tokens = api::get_tokens_str(&program(defined), "bench calls", &mut NoCache).unwrap();
+        let code = api::parse_raw_tokens(tokens).unwrap();
+        let mut runtime = RuntimeContext::new();
+        let start = Instant::now();
+        runtime.execute_entire_code(&code).unwrap();
+        let per_call = start.elapsed() / CALLS as u32;
+        println!("{defined:>5} fns defined: {per_call:?} per call");
+    }
+}
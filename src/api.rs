@@ -50,7 +50,9 @@ pub fn get_project_code(path: impl AsRef<Path>, file_cache: &mut impl FileCacher
     let TokenBlock { tokens, source } = get_tokens(path, file_cache)?;
     let mut parser = parse::Context::new(tokens, &source);
     let exprs = parser.parse_block()?;
-    Ok(Code { source, exprs })
+    let mut code = Code::new(source, exprs);
+    resolve::resolve(&mut code);
+    Ok(code)
 }
 
 /// # Parse expressions from tokens
@@ -63,7 +65,9 @@ pub fn get_project_code(path: impl AsRef<Path>, file_cache: &mut impl FileCacher
 pub fn parse_raw_tokens(TokenBlock { tokens, source }: TokenBlock) -> SResult<Code> {
     let mut parser = parse::Context::new(tokens, &source);
     let exprs = parser.parse_block()?;
-    Ok(Code { source, exprs })
+    let mut code = Code::new(source, exprs);
+    resolve::resolve(&mut code);
+    Ok(code)
 }
 
 /// # Execute code from file
@@ -91,6 +95,11 @@ pub fn execute_raw_code(code: &Code) -> SResult<runtime::Context> {
 
 // steps for token.rs:
 fn get_raw_tokens(file_path: &Path, file_cache: &mut impl FileCacher) -> SResult<TokenBlock> {
+    if let Some(cont) = stdlib::file(file_path) {
+        return token::Context::new(cont)
+            .tokenize(file_path.to_path_buf())
+            .map_err(error::Error::from);
+    }
     let cont = file_cache
         .read_file(file_path)
         .map_err(|_| StckError::CantReadFile(file_path.to_path_buf()))?;
@@ -115,8 +124,7 @@ fn preproc_tokens(
     file_path: &Path,
     file_cache: &mut impl FileCacher,
 ) -> SResult<TokenBlock> {
-    let cwd = PathBuf::from(".");
-    let preprocessor = preproc::Context::new(file_path.parent().unwrap_or(cwd.as_path()));
+    let preprocessor = preproc::Context::new(file_path);
     let tokens = preprocessor.parse_clean(tokens, file_cache)?;
     Ok(TokenBlock { source, tokens })
 }
@@ -127,8 +135,7 @@ fn preproc_tokens_with_vars<S: std::hash::BuildHasher>(
     vars: &mut HashSet<String, S>,
     file_cache: &mut impl FileCacher,
 ) -> SResult<TokenBlock> {
-    let cwd = PathBuf::from(".");
-    let preprocessor = preproc::Context::new(file_path.parent().unwrap_or(cwd.as_path()));
+    let preprocessor = preproc::Context::new(file_path);
     let tokens = preprocessor.parse(tokens, vars, file_cache)?;
     Ok(TokenBlock { source, tokens })
 }
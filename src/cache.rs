@@ -1,6 +1,8 @@
 use crate::*;
 use std::collections::hash_map::{Entry, HashMap, OccupiedEntry};
+use std::collections::{BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 pub trait FileCacher {
     type FileRecord<'s>: AsRef<str>
@@ -9,22 +11,57 @@ pub trait FileCacher {
     fn read_file(&mut self, path: impl AsRef<Path>)
     -> Result<Self::FileRecord<'_>, std::io::Error>;
 
+    /// # If the path is a file that can be read
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        path.as_ref().is_file()
+    }
+
+    /// # If the path is a directory, that is included through its `stck.stck`
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        path.as_ref().is_dir()
+    }
+
+    /// # File read by `(include path)` in a file inside `dir`
+    ///
+    /// Directories are included through their `stck.stck`, `None` if nothing can be read
+    fn resolve_include(&mut self, dir: &Path, path: &Path) -> Option<PathBuf> {
+        let path = dir.join(path);
+        if self.is_dir(&path) {
+            Some(path.join("stck.stck")).filter(|entry| self.is_file(entry))
+        } else {
+            Some(path).filter(|file| self.is_file(file))
+        }
+    }
+
+    /// # Called by the preprocessor when the file `from` includes the file `included`
+    ///
+    /// Includes that couldn't be resolved are recorded with the path that was tried
+    fn record_include(&mut self, from: &Path, included: &Path) {
+        let _ = (from, included);
+    }
+
     fn get_span(
         &mut self,
         path: impl AsRef<Path>,
         lines: &LineRange,
     ) -> Result<String, std::io::Error> {
+        if let Some(cont) = stdlib::file(path.as_ref()) {
+            return Ok(select_lines(cont, lines));
+        }
         let entry = self.read_file(path)?;
-        let lines: Vec<&str> = entry
-            .as_ref()
-            .split('\n')
-            .skip(lines.start - 1)
-            .take(lines.delta().max(1))
-            .collect();
-        Ok(lines.join("\n"))
+        Ok(select_lines(entry.as_ref(), lines))
     }
 }
 
+fn select_lines(cont: &str, lines: &LineRange) -> String {
+    let lines: Vec<&str> = cont
+        .split('\n')
+        .skip(lines.start - 1)
+        .take(lines.delta().max(1))
+        .collect();
+    lines.join("\n")
+}
+
 /// # Caching system for files
 ///
 /// Used with [Line range](LineRange) to read specific lines from files on [get span](ErrorHelper::get_span)
@@ -64,6 +101,10 @@ impl FileCacher for CacheHelper {
         };
         Ok(CachedFile(entry))
     }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.files.contains_key(path.as_ref()) || path.as_ref().is_file()
+    }
 }
 
 pub struct NoCache;
@@ -86,7 +127,7 @@ impl MockFileCacher {
         Self::default()
     }
     pub fn mock_file(&mut self, path: PathBuf, content: String) {
-        self.0.files.insert(path, content);
+        self.0.files.insert(normalize(path), content);
     }
 }
 
@@ -96,7 +137,17 @@ impl FileCacher for MockFileCacher {
         &mut self,
         path: impl AsRef<Path>,
     ) -> Result<Self::FileRecord<'_>, std::io::Error> {
-        self.0.read_file(path)
+        self.0.read_file(normalize(path.as_ref()))
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.0.is_file(normalize(path.as_ref()))
+    }
+
+    // mocked files make their directories, the rest is read from disk
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        contains_dir(self.0.files.keys(), &normalize(path)) || path.is_dir()
     }
 }
 
@@ -111,18 +162,22 @@ impl Isolated {
         Self::default()
     }
     pub fn add_file_cached(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
-        let entry = self.allowed.entry(path);
+        let entry = self.allowed.entry(normalize(&path));
         if let Entry::Vacant(entry) = entry {
-            let cont = std::fs::read_to_string(entry.key())?;
+            let cont = std::fs::read_to_string(path)?;
             entry.insert_entry(cont);
         }
         Ok(())
     }
     pub fn force_add_file(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
         let cont = std::fs::read_to_string(&path)?;
-        self.allowed.insert(path, cont);
+        self.allowed.insert(normalize(path), cont);
         Ok(())
     }
+    /// # Allow a file with the given contents, without reading the disk
+    pub fn allow_file(&mut self, path: PathBuf, content: String) {
+        self.allowed.insert(normalize(path), content);
+    }
 }
 
 impl FileCacher for Isolated {
@@ -131,9 +186,490 @@ impl FileCacher for Isolated {
         &mut self,
         path: impl AsRef<Path>,
     ) -> Result<Self::FileRecord<'_>, std::io::Error> {
-        self.allowed.get(path.as_ref()).ok_or(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Can't read file with Isolated cache system",
-        ))
+        self.allowed
+            .get(&normalize(path.as_ref()))
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Can't read file with Isolated cache system",
+            ))
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.allowed.contains_key(&normalize(path.as_ref()))
+    }
+
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        contains_dir(self.allowed.keys(), &normalize(path.as_ref()))
+    }
+}
+
+// if any of the files is inside the directory
+fn contains_dir<'f>(mut files: impl Iterator<Item = &'f PathBuf>, dir: &Path) -> bool {
+    files.any(|file| file != dir && file.starts_with(dir))
+}
+
+/// # Files kept in memory, organized as a directory tree
+///
+/// A directory exists while any file is inside it
+///
+/// ```rust
+/// use stck::cache::VirtualTree;
+/// let mut tree = VirtualTree::new();
+/// tree.add_file("main.stck", "(include lib/math.stck)\n1 double\n");
+/// tree.add_file("lib/math.stck", "(fn) [ n ] double { n 2 * }\n");
+/// assert!(tree.contains_dir("lib"));
+/// let code = stck::api::get_project_code("main.stck", &mut tree).unwrap();
+/// let ctx = stck::api::execute_raw_code(&code).unwrap();
+/// assert_eq!(ctx.get_stack(), [stck::internals::Value::Num(2)]);
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct VirtualTree {
+    files: HashMap<PathBuf, String>,
+}
+
+impl VirtualTree {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Add or replace a file, returning the old contents
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        content: impl Into<String>,
+    ) -> Option<String> {
+        self.files.insert(normalize(path.as_ref()), content.into())
+    }
+
+    #[must_use]
+    pub fn with_file(mut self, path: impl AsRef<Path>, content: impl Into<String>) -> Self {
+        self.add_file(path, content);
+        self
+    }
+
+    pub fn remove_file(&mut self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+
+    #[must_use]
+    pub fn contains_file(&self, path: impl AsRef<Path>) -> bool {
+        self.files.contains_key(&normalize(path.as_ref()))
+    }
+
+    #[must_use]
+    pub fn contains_dir(&self, path: impl AsRef<Path>) -> bool {
+        contains_dir(self.files.keys(), &normalize(path.as_ref()))
+    }
+
+    /// # Every file in the tree
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+}
+
+impl FileCacher for VirtualTree {
+    type FileRecord<'s> = &'s String;
+    fn read_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self::FileRecord<'_>, std::io::Error> {
+        self.files
+            .get(&normalize(path.as_ref()))
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "File isn't in the virtual tree",
+            ))
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.contains_file(path)
+    }
+
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        self.contains_dir(path)
+    }
+}
+
+// paths of in-memory files are compared after removing `.` and resolving `..` lexically, so
+// `a/./b/../c.stck` reads `a/c.stck`
+fn normalize(path: impl AsRef<Path>) -> PathBuf {
+    use std::path::Component;
+    let mut normal = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normal.file_name().is_some() => {
+                normal.pop();
+            }
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+/// # Script bundle read from a tar archive
+///
+/// Every regular file of the archive is loaded in memory as a [`VirtualTree`], so the
+/// archive is only read once
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut builder = tar::Builder::new(Vec::new());
+/// let script = "2 3 -\n";
+/// let mut header = tar::Header::new_gnu();
+/// header.set_size(script.len() as u64);
+/// builder.append_data(&mut header, "bundle/main.stck", script.as_bytes())?;
+/// let bundle = builder.into_inner()?;
+///
+/// let mut archive = stck::cache::TarArchive::from_reader(bundle.as_slice())?;
+/// let code = stck::api::get_project_code("bundle/main.stck", &mut archive)?;
+/// let ctx = stck::api::execute_raw_code(&code)?;
+/// assert_eq!(ctx.get_stack(), [stck::internals::Value::Num(-1)]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tar")]
+#[derive(Default, Clone, Debug)]
+pub struct TarArchive {
+    tree: VirtualTree,
+}
+
+#[cfg(feature = "tar")]
+impl TarArchive {
+    /// # Load the files of an archive
+    ///
+    /// Fails if the archive is malformed or any of its files isn't UTF-8
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, std::io::Error> {
+        use std::io::Read;
+        let mut tree = VirtualTree::new();
+        for entry in tar::Archive::new(reader).entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            tree.add_file(path, content);
+        }
+        Ok(Self { tree })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    #[must_use]
+    pub fn tree(&self) -> &VirtualTree {
+        &self.tree
+    }
+
+    #[must_use]
+    pub fn into_tree(self) -> VirtualTree {
+        self.tree
+    }
+}
+
+#[cfg(feature = "tar")]
+impl FileCacher for TarArchive {
+    type FileRecord<'s> = &'s String;
+    fn read_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self::FileRecord<'_>, std::io::Error> {
+        self.tree.read_file(path)
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.tree.contains_file(path)
+    }
+
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        self.tree.contains_dir(path)
+    }
+}
+
+/// # Tries to read from the first cacher, then from the second
+///
+/// Made with [`Overlay::new`], more layers are added with [then](Overlay::then). Useful to
+/// let a [`VirtualTree`] shadow files on disk, or to keep a bundle read-only under edits
+///
+/// ```rust
+/// use stck::cache::{CacheHelper, Overlay, VirtualTree};
+/// let patched = VirtualTree::new().with_file("examples/test.stck", "7\n");
+/// let mut cacher = Overlay::new(patched, CacheHelper::new());
+/// let code = stck::api::get_project_code("examples/test.stck", &mut cacher).unwrap();
+/// let ctx = stck::api::execute_raw_code(&code).unwrap();
+/// assert_eq!(ctx.get_stack(), [stck::internals::Value::Num(7)]);
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct Overlay<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: FileCacher, B: FileCacher> Overlay<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// # Try the `next` cacher after every other
+    pub fn then<C: FileCacher>(self, next: C) -> Overlay<Self, C> {
+        Overlay::new(self, next)
+    }
+
+    pub fn first(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn second(&mut self) -> &mut B {
+        &mut self.second
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// # File read by either layer of an [Overlay]
+pub enum Layered<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<A: AsRef<str>, B: AsRef<str>> AsRef<str> for Layered<A, B> {
+    fn as_ref(&self) -> &str {
+        match self {
+            Layered::First(a) => a.as_ref(),
+            Layered::Second(b) => b.as_ref(),
+        }
+    }
+}
+
+impl<A: FileCacher, B: FileCacher> FileCacher for Overlay<A, B> {
+    type FileRecord<'s>
+        = Layered<A::FileRecord<'s>, B::FileRecord<'s>>
+    where
+        Self: 's;
+    fn read_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self::FileRecord<'_>, std::io::Error> {
+        let path = path.as_ref();
+        if let Ok(file) = self.first.read_file(path) {
+            return Ok(Layered::First(file));
+        }
+        self.second.read_file(path).map(Layered::Second)
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.first.is_file(path) || self.second.is_file(path)
+    }
+
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.first.is_dir(path) || self.second.is_dir(path)
+    }
+
+    fn record_include(&mut self, from: &Path, included: &Path) {
+        self.first.record_include(from, included);
+        self.second.record_include(from, included);
+    }
+}
+
+/// # Cache that notices when files change on disk
+///
+/// Keeps the modification time and a hash of the contents of every file read, and which files
+/// each one included. [Refreshing](TrackingCache::refresh) forgets the files that changed and
+/// returns the top level scripts that have to be parsed again
+///
+/// ```rust,no_run
+/// use stck::cache::TrackingCache;
+/// let mut cache = TrackingCache::new();
+/// let mut code = stck::api::get_project_code("main.stck", &mut cache).unwrap();
+/// loop {
+///     std::thread::sleep(std::time::Duration::from_millis(500));
+///     if !cache.refresh().is_empty() {
+///         code = stck::api::get_project_code("main.stck", &mut cache).unwrap();
+///     }
+/// }
+/// ```
+#[derive(Default, Debug)]
+pub struct TrackingCache {
+    files: HashMap<PathBuf, TrackedFile>,
+    includes: HashMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+#[derive(Debug)]
+struct TrackedFile {
+    content: String,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl TrackedFile {
+    fn read(path: &Path) -> Result<Self, std::io::Error> {
+        let modified = std::fs::metadata(path)?.modified().ok();
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self {
+            hash: hash_content(&content),
+            content,
+            modified,
+        })
+    }
+
+    // a newer modification time with the same contents isn't a change
+    fn is_outdated(&self, path: &Path) -> bool {
+        let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) else {
+            return true;
+        };
+        if self.modified == Some(modified) {
+            return false;
+        }
+        match std::fs::read_to_string(path) {
+            Ok(content) => hash_content(&content) != self.hash,
+            Err(_) => true,
+        }
+    }
+}
+
+// a file, or a directory included through its `stck.stck`
+fn can_include(path: &Path) -> bool {
+    path.is_file() || path.join("stck.stck").is_file()
+}
+
+fn hash_content(content: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::hash::DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl TrackingCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Files that changed or were removed since they were read
+    ///
+    /// Includes that were missing count as changed once they can be read
+    #[must_use]
+    pub fn changed_files(&self) -> BTreeSet<PathBuf> {
+        let outdated = self
+            .files
+            .iter()
+            .filter(|(path, file)| file.is_outdated(path))
+            .map(|(path, _)| path.clone());
+        let created = self
+            .includes
+            .values()
+            .flatten()
+            .filter(|path| !self.files.contains_key(*path) && can_include(path))
+            .cloned();
+        outdated.chain(created).collect()
+    }
+
+    /// # If the file was read, and hasn't changed since the last [refresh](Self::refresh)
+    #[must_use]
+    pub fn is_tracked(&self, path: impl AsRef<Path>) -> bool {
+        self.files.contains_key(path.as_ref())
+    }
+
+    /// # Files read that weren't included by another file
+    #[must_use]
+    pub fn scripts(&self) -> BTreeSet<PathBuf> {
+        let included: HashSet<&PathBuf> = self.includes.values().flatten().collect();
+        self.files
+            .keys()
+            .filter(|path| !included.contains(path))
+            .cloned()
+            .collect()
+    }
+
+    /// # Files included by the script, directly or not
+    #[must_use]
+    pub fn dependencies(&self, script: impl AsRef<Path>) -> BTreeSet<PathBuf> {
+        let mut found = BTreeSet::new();
+        let mut next = vec![script.as_ref().to_path_buf()];
+        while let Some(path) = next.pop() {
+            for included in self.includes.get(&path).into_iter().flatten() {
+                if found.insert(included.clone()) {
+                    next.push(included.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// # Scripts that have to be parsed again, since they or their includes changed
+    #[must_use]
+    pub fn stale_scripts(&self) -> BTreeSet<PathBuf> {
+        let changed = self.changed_files();
+        self.stale_with(&changed)
+    }
+
+    /// # Forget the files that changed, returning the [stale scripts](Self::stale_scripts)
+    ///
+    /// Files that were only touched keep their contents, and aren't reported again
+    pub fn refresh(&mut self) -> BTreeSet<PathBuf> {
+        let changed = self.changed_files();
+        let stale = self.stale_with(&changed);
+        // the includes of a changed file are kept until it's read again, so its dependencies
+        // aren't taken for scripts, while created files are recorded again by the parse
+        for path in &changed {
+            if self.files.remove(path).is_none() {
+                for included in self.includes.values_mut() {
+                    included.remove(path);
+                }
+            }
+        }
+        for (path, file) in &mut self.files {
+            if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+                file.modified = Some(modified);
+            }
+        }
+        stale
+    }
+
+    fn stale_with(&self, changed: &BTreeSet<PathBuf>) -> BTreeSet<PathBuf> {
+        self.scripts()
+            .into_iter()
+            .filter(|script| {
+                changed.contains(script) || !self.dependencies(script).is_disjoint(changed)
+            })
+            .collect()
+    }
+}
+
+impl FileCacher for TrackingCache {
+    type FileRecord<'s> = &'s String;
+    fn read_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self::FileRecord<'_>, std::io::Error> {
+        let entry = match self.files.entry(path.as_ref().to_path_buf()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let file = TrackedFile::read(entry.key())?;
+                // the includes are recorded again while the new contents are preprocessed
+                self.includes.remove(entry.key());
+                entry.insert(file)
+            }
+        };
+        Ok(&entry.content)
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.files.contains_key(path.as_ref()) || path.as_ref().is_file()
+    }
+
+    fn record_include(&mut self, from: &Path, included: &Path) {
+        self.includes
+            .entry(from.to_path_buf())
+            .or_default()
+            .insert(included.to_path_buf());
     }
 }
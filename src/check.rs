@@ -0,0 +1,497 @@
+//! # Static analysis of stack effects
+//!
+//! Walks the [code](Code) before execution, following how many values each expression pops and
+//! pushes, to report stack underflows, `(ifs)` whose branches leave different amounts of values
+//! and check blocks that don't leave a single boolean
+//!
+//! The analysis is conservative: rust hooks, closure calls and builtins like `arr$unpack` push
+//! an unknown amount of values, and stop underflows from being reported until the stack is known
+//! again
+//!
+//! ```rust
+//! let token_block = stck::api::get_tokens_str("(fn) [ a b ] sub { a b - }\n1 sub\n", "Check", &mut stck::cache::Isolated::new()).unwrap();
+//! let code = stck::api::parse_raw_tokens(token_block).unwrap();
+//! let diagnostics = stck::check::check(&code);
+//! assert_eq!(diagnostics.len(), 1);
+//! assert_eq!(diagnostics[0].to_string(), "Check:2: `sub` needs 2 values, but the stack has 1");
+//! ```
+
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    #[error("`{name}` needs {needs} values, but the stack has {has}")]
+    Underflow {
+        name: String,
+        needs: usize,
+        has: usize,
+    },
+    #[error("(ifs) branches leave different stack sizes: {}", DisplayDeltas(.deltas))]
+    UnbalancedBranches { deltas: Vec<isize> },
+    #[error("Check block should leave one boolean, but leaves {delta:+} values")]
+    CheckSize { delta: isize },
+    #[error("Check block should leave a boolean, but leaves {got}")]
+    CheckNotBool { got: Value },
+    #[error("`{name}` is a builtin, which is called instead of this function")]
+    ShadowedFn { name: String },
+    #[error("`{name}` is a builtin, which is called instead of reading this argument")]
+    ShadowedArg { name: String },
+}
+
+struct DisplayDeltas<'a>(&'a [isize]);
+
+impl std::fmt::Display for DisplayDeltas<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let deltas: Vec<_> = self.0.iter().map(|d| format!("{d:+}")).collect();
+        write!(f, "[ {} ]", deltas.join(" "))
+    }
+}
+
+/// # A likely runtime error found before execution
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub source: PathBuf,
+    pub span: LineRange,
+    pub kind: DiagnosticKind,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.source.display(), self.span, self.kind)
+    }
+}
+
+/// # Analyze code executed on an empty stack
+#[must_use]
+pub fn check(code: &Code) -> Vec<Diagnostic> {
+    check_with_stack(code, 0)
+}
+
+/// # Analyze code executed on a stack with `stack_size` values
+#[must_use]
+pub fn check_with_stack(code: &Code, stack_size: usize) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    checker.collect_fns(&code.exprs, &code.source);
+    let start = Height {
+        base: None,
+        offset: stack_size as isize,
+    };
+    checker.walk(&code.exprs, &code.source, &Scope::default(), start);
+    let mut diagnostics = resolve::conflicts(code);
+    diagnostics.append(&mut checker.diagnostics);
+    diagnostics
+}
+
+/// # Amount of values on the stack
+///
+/// Unknown effects replace the height with a fresh symbolic `base`, so values pushed after them
+/// can still be compared between branches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Height {
+    base: Option<usize>,
+    offset: isize,
+}
+
+/// # Stack effect of a builtin or function
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Effect {
+    Known {
+        pops: usize,
+        pushes: usize,
+    },
+    /// Pops a known amount of values and pushes an unknown amount
+    PopsThenUnknown(usize),
+    /// Consumes the entire stack, pushing a known amount of values if any
+    AllStack(Option<usize>),
+}
+
+const fn known(pops: usize, pushes: usize) -> Effect {
+    Effect::Known { pops, pushes }
+}
+
+/// # Stack effect of the builtins
+///
+/// Values only peeked, like the array of `&arr$len`, count as popped and pushed back
+pub(crate) fn builtin_effect(name: &str, last_num: Option<isize>) -> Option<Effect> {
+    Some(match name {
+        "print" | "sys$exit" => known(1, 0),
+        "sys$argv" | "stack$len" | "none" | "arr$new" | "map$new" => known(0, 1),
+        "sh" | "get" | "!" | "ok" | "err" | "some" | "str$trim" | "str$into-arr"
+        | "arr$reverse" | "type$is-str" | "type$is-num" | "type$is-bool" | "type$is-array"
+        | "type$is-map" | "type$is-result" | "type$is-option" => known(1, 1),
+        "write-to" | "-" | ".-" | "*" | ".*" | "≃" | "=" | ">" | "%" | "%."
+        | "str$remove-prefix" | "arr$append" | "arr$join" => known(2, 1),
+        "set" => known(2, 0),
+        "&result$is-ok" | "&option$is-some" | "&arr$len" | "arr$pop" => known(1, 2),
+        "&str$has-prefix" | "map$get" => known(2, 2),
+        "map$insert-kv" => known(3, 1),
+        "@" => Effect::PopsThenUnknown(2),
+        "%%" | "arr$unpack" => Effect::PopsThenUnknown(1),
+        "arr$pack-n" => match last_num.and_then(|n| usize::try_from(n).ok()) {
+            Some(n) => known(n + 1, 1),
+            None => Effect::PopsThenUnknown(1),
+        },
+        "debug$stack" | "debug$vars" | "debug$args" | "debug$fns" | "debug$modules"
+        | "debug$generics" => known(0, 0),
+        _ => return None,
+    })
+}
+
+struct FnInfo<'c> {
+    source: &'c Path,
+    args: &'c FnArgs,
+    out_args: Option<&'c Vec<FnArgDef>>,
+    code: &'c [Expr],
+}
+
+/// # Names of the arguments visible to the code
+#[derive(Default, Clone)]
+struct Scope<'c> {
+    args: HashSet<&'c str>,
+}
+
+#[derive(Default)]
+struct Checker<'c> {
+    fns: HashMap<&'c str, FnInfo<'c>>,
+    /// Amount of values pushed by each analyzed function body, keyed by its address
+    pushes: HashMap<*const Expr, Option<usize>>,
+    in_progress: HashSet<*const Expr>,
+    next_base: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'c> Checker<'c> {
+    fn collect_fns(&mut self, exprs: &'c [Expr], source: &'c Path) {
+        for expr in exprs {
+            match &expr.cont {
+                ExprCont::Keyword(KeywordKind::FnDef {
+                    name,
+                    code,
+                    args,
+                    out_args,
+                    ..
+                }) => {
+                    self.fns.insert(
+                        name,
+                        FnInfo {
+                            source,
+                            args,
+                            out_args: out_args.as_ref(),
+                            code,
+                        },
+                    );
+                    self.collect_fns(code, source);
+                }
+                ExprCont::IncludedCode(Code { source, exprs }) => self.collect_fns(exprs, source),
+                _ => {}
+            }
+        }
+    }
+
+    fn unknown(&mut self) -> Height {
+        self.next_base += 1;
+        Height {
+            base: Some(self.next_base),
+            offset: 0,
+        }
+    }
+
+    fn report(&mut self, source: &Path, expr: &Expr, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            source: source.to_path_buf(),
+            span: expr.span.clone(),
+            kind,
+        });
+    }
+
+    /// # Pop values, reporting an underflow if the stack is known to be too small
+    fn pop(&mut self, h: Height, n: usize, name: &str, source: &Path, expr: &Expr) -> Height {
+        let offset = h.offset - n as isize;
+        if h.base.is_none() && offset < 0 {
+            let kind = DiagnosticKind::Underflow {
+                name: name.to_string(),
+                needs: n,
+                has: h.offset as usize,
+            };
+            self.report(source, expr, kind);
+            // keep checking as if the missing values were there
+            return Height {
+                base: None,
+                offset: 0,
+            };
+        }
+        Height { offset, ..h }
+    }
+
+    fn apply(
+        &mut self,
+        effect: Effect,
+        h: Height,
+        name: &str,
+        source: &Path,
+        expr: &Expr,
+    ) -> Height {
+        match effect {
+            Effect::Known { pops, pushes } => {
+                let h = self.pop(h, pops, name, source, expr);
+                Height {
+                    offset: h.offset + pushes as isize,
+                    ..h
+                }
+            }
+            Effect::PopsThenUnknown(pops) => {
+                self.pop(h, pops, name, source, expr);
+                self.unknown()
+            }
+            Effect::AllStack(Some(pushes)) => Height {
+                base: None,
+                offset: pushes as isize,
+            },
+            Effect::AllStack(None) => self.unknown(),
+        }
+    }
+
+    /// # Amount of values left by a function, `None` if unknown
+    fn fn_pushes(&mut self, info: &FnInfo<'c>) -> Option<usize> {
+        if let Some(out) = info.out_args {
+            return Some(out.len());
+        }
+        let key = info.code.as_ptr();
+        if let Some(pushes) = self.pushes.get(&key) {
+            return *pushes;
+        }
+        if !self.in_progress.insert(key) {
+            // recursive function without typed outputs
+            return None;
+        }
+        let (scope, start) = match info.args {
+            FnArgs::Args(args) => (
+                Scope {
+                    args: args.iter().map(FnArgDef::get_name).collect(),
+                },
+                Height {
+                    base: None,
+                    offset: 0,
+                },
+            ),
+            FnArgs::AllStack => (Scope::default(), self.unknown()),
+        };
+        let end = self.walk(info.code, info.source, &scope, start);
+        self.in_progress.remove(&key);
+        let pushes = match end {
+            Some(Height { base: None, offset }) => usize::try_from(offset).ok(),
+            _ => None,
+        };
+        self.pushes.insert(key, pushes);
+        pushes
+    }
+
+    fn call_effect(
+        &mut self,
+        name: &str,
+        scope: &Scope,
+        last_num: Option<isize>,
+    ) -> Option<Effect> {
+        // same precedence as the runtime: builtins, arguments, user functions
+        if let Some(effect) = builtin_effect(name, last_num) {
+            return Some(effect);
+        }
+        if scope.args.contains(name) {
+            return Some(known(0, 1));
+        }
+        let (args, out_args, source, code) = {
+            let info = self.fns.get(name)?;
+            (info.args, info.out_args, info.source, info.code)
+        };
+        let pushes = self.fn_pushes(&FnInfo {
+            source,
+            args,
+            out_args,
+            code,
+        });
+        Some(match (args, pushes) {
+            (FnArgs::Args(args), Some(pushes)) => known(args.len(), pushes),
+            (FnArgs::Args(args), None) => Effect::PopsThenUnknown(args.len()),
+            (FnArgs::AllStack, pushes) => Effect::AllStack(pushes),
+        })
+    }
+
+    /// # Follow the stack through the expressions
+    ///
+    /// Returns `None` if the code always returns or breaks
+    fn walk(
+        &mut self,
+        exprs: &'c [Expr],
+        source: &'c Path,
+        scope: &Scope<'c>,
+        mut h: Height,
+    ) -> Option<Height> {
+        let mut last_num = None;
+        for expr in exprs {
+            h = match &expr.cont {
+                ExprCont::Immediate(Value::Closure(cl)) => {
+                    let mut inner = scope.clone();
+                    inner.args.extend(
+                        cl.get_args()
+                            .get_unfilled_args()
+                            .iter()
+                            .map(FnArgDef::get_name),
+                    );
+                    let start = Height {
+                        base: None,
+                        offset: 0,
+                    };
+                    self.walk(&cl.code, source, &inner, start);
+                    Height {
+                        offset: h.offset + 1,
+                        ..h
+                    }
+                }
+                ExprCont::Immediate(_) => Height {
+                    offset: h.offset + 1,
+                    ..h
+                },
+                ExprCont::FnCall(name, _) => match self.call_effect(name, scope, last_num) {
+                    Some(effect) => self.apply(effect, h, name, source, expr),
+                    // rust hooks and missing identifiers
+                    None => self.unknown(),
+                },
+                ExprCont::IncludedCode(Code { source, exprs }) => {
+                    self.walk(exprs, source, scope, h)?
+                }
+                ExprCont::Keyword(kw) => self.walk_kw(kw, expr, source, scope, h)?,
+            };
+            last_num = match &expr.cont {
+                ExprCont::Immediate(Value::Num(n)) => Some(*n),
+                _ => None,
+            };
+        }
+        Some(h)
+    }
+
+    fn walk_kw(
+        &mut self,
+        kw: &'c KeywordKind,
+        expr: &'c Expr,
+        source: &'c Path,
+        scope: &Scope<'c>,
+        h: Height,
+    ) -> Option<Height> {
+        Some(match kw {
+            KeywordKind::Return | KeywordKind::Break => return None,
+            KeywordKind::BubbleError => self.apply(known(1, 1), h, "(!)", source, expr),
+            KeywordKind::IntoClosure { .. } => Height {
+                offset: h.offset + 1,
+                ..h
+            },
+            KeywordKind::DefinedGeneric(_) | KeywordKind::Require(_) => h,
+            KeywordKind::FnDef {
+                code,
+                args,
+                out_args,
+                ..
+            } => {
+                self.fn_pushes(&FnInfo {
+                    source,
+                    args,
+                    out_args: out_args.as_ref(),
+                    code,
+                });
+                h
+            }
+            KeywordKind::Ifs { branches } => {
+                let mut ends = Vec::new();
+                let mut fallthrough = h;
+                for branch in branches {
+                    let after_check =
+                        self.walk_check(&branch.check, expr, source, scope, fallthrough);
+                    ends.push(self.walk(&branch.code, source, scope, after_check));
+                    fallthrough = after_check;
+                }
+                self.report_unbalanced(&ends, h, expr, source);
+                // the fallthrough isn't reported, since the last check is usually `true`
+                ends.push(Some(fallthrough));
+                self.merge(&ends)?
+            }
+            KeywordKind::Switch { cases, default } => {
+                let h = self.pop(h, 1, "(switch)", source, expr);
+                let mut ends: Vec<_> = cases
+                    .iter()
+                    .map(|case| self.walk(&case.code, source, scope, h))
+                    .collect();
+                ends.push(match default {
+                    Some(code) => self.walk(code, source, scope, h),
+                    None => Some(h),
+                });
+                self.merge(&ends)?
+            }
+            KeywordKind::While { check, code } => {
+                let after_check = self.walk_check(check, expr, source, scope, h);
+                match self.walk(code, source, scope, after_check) {
+                    Some(end) if end == after_check => after_check,
+                    None => after_check,
+                    Some(_) => self.unknown(),
+                }
+            }
+        })
+    }
+
+    /// # Follow a check block, which must push a single boolean that is then consumed
+    fn walk_check(
+        &mut self,
+        check: &'c [Expr],
+        expr: &'c Expr,
+        source: &'c Path,
+        scope: &Scope<'c>,
+        h: Height,
+    ) -> Height {
+        let Some(end) = self.walk(check, source, scope, h) else {
+            return h;
+        };
+        if end.base == h.base {
+            let delta = end.offset - h.offset;
+            if delta != 1 {
+                self.report(source, expr, DiagnosticKind::CheckSize { delta });
+                return h;
+            }
+        }
+        if let Some(Expr {
+            cont: ExprCont::Immediate(got),
+            ..
+        }) = check.last()
+            && !matches!(got, Value::Bool(_))
+        {
+            let kind = DiagnosticKind::CheckNotBool { got: got.clone() };
+            self.report(source, expr, kind);
+        }
+        h
+    }
+
+    fn report_unbalanced(
+        &mut self,
+        ends: &[Option<Height>],
+        start: Height,
+        expr: &Expr,
+        source: &Path,
+    ) {
+        let ends: Vec<_> = ends.iter().flatten().collect();
+        let comparable = ends.iter().all(|e| e.base == start.base);
+        if comparable && ends.windows(2).any(|w| w[0] != w[1]) {
+            let deltas = ends.iter().map(|e| e.offset - start.offset).collect();
+            self.report(source, expr, DiagnosticKind::UnbalancedBranches { deltas });
+        }
+    }
+
+    /// # Height after one of many branches, `None` if every branch returns
+    fn merge(&mut self, ends: &[Option<Height>]) -> Option<Height> {
+        let ends: Vec<_> = ends.iter().flatten().collect();
+        let (first, rest) = ends.split_first()?;
+        if rest.iter().all(|e| e == first) {
+            Some(**first)
+        } else {
+            Some(self.unknown())
+        }
+    }
+}
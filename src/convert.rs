@@ -0,0 +1,392 @@
+//! # Conversion between rust types and stck [values](Value)
+//!
+//! Used by [typed hooks](crate::internals::StckHook::typed) to pop their arguments and push
+//! their outputs. With the `derive` feature, `#[derive(StckValue)]` implements both
+//! conversions for host structs and enums
+
+use crate::*;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+#[cfg(feature = "derive")]
+pub use stck_derive::StckValue;
+
+/// # A rust type that can be read from a stck [value](Value)
+pub trait FromValue: Sized {
+    /// # Type of the values accepted by [`FromValue::from_value`]
+    fn type_tester() -> TypeTester;
+    /// # Convert the value, failing if it doesn't have the right shape
+    fn from_value(value: Value) -> Result<Self, FromValueError>;
+}
+
+/// # A value that couldn't be converted by [`FromValue`]
+///
+/// The path names the fields, indexes and keys leading to the failing part of the value,
+/// like `config.ports[2]`, and is empty when the value itself failed
+#[derive(Debug, Clone)]
+pub struct FromValueError {
+    pub path: String,
+    pub kind: FromValueErrorKind,
+}
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum FromValueErrorKind {
+    #[error("must be a {expected} and got {got}")]
+    WrongType {
+        expected: TypeTester,
+        got: Box<Value>,
+    },
+    #[error("is missing")]
+    MissingField,
+    #[error("has {got} items, but {expected} are needed")]
+    WrongLength { expected: usize, got: usize },
+    #[error("has the unknown variant `{0}`")]
+    UnknownVariant(String),
+}
+
+impl std::fmt::Display for FromValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "`{}` {}", self.path, self.kind)
+        }
+    }
+}
+
+impl std::error::Error for FromValueError {}
+
+impl FromValueError {
+    #[must_use]
+    pub fn new(kind: FromValueErrorKind) -> Self {
+        Self {
+            path: String::new(),
+            kind,
+        }
+    }
+    #[must_use]
+    pub fn wrong_type(expected: TypeTester, got: Value) -> Self {
+        Self::new(FromValueErrorKind::WrongType {
+            expected,
+            got: Box::new(got),
+        })
+    }
+    /// # The error happened inside the field of a struct
+    #[must_use]
+    pub fn in_field(self, name: &str) -> Self {
+        self.prefixed(name)
+    }
+    /// # The error happened inside an item of an array
+    #[must_use]
+    pub fn at_index(self, index: usize) -> Self {
+        self.prefixed(&format!("[{index}]"))
+    }
+    /// # The error happened inside an entry of a map
+    #[must_use]
+    pub fn at_key(self, key: &str) -> Self {
+        self.prefixed(&format!("[{key:?}]"))
+    }
+    fn prefixed(mut self, prefix: &str) -> Self {
+        if !self.path.is_empty() && !self.path.starts_with('[') {
+            self.path.insert(0, '.');
+        }
+        self.path.insert_str(0, prefix);
+        self
+    }
+}
+
+/// # A rust type that can be made into a stck [value](Value)
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// # The outputs of a [typed hook](crate::internals::StckHook::typed)
+///
+/// Every [`IntoValue`] pushes a single value, `()` pushes nothing and tuples push each of
+/// their items in order
+pub trait IntoValues {
+    fn into_values(self) -> Vec<Value>;
+}
+
+impl<T: IntoValue> IntoValues for T {
+    fn into_values(self) -> Vec<Value> {
+        vec![self.into_value()]
+    }
+}
+
+impl IntoValues for () {
+    fn into_values(self) -> Vec<Value> {
+        vec![]
+    }
+}
+
+macro_rules! tuple_into_values {
+    ($($t:ident $v:ident),+) => {
+        impl<$($t: IntoValue),+> IntoValues for ($($t,)+) {
+            fn into_values(self) -> Vec<Value> {
+                let ($($v,)+) = self;
+                vec![$($v.into_value()),+]
+            }
+        }
+    };
+}
+
+tuple_into_values!(A a, B b);
+tuple_into_values!(A a, B b, C c);
+tuple_into_values!(A a, B b, C c, D d);
+
+macro_rules! primitive {
+    ($t:ty, $tt:expr, $get:path) => {
+        impl FromValue for $t {
+            fn type_tester() -> TypeTester {
+                $tt
+            }
+            fn from_value(value: Value) -> Result<Self, FromValueError> {
+                $get(value).map_err(|got| FromValueError::wrong_type($tt, got))
+            }
+        }
+        impl IntoValue for $t {
+            fn into_value(self) -> Value {
+                Value::from(self)
+            }
+        }
+    };
+}
+
+primitive!(isize, TypeTester::Num, Value::get_num);
+primitive!(f64, TypeTester::Float, Value::get_float);
+primitive!(bool, TypeTester::Bool, Value::get_bool);
+primitive!(String, TypeTester::Str, Value::get_str);
+primitive!(char, TypeTester::Char, Value::get_char);
+
+impl FromValue for Value {
+    fn type_tester() -> TypeTester {
+        TypeTester::Any
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::from(self.to_string())
+    }
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl FromValue for $t {
+            fn type_tester() -> TypeTester {
+                TypeTester::Num
+            }
+            fn from_value(value: Value) -> Result<Self, FromValueError> {
+                match value {
+                    Value::Num(n) => {
+                        <$t>::try_from(n).map_err(|_| FromValueError::wrong_type(TypeTester::Num, Value::Num(n)))
+                    }
+                    got => Err(FromValueError::wrong_type(TypeTester::Num, got)),
+                }
+            }
+        }
+        // values above `isize::MAX` are saturated instead of wrapping to negative numbers
+        impl IntoValue for $t {
+            fn into_value(self) -> Value {
+                Value::Num(isize::try_from(self).unwrap_or(isize::MAX))
+            }
+        }
+    )*};
+}
+
+integer!(i8, i16, i32, u8, u16, u32, usize);
+
+impl FromValue for f32 {
+    fn type_tester() -> TypeTester {
+        TypeTester::Float
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        f64::from_value(value).map(|f| f as f32)
+    }
+}
+
+impl IntoValue for f32 {
+    fn into_value(self) -> Value {
+        Value::Float(f64::from(self))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn type_tester() -> TypeTester {
+        TypeTester::Array(Box::new(T::type_tester()))
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        let items = value
+            .get_arr()
+            .map_err(|got| FromValueError::wrong_type(Self::type_tester(), got))?;
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(idx, item)| T::from_value(item).map_err(|e| e.at_index(idx)))
+            .collect()
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::Array(Arc::new(
+            self.into_iter().map(IntoValue::into_value).collect(),
+        ))
+    }
+}
+
+impl<T: FromValue, S: BuildHasher + Default> FromValue for HashMap<String, T, S> {
+    fn type_tester() -> TypeTester {
+        TypeTester::Map(Box::new(T::type_tester()))
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        let entries = value
+            .get_map()
+            .map_err(|got| FromValueError::wrong_type(Self::type_tester(), got))?;
+        entries
+            .into_iter()
+            .map(|(key, v)| match T::from_value(v) {
+                Ok(v) => Ok((key, v)),
+                Err(e) => Err(e.at_key(&key)),
+            })
+            .collect()
+    }
+}
+
+impl<T: IntoValue, S: BuildHasher> IntoValue for HashMap<String, T, S> {
+    fn into_value(self) -> Value {
+        Value::Map(Arc::new(
+            self.into_iter().map(|(k, v)| (k, v.into_value())).collect(),
+        ))
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn type_tester() -> TypeTester {
+        TypeTester::Option(Box::new(T::type_tester()))
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        let option = value
+            .get_option()
+            .map_err(|got| FromValueError::wrong_type(Self::type_tester(), got))?;
+        option.map(|v| T::from_value(*v)).transpose()
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        Value::Option(self.map(|v| Box::new(v.into_value())))
+    }
+}
+
+impl<T: FromValue, E: FromValue> FromValue for Result<T, E> {
+    fn type_tester() -> TypeTester {
+        TypeTester::Result(Box::new((T::type_tester(), E::type_tester())))
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        let result = value
+            .get_result()
+            .map_err(|got| FromValueError::wrong_type(Self::type_tester(), got))?;
+        Ok(match result {
+            Ok(v) => Ok(T::from_value(v)?),
+            Err(e) => Err(E::from_value(e)?),
+        })
+    }
+}
+
+impl<T: IntoValue, E: IntoValue> IntoValue for Result<T, E> {
+    fn into_value(self) -> Value {
+        Value::Result(Box::new(match self {
+            Ok(v) => Ok(v.into_value()),
+            Err(e) => Err(e.into_value()),
+        }))
+    }
+}
+
+impl<T: FromValue> FromValue for Box<T> {
+    fn type_tester() -> TypeTester {
+        T::type_tester()
+    }
+    fn from_value(value: Value) -> Result<Self, FromValueError> {
+        T::from_value(value).map(Box::new)
+    }
+}
+
+impl<T: IntoValue> IntoValue for Box<T> {
+    fn into_value(self) -> Value {
+        (*self).into_value()
+    }
+}
+
+/// # Helpers for the code made by `#[derive(StckValue)]`
+#[doc(hidden)]
+pub mod derive {
+    use super::{FromValue, FromValueError, FromValueErrorKind};
+    use crate::{TypeTester, Value};
+    use std::collections::HashMap;
+    use std::hash::BuildHasher;
+
+    pub fn map(value: Value) -> Result<HashMap<String, Value>, FromValueError> {
+        value
+            .get_map()
+            .map_err(|got| FromValueError::wrong_type(TypeTester::MapAny, got))
+    }
+
+    /// # Items of a tuple struct
+    pub fn array(value: Value, expected: usize) -> Result<Vec<Value>, FromValueError> {
+        let items = value
+            .get_arr()
+            .map_err(|got| FromValueError::wrong_type(TypeTester::ArrayAny, got))?;
+        if items.len() == expected {
+            Ok(items)
+        } else {
+            let got = items.len();
+            Err(FromValueError::new(FromValueErrorKind::WrongLength {
+                expected,
+                got,
+            }))
+        }
+    }
+
+    /// # Take a field out of the map, missing options are `None`
+    pub fn field<T: FromValue, S: BuildHasher>(
+        map: &mut HashMap<String, Value, S>,
+        name: &str,
+    ) -> Result<T, FromValueError> {
+        let value = match map.remove(name) {
+            Some(value) => value,
+            None if matches!(T::type_tester(), TypeTester::Option(_)) => Value::Option(None),
+            None => {
+                return Err(FromValueError::new(FromValueErrorKind::MissingField).in_field(name));
+            }
+        };
+        T::from_value(value).map_err(|e| e.in_field(name))
+    }
+
+    pub fn item<T: FromValue>(
+        items: &mut std::vec::IntoIter<Value>,
+        index: usize,
+    ) -> Result<T, FromValueError> {
+        let value = items.next().expect("length was checked");
+        T::from_value(value).map_err(|e| e.at_index(index))
+    }
+
+    #[must_use]
+    pub fn unknown_variant(tag: String) -> FromValueError {
+        FromValueError::new(FromValueErrorKind::UnknownVariant(tag)).in_field(TAG)
+    }
+
+    /// # Key that holds the variant of an enum
+    pub const TAG: &str = "tag";
+}
@@ -86,7 +86,7 @@ impl Display for ExprCont {
             Self::Immediate(v) => {
                 write!(f, "Push value {v:?}")
             }
-            Self::FnCall(fn_name) => {
+            Self::FnCall(fn_name, _) => {
                 write!(f, "Execute `{}`", fn_name.bright_yellow())
             }
             Self::IncludedCode(code) => {
@@ -242,17 +242,16 @@ impl Display for Value {
             },
             Value::Array(a) => {
                 write!(f, "{}<", "Array".bright_yellow())?;
-                let last_idx = a.len() - 1;
                 for (idx, v) in a.iter().enumerate() {
-                    if idx == last_idx {
+                    if idx == 0 {
                         write!(f, "{v}")?;
                     } else {
-                        write!(f, "{v} ")?;
+                        write!(f, " {v}")?;
                     }
                 }
                 write!(f, ">")
             }
-            Value::Map(m) => f.debug_map().entries(m).finish(),
+            Value::Map(m) => f.debug_map().entries(m.iter()).finish(),
             Value::Closure(c) => write!(f, "Closure <...> -> <...> @ {c:p}"),
         }
     }
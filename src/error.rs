@@ -3,7 +3,6 @@
 use super::*;
 use crate::cache::FileCacher;
 use colored::Colorize;
-use std::collections::hash_map::HashMap;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 
@@ -148,6 +147,16 @@ impl From<Range<usize>> for LineRange {
 }
 
 impl LineRange {
+    /// # First line, starting from one
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    /// # Last line, same as [start](LineRange::start) for single line spans
+    #[must_use]
+    pub fn end(&self) -> usize {
+        self.end
+    }
     pub(crate) fn delta(&self) -> usize {
         self.end - self.start
     }
@@ -204,6 +213,57 @@ pub enum StckError {
     BuiltinModuleWithoutBang(String),
     #[error("Hosts can't make modules with the # prefix (sign of builtin module)")]
     UserModuleWithBang(String),
+    #[error("{}:{span}: {error}", file.display())]
+    Spanned {
+        file: PathBuf,
+        span: LineRange,
+        error: Box<StckError>,
+    },
+}
+
+impl StckError {
+    /// # Attach the lines of the file that caused the error
+    ///
+    /// Errors that already have a span keep it, since it points to the innermost file
+    #[must_use]
+    pub fn spanned(self, file: &Path, span: LineRange) -> Self {
+        match self {
+            e @ StckError::Spanned { .. } => e,
+            e => StckError::Spanned {
+                file: file.to_path_buf(),
+                span,
+                error: Box::new(e),
+            },
+        }
+    }
+
+    /// # File and lines that caused the error, if known
+    #[must_use]
+    pub fn span(&self) -> Option<(&Path, &LineRange)> {
+        match self {
+            StckError::Spanned { file, span, .. } => Some((file, span)),
+            _ => None,
+        }
+    }
+
+    /// # The error without the file and lines that caused it
+    #[must_use]
+    pub fn unspanned(&self) -> &StckError {
+        match self {
+            StckError::Spanned { error, .. } => error,
+            e => e,
+        }
+    }
+}
+
+impl Error {
+    #[must_use]
+    pub(crate) fn spanned(self, file: &Path, span: LineRange) -> Self {
+        match self {
+            Error::Anoter(e) => Error::Anoter(e.spanned(file, span)),
+            e @ Error::RuntimeError(_) => e,
+        }
+    }
 }
 
 /// # A runtime error
@@ -219,6 +279,12 @@ pub enum RuntimeErrorKind {
         got: Vec<Value>,
         needs: Vec<String>,
     },
+    #[error("Too many arguments to execute {name}, got {got} needs {expected}")]
+    TooManyArgs {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
     #[error("Found {} while executing `!` on a Result: {error}", "Error".bright_yellow())]
     UnwrapResultBuiltinFailed { error: Value },
     #[error("Found missing value while exeuting `!` on an Option")]
@@ -301,10 +367,39 @@ pub enum RuntimeErrorKind {
     )]
     DEVResettingParentValuesForClosure {
         closure_args: Box<ClosurePartialArgs>,
-        parent_args: HashMap<ArgName, FnArg>,
+        parent_args: Args,
     },
     #[error("No such function or function argument called `{0}`")]
     MissingIdent(String),
     #[error("Module `{0}` is required but was not loaded")]
     MissingModule(String),
+    #[error("Executed more than {limit} expressions")]
+    ExprLimitExceeded { limit: usize },
+    #[error("Function calls nested deeper than the limit of {limit}")]
+    CallDepthExceeded { limit: usize },
+    #[error("The stack holds {got} values, more than the limit of {limit}")]
+    StackSizeExceeded { limit: usize, got: usize },
+    #[error("The stack's values allocate {got} units, more than the limit of {limit}")]
+    AllocLimitExceeded { limit: usize, got: usize },
+    #[error("The host doesn't allow {0}")]
+    CapabilityDenied(crate::internals::Capability),
+    #[error("Couldn't write the output: {0}")]
+    Output(std::io::Error),
+    #[error("The script was {0}")]
+    Interrupted(crate::internals::Interruption),
+    #[error("Function {for_fn} accepts {args}. But {missing} args are missing")]
+    MissingValuesForHook {
+        for_fn: String,
+        args: String,
+        missing: usize,
+    },
+    #[error("Function {for_fn} accepts {args}. But argument #{this_arg} {error}")]
+    WrongTypeForHook {
+        for_fn: String,
+        args: String,
+        this_arg: usize,
+        error: Box<crate::convert::FromValueError>,
+    },
+    #[error("Rust hook called itself while it was still running")]
+    ReentrantHook,
 }
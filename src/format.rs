@@ -0,0 +1,216 @@
+use crate::{StckError, Token, TokenCont, token};
+use std::path::Path;
+
+const INDENT: char = '\t';
+
+/// # Format a stck script
+///
+/// Blocks are indented with tabs by their depth, tokens of a line are separated by a single
+/// space and runs of blank lines become a single one. Comments and the line each token is on
+/// are kept, so a block that fits in one line stays in one line
+///
+/// ```rust
+/// let formatted = stck::format::format("(fn) [a]  double {\n      a 2 *  # twice\n}\n", "fmt").unwrap();
+/// assert_eq!(formatted, "(fn) [ a ] double {\n\ta 2 * # twice\n}\n");
+/// ```
+pub fn format(cont: &str, content_name: impl AsRef<Path>) -> Result<String, StckError> {
+    let block = token::Context::new(cont)
+        .keep_layout()
+        .tokenize(content_name.as_ref().to_path_buf())?;
+    let mut writer = Writer::default();
+    writer.write_tokens(&block.tokens, 0);
+    if !writer.out.is_empty() {
+        writer.out.push('\n');
+    }
+    Ok(writer.out)
+}
+
+/// # Check if a script is already [formatted](format)
+pub fn is_formatted(cont: &str, content_name: impl AsRef<Path>) -> Result<bool, StckError> {
+    Ok(format(cont, content_name)? == cont)
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    // source line where the last written token ends
+    line: usize,
+    // indentation of the last written line
+    line_depth: usize,
+    // the last token can have a comma glued to it, as in manual arrays
+    last_was_value: bool,
+    // just wrote a '{', so no blank line
+    opened_block: bool,
+}
+
+/// Branches of an `(ifs)` or `(switch)`, written one level deeper when on their own lines
+#[derive(Clone, Copy)]
+enum Branches {
+    Ifs,
+    SwitchCase,
+    SwitchCode,
+    Ended,
+}
+
+impl Branches {
+    fn new(keyword: &str) -> Option<Self> {
+        match keyword {
+            "(ifs)" => Some(Branches::Ifs),
+            "(switch)" => Some(Branches::SwitchCase),
+            _ => None,
+        }
+    }
+
+    // returns false once the token no longer belongs to the branches
+    fn accepts(&mut self, cont: &TokenCont) -> bool {
+        let is_block = matches!(cont, TokenCont::Block(_));
+        let is_case = match cont {
+            TokenCont::Number(_) => true,
+            TokenCont::Verbatim(v) => v.starts_with(['\'', '"']),
+            _ => false,
+        };
+        let (accepts, next) = match *self {
+            Branches::Ended => (false, Branches::Ended),
+            Branches::Ifs => (is_block, Branches::Ifs),
+            Branches::SwitchCase if is_case => (true, Branches::SwitchCode),
+            // the default case ends the switch
+            Branches::SwitchCase => (is_block, Branches::Ended),
+            Branches::SwitchCode => (is_block, Branches::SwitchCase),
+        };
+        *self = next;
+        accepts
+    }
+}
+
+impl Writer {
+    fn write_tokens(&mut self, tokens: &[Token], depth: usize) {
+        let mut branches: Option<Branches> = None;
+        for (idx, token) in tokens.iter().enumerate() {
+            if let TokenCont::Comment(_) = token.cont {
+                // comments go with the next token
+                let next = tokens[idx..]
+                    .iter()
+                    .find(|t| !matches!(t.cont, TokenCont::Comment(_)));
+                if let (Some(mut b), Some(next)) = (branches, next)
+                    && !b.accepts(&next.cont)
+                {
+                    branches = None;
+                }
+            } else if branches.as_mut().is_some_and(|b| !b.accepts(&token.cont)) {
+                branches = None;
+            }
+            let token_depth = depth + usize::from(branches.is_some());
+            match &token.cont {
+                TokenCont::EndOfBlock => break,
+                TokenCont::Block(inner) => {
+                    self.separate(token.span.start, token_depth);
+                    self.out.push('{');
+                    self.line = token.span.start;
+                    self.write_block(inner, self.line_depth);
+                }
+                TokenCont::Ident(comma)
+                    if comma == "," && self.last_was_value && token.span.start == self.line =>
+                {
+                    self.out.push(',');
+                }
+                cont => {
+                    self.separate(token.span.start, token_depth);
+                    write_token(&mut self.out, cont);
+                }
+            }
+            if let TokenCont::Verbatim(keyword) = &token.cont {
+                branches = Branches::new(keyword).or(branches);
+            }
+            self.line = token.span.end;
+            self.last_was_value = matches!(
+                token.cont,
+                TokenCont::Number(_) | TokenCont::Float(_) | TokenCont::Ident(_)
+            );
+        }
+    }
+
+    // just wrote the '{' of the block, on a line indented by depth
+    fn write_block(&mut self, tokens: &[Token], depth: usize) {
+        let Some((end, inner)) = tokens.split_last() else {
+            return;
+        };
+        let start = self.line;
+        self.opened_block = true;
+        self.write_tokens(inner, depth + 1);
+        self.opened_block = false;
+        if !inner.is_empty() {
+            // blocks written in many lines end in their own line
+            if end.span.start > self.line || self.line > start {
+                self.new_line(depth);
+            } else {
+                self.out.push(' ');
+            }
+        }
+        self.out.push('}');
+    }
+
+    fn separate(&mut self, line: usize, depth: usize) {
+        let opened_block = std::mem::take(&mut self.opened_block);
+        if self.out.is_empty() {
+            return;
+        }
+        if line > self.line {
+            if line > self.line + 1 && !opened_block {
+                self.out.push('\n');
+            }
+            self.new_line(depth);
+        } else {
+            self.out.push(' ');
+        }
+    }
+
+    fn new_line(&mut self, depth: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(INDENT, depth));
+        self.line_depth = depth;
+    }
+}
+
+fn write_token(out: &mut String, cont: &TokenCont) {
+    match cont {
+        TokenCont::Ident(ident) => out.push_str(ident),
+        TokenCont::Number(num) => out.push_str(&num.to_string()),
+        TokenCont::Float(num) => {
+            let num = num.to_string();
+            out.push_str(&num);
+            if !num.contains('.') {
+                out.push_str(".0");
+            }
+        }
+        TokenCont::Comment(comment) => {
+            out.push('#');
+            out.push_str(comment.trim_end());
+        }
+        TokenCont::Verbatim(text) => out.push_str(&normalize_verbatim(text)),
+        // only made without keeping the layout, or by the preprocessor
+        TokenCont::Str(_)
+        | TokenCont::Char(_)
+        | TokenCont::Keyword(_)
+        | TokenCont::FnArgs(_)
+        | TokenCont::Block(_)
+        | TokenCont::IncludedBlock(_)
+        | TokenCont::EndOfBlock => unreachable!("token without layout: {cont:?}"),
+    }
+}
+
+// keywords and argument lists get single spaces, strings and chars are kept as they are
+fn normalize_verbatim(text: &str) -> String {
+    let words = |inner: &str| inner.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        format!("({})", words(inner))
+    } else if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let inner = words(inner);
+        if inner.is_empty() {
+            "[]".to_string()
+        } else {
+            format!("[ {inner} ]")
+        }
+    } else {
+        text.to_string()
+    }
+}
@@ -4,24 +4,67 @@
 
 use super::*;
 
+pub use crate::symbol::Symbol;
+pub use runtime::BUILTINS;
+pub use runtime::Builtin;
 pub use runtime::Context as RuntimeContext;
 pub use runtime::Hook as StckHook;
+pub use runtime::Limits;
+pub use runtime::TypedHook;
 pub use runtime::module;
-use std::cell::OnceCell;
+pub use runtime::{Breakpoints, DebugHook, Debugger, Pause, PauseReason, Step};
+pub use runtime::{Capabilities, Capability};
+pub use runtime::{Capture, Interrupt, Interruption};
+pub use runtime::{Task, TaskState};
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+pub use types::TypeTester;
 
+/// # Block of expressions
+///
+/// Shared, so that functions, closures and running [tasks](runtime::Task) hold on to their code
+/// without copying it
+pub type Exprs = Arc<[Expr]>;
+
+/// # Parsed script, ready to be executed
+///
+/// Code is `Send + Sync` and cheap to clone, so a script can be parsed once and executed by
+/// many independent [contexts](runtime::Context), each on its own thread
+///
+/// ```rust
+/// use stck::internals::{RuntimeContext, Value};
+/// let token_block = stck::api::get_tokens_str("(fn) [ n ] double { n 2 * }\n21 double\n", "Shared", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             let mut ctx = RuntimeContext::new();
+///             ctx.execute_entire_code(&code).unwrap();
+///             assert_eq!(ctx.get_stack(), [Value::Num(42)]);
+///         });
+///     }
+/// });
+/// ```
 #[cfg_attr(test, derive(PartialEq))]
 #[derive(Clone, Debug)]
 pub struct Code {
     pub(crate) source: PathBuf,
-    pub(crate) exprs: Vec<Expr>,
+    pub(crate) exprs: Exprs,
 }
 
 impl Code {
     #[must_use]
     pub fn new(source: PathBuf, exprs: Vec<Expr>) -> Self {
-        Code { source, exprs }
+        Code {
+            source,
+            exprs: exprs.into(),
+        }
+    }
+    #[must_use]
+    pub fn source(&self) -> &Path {
+        &self.source
     }
     #[must_use]
     pub fn expr_count(&self) -> usize {
@@ -65,10 +108,11 @@ impl FnArgDef {
         Self { name, type_check }
     }
     #[must_use]
-    pub(crate) fn get_name(&self) -> &str {
+    pub fn get_name(&self) -> &str {
         &self.name
     }
-    pub(crate) fn get_type(&self) -> Option<&TypeTester> {
+    #[must_use]
+    pub fn get_type(&self) -> Option<&TypeTester> {
         self.type_check.as_ref()
     }
     pub(crate) fn take_type(self) -> Option<TypeTester> {
@@ -101,7 +145,7 @@ enum ClosureFillError {
 pub struct ClosurePartialArgs {
     pub(crate) next: Vec<FnArgDef>,
     pub(crate) filled: Vec<(ArgName, Value)>,
-    parent: OnceCell<HashMap<ArgName, FnArg>>,
+    parent: OnceLock<Args>,
 }
 
 impl ClosurePartialArgs {
@@ -114,7 +158,7 @@ impl ClosurePartialArgs {
     pub fn get_unfilled_args_count(&self) -> usize {
         self.next.len()
     }
-    fn set_parent(&self, args: HashMap<String, FnArg>) -> Result<(), HashMap<ArgName, FnArg>> {
+    fn set_parent(&self, args: Args) -> Result<(), Args> {
         self.parent.set(args)
     }
     #[must_use]
@@ -123,7 +167,7 @@ impl ClosurePartialArgs {
         ClosurePartialArgs {
             filled: Vec::with_capacity(arg_list.len()),
             next: arg_list,
-            parent: OnceCell::new(),
+            parent: OnceLock::new(),
         }
     }
     pub fn parse(arg_list: Vec<FnArgDef>, span: LineRange) -> Result<Self, StckError> {
@@ -133,6 +177,14 @@ impl ClosurePartialArgs {
             Ok(Self::new(arg_list))
         }
     }
+    /// # Arguments left to fill, filled from the last one, and the ones already filled
+    pub(crate) fn from_parts(next: Vec<FnArgDef>, filled: Vec<(ArgName, Value)>) -> Self {
+        ClosurePartialArgs {
+            next,
+            filled,
+            parent: OnceLock::new(),
+        }
+    }
     pub fn convert(arg_list: Vec<FnArgDef>, fn_name: &str) -> Result<Self, RuntimeErrorKind> {
         if arg_list.is_empty() {
             Err(RuntimeErrorKind::CantMakeFnIntoClosureZeroArgs {
@@ -162,14 +214,14 @@ impl ClosurePartialArgs {
 #[derive(Clone, Debug)]
 pub struct Closure {
     pub(crate) trc: TypeResolutionContext,
-    pub(crate) code: Vec<Expr>,
+    pub(crate) code: Exprs,
     pub(crate) request_args: ClosurePartialArgs,
     pub(crate) output_types: Option<TypedOutputs>,
 }
 
 pub(crate) struct FullClosure {
-    pub(crate) code: Vec<Expr>,
-    pub(crate) request_args: HashMap<ArgName, FnArg>,
+    pub(crate) code: Exprs,
+    pub(crate) request_args: Args,
     pub(crate) output_types: Option<TypedOutputs>,
 }
 
@@ -183,10 +235,7 @@ impl Closure {
     pub(crate) fn get_args(&self) -> &ClosurePartialArgs {
         &self.request_args
     }
-    pub fn set_parent_args(
-        &self,
-        args: HashMap<String, FnArg>,
-    ) -> Result<(), HashMap<String, FnArg>> {
+    pub fn set_parent_args(&self, args: Args) -> Result<(), Args> {
         self.request_args.set_parent(args)
     }
     pub(crate) fn fill(mut self, value: Value) -> Result<ClosureCurry, RuntimeErrorKind> {
@@ -199,19 +248,15 @@ impl Closure {
             });
         }
         Ok(if self.request_args.is_full() {
-            let args = if let Some(parent_args) = self.request_args.parent.get() {
-                let mut closure_args = parent_args.clone();
-                for (k, v) in self.request_args.filled {
-                    closure_args.insert(k, FnArg(v));
-                }
-                closure_args
-            } else {
-                self.request_args
-                    .filled
-                    .into_iter()
-                    .map(|(k, v)| (k, FnArg(v)))
-                    .collect()
-            };
+            let mut args: Args = self
+                .request_args
+                .filled
+                .into_iter()
+                .map(|(k, v)| (k, FnArg(v)))
+                .collect();
+            if let Some(parent_args) = self.request_args.parent.get() {
+                args.extend_parent(parent_args);
+            }
             ClosureCurry::Full(FullClosure {
                 code: self.code,
                 request_args: args,
@@ -248,33 +293,156 @@ impl FnArgs {
 
 #[derive(Clone, Debug)]
 pub(crate) enum FnArgsInsCap {
-    Args(HashMap<ArgName, FnArg>),
+    Args(Args),
     AllStack(Vec<Value>),
 }
 
-#[derive(Debug, Default)]
-pub struct Stack(Vec<Value>);
+/// # Values of a context
+///
+/// The [allocated size](Value::alloc_size) of the values is only counted once a limit asks
+/// for it, and then kept up to date as values are pushed and popped
+#[derive(Default)]
+pub struct Stack(Vec<Value>, Option<AllocCount>);
+
+// strings and collections shared by several values of the stack are counted once, keyed by the
+// address of their payload, which can't change or be reused while the stack holds it
+#[derive(Default)]
+struct AllocCount {
+    total: usize,
+    shared: HashMap<usize, (usize, usize)>,
+}
+
+impl AllocCount {
+    fn payload(value: &Value) -> Option<usize> {
+        match value {
+            Value::Str(s) => Some(Arc::as_ptr(s).addr()),
+            Value::Array(xs) => Some(Arc::as_ptr(xs).addr()),
+            Value::Map(m) => Some(Arc::as_ptr(m).addr()),
+            _ => None,
+        }
+    }
+    fn add(&mut self, value: &Value) {
+        let Some(payload) = Self::payload(value) else {
+            self.total += value.alloc_size();
+            return;
+        };
+        let (count, size) = self
+            .shared
+            .entry(payload)
+            .or_insert_with(|| (0, value.alloc_size()));
+        if *count == 0 {
+            self.total += *size;
+        }
+        *count += 1;
+    }
+    fn remove(&mut self, value: &Value) {
+        let Some(payload) = Self::payload(value) else {
+            self.total -= value.alloc_size();
+            return;
+        };
+        if let Entry::Occupied(mut entry) = self.shared.entry(payload) {
+            let (count, size) = entry.get_mut();
+            *count -= 1;
+            if *count == 0 {
+                self.total -= *size;
+                entry.remove();
+            }
+        }
+    }
+}
 #[derive(Debug, Clone)]
 pub struct FnArg(pub Value);
 
+/// # Arguments given to a function or closure
+///
+/// Kept in the order of their slots, which the names in the code are resolved to: the
+/// arguments of a closure come before the ones of the function it was made in, and a repeated
+/// name keeps its first slot with the last value given to it
+#[derive(Clone, Default)]
+pub struct Args(Vec<(ArgName, FnArg)>);
+
+impl Args {
+    pub(crate) fn insert(&mut self, name: ArgName, value: FnArg) {
+        match self.0.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((name, value)),
+        }
+    }
+    /// # Add the arguments of the function a closure was made in
+    ///
+    /// They keep their slots after the ones of the closure, even the ones it shadows
+    fn extend_parent(&mut self, parent: &Args) {
+        self.0.extend(parent.0.iter().cloned());
+    }
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, FnArg(v))| v)
+    }
+    pub(crate) fn slot(&self, slot: u32) -> &Value {
+        &self.0[slot as usize].1.0
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.0.iter().map(|(name, FnArg(v))| (name.as_str(), v))
+    }
+}
+
+impl FromIterator<(ArgName, FnArg)> for Args {
+    fn from_iter<T: IntoIterator<Item = (ArgName, FnArg)>>(iter: T) -> Self {
+        let mut args = Args::default();
+        for (name, value) in iter {
+            args.insert(name, value);
+        }
+        args
+    }
+}
+
+impl std::fmt::Debug for Args {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for Stack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Stack").field(&self.0).finish()
+    }
+}
+
 impl Stack {
     pub(crate) fn new_with(v: Vec<Value>) -> Self {
-        Self(v)
+        Self(v, None)
     }
     pub(crate) fn new() -> Self {
-        Self(Vec::new())
+        Self::default()
     }
     pub fn push(&mut self, v: Value) {
+        if let Some(alloc) = &mut self.1 {
+            alloc.add(&v);
+        }
         self.0.push(v);
     }
     pub fn push_this(&mut self, v: impl Into<Value>) {
-        self.0.push(v.into());
+        self.push(v.into());
     }
     pub fn pushn(&mut self, mut vs: Vec<Value>) {
+        if let Some(alloc) = &mut self.1 {
+            for v in &vs {
+                alloc.add(v);
+            }
+        }
         self.0.append(&mut vs);
     }
     pub fn pop(&mut self) -> Option<Value> {
-        self.0.pop()
+        let v = self.0.pop()?;
+        if let Some(alloc) = &mut self.1 {
+            alloc.remove(&v);
+        }
+        Some(v)
     }
     pub fn peek(&mut self) -> Option<&Value> {
         self.0.get(self.len() - 1)
@@ -283,7 +451,28 @@ impl Stack {
         if n > self.len() {
             return None;
         }
-        Some(self.0.split_off(self.len() - n))
+        let vs = self.0.split_off(self.len() - n);
+        if let Some(alloc) = &mut self.1 {
+            for v in &vs {
+                alloc.remove(v);
+            }
+        }
+        Some(vs)
+    }
+    /// # [Allocated size](Value::alloc_size) of the values, counting shared payloads once
+    ///
+    /// The first call counts every value, later ones only what was pushed or popped since
+    pub(crate) fn alloc_size(&mut self) -> usize {
+        let values = &self.0;
+        self.1
+            .get_or_insert_with(|| {
+                let mut alloc = AllocCount::default();
+                for v in values {
+                    alloc.add(v);
+                }
+                alloc
+            })
+            .total
     }
     #[must_use]
     pub fn as_slice(&self) -> &[Value] {
@@ -301,7 +490,16 @@ impl Stack {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+    pub fn clear(&mut self) {
+        self.0.clear();
+        if let Some(alloc) = &mut self.1 {
+            *alloc = AllocCount::default();
+        }
+    }
     pub(crate) fn take(&mut self) -> Vec<Value> {
+        if let Some(alloc) = &mut self.1 {
+            *alloc = AllocCount::default();
+        }
         std::mem::take(&mut self.0)
     }
     pub fn pop_this<T, F>(&mut self, get_fn: F) -> Option<Result<T, Value>>
@@ -328,22 +526,38 @@ pub enum FnScope {
     Isolated, // fully isolated
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub(crate) struct FnDef {
-    pub(crate) source: PathBuf,
+    pub(crate) source: Arc<Path>,
     pub(crate) scope: FnScope,
-    pub(crate) code: Vec<Expr>,
+    pub(crate) code: Exprs,
     pub(crate) args: FnArgs,
     pub(crate) output_types: Option<TypedOutputs>,
+    pub(crate) body: Arc<runtime::Chunk>,
+}
+
+// the compiled body is left out, it's the same code
+#[allow(clippy::missing_fields_in_debug)]
+impl std::fmt::Debug for FnDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FnDef")
+            .field("source", &self.source)
+            .field("scope", &self.scope)
+            .field("code", &self.code)
+            .field("args", &self.args)
+            .field("output_types", &self.output_types)
+            .finish()
+    }
 }
 
 impl FnDef {
     pub(crate) fn new(
         scope: FnScope,
-        code: Vec<Expr>,
+        code: Exprs,
         args: FnArgs,
         output_types: Option<TypedOutputs>,
-        source: PathBuf,
+        source: Arc<Path>,
+        body: Arc<runtime::Chunk>,
     ) -> Self {
         FnDef {
             source,
@@ -351,6 +565,7 @@ impl FnDef {
             code,
             args,
             output_types,
+            body,
         }
     }
     pub fn into_closure(
@@ -373,14 +588,19 @@ impl FnDef {
     }
 }
 
+/// # A stck value
+///
+/// Strings, arrays and maps are reference counted, so cloning a value is cheap. Taking the
+/// payload out with [`get_str`](Value::get_str), [`get_arr`](Value::get_arr) or
+/// [`get_map`](Value::get_map) only copies it when another value still shares it
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Char(char),
-    Str(String),
+    Str(Arc<String>),
     Num(isize),
     Bool(bool),
-    Array(Vec<Value>),
-    Map(HashMap<String, Value>),
+    Array(Arc<Vec<Value>>),
+    Map(Arc<HashMap<String, Value>>),
     Result(Box<Result<Value, Value>>),
     Option(Option<Box<Value>>),
     Closure(Box<Closure>),
@@ -388,6 +608,30 @@ pub enum Value {
 }
 
 impl Value {
+    /// # Memory used by strings and collections in this value
+    ///
+    /// Counts the bytes of every string and one unit for each array item or map entry
+    #[must_use]
+    pub fn alloc_size(&self) -> usize {
+        match self {
+            Value::Str(s) => s.len(),
+            Value::Array(xs) => xs.len() + xs.iter().map(Value::alloc_size).sum::<usize>(),
+            Value::Map(m) => m
+                .iter()
+                .map(|(k, v)| 1 + k.len() + v.alloc_size())
+                .sum::<usize>(),
+            Value::Result(r) => match r.as_ref() {
+                Ok(v) | Err(v) => v.alloc_size(),
+            },
+            Value::Option(Some(v)) => v.alloc_size(),
+            Value::Option(None)
+            | Value::Char(_)
+            | Value::Num(_)
+            | Value::Bool(_)
+            | Value::Closure(_)
+            | Value::Float(_) => 0,
+        }
+    }
     pub fn get_float(self) -> Result<f64, Value> {
         match self {
             Value::Float(n) => Ok(n),
@@ -412,9 +656,15 @@ impl Value {
             o => Err(o),
         }
     }
+    pub fn get_char(self) -> Result<char, Value> {
+        match self {
+            Value::Char(x) => Ok(x),
+            o => Err(o),
+        }
+    }
     pub fn get_str(self) -> Result<String, Value> {
         match self {
-            Value::Str(x) => Ok(x),
+            Value::Str(x) => Ok(Arc::unwrap_or_clone(x)),
             o => Err(o),
         }
     }
@@ -432,11 +682,39 @@ impl Value {
     }
     pub fn get_arr(self) -> Result<Vec<Value>, Value> {
         match self {
-            Value::Array(x) => Ok(x),
+            Value::Array(x) => Ok(Arc::unwrap_or_clone(x)),
             o => Err(o),
         }
     }
     pub fn get_map(self) -> Result<HashMap<String, Value>, Value> {
+        match self {
+            Value::Map(x) => Ok(Arc::unwrap_or_clone(x)),
+            o => Err(o),
+        }
+    }
+
+    /// # Take the shared payload of a string
+    ///
+    /// Mutate it with [`Arc::make_mut`], which only copies the string if it's still shared
+    pub fn get_shared_str(self) -> Result<Arc<String>, Value> {
+        match self {
+            Value::Str(x) => Ok(x),
+            o => Err(o),
+        }
+    }
+    /// # Take the shared payload of an array
+    ///
+    /// Mutate it with [`Arc::make_mut`], which only copies the array if it's still shared
+    pub fn get_shared_arr(self) -> Result<Arc<Vec<Value>>, Value> {
+        match self {
+            Value::Array(x) => Ok(x),
+            o => Err(o),
+        }
+    }
+    /// # Take the shared payload of a map
+    ///
+    /// Mutate it with [`Arc::make_mut`], which only copies the map if it's still shared
+    pub fn get_shared_map(self) -> Result<Arc<HashMap<String, Value>>, Value> {
         match self {
             Value::Map(x) => Ok(x),
             o => Err(o),
@@ -467,6 +745,12 @@ impl Value {
             o => Err(o),
         }
     }
+    pub fn get_ref_char(&self) -> Result<&char, &Value> {
+        match self {
+            Value::Char(x) => Ok(x),
+            o => Err(o),
+        }
+    }
     pub fn get_ref_str(&self) -> Result<&String, &Value> {
         match self {
             Value::Str(x) => Ok(x),
@@ -519,7 +803,7 @@ impl From<Option<Value>> for Value {
 
 impl From<String> for Value {
     fn from(value: String) -> Self {
-        Value::Str(value)
+        Value::Str(Arc::new(value))
     }
 }
 impl From<isize> for Value {
@@ -534,12 +818,27 @@ impl From<bool> for Value {
 }
 impl From<Vec<Value>> for Value {
     fn from(value: Vec<Value>) -> Self {
+        Value::Array(Arc::new(value))
+    }
+}
+impl From<Arc<String>> for Value {
+    fn from(value: Arc<String>) -> Self {
+        Value::Str(value)
+    }
+}
+impl From<Arc<Vec<Value>>> for Value {
+    fn from(value: Arc<Vec<Value>>) -> Self {
         Value::Array(value)
     }
 }
+impl From<Arc<HashMap<String, Value>>> for Value {
+    fn from(value: Arc<HashMap<String, Value>>) -> Self {
+        Value::Map(value)
+    }
+}
 impl From<HashMap<String, Value>> for Value {
     fn from(value: HashMap<String, Value>) -> Self {
-        Value::Map(value)
+        Value::Map(Arc::new(value))
     }
 }
 impl From<Result<Value, Value>> for Value {
@@ -556,8 +855,8 @@ impl From<Closure> for Value {
 #[cfg_attr(test, derive(PartialEq))]
 #[derive(Clone, Debug)]
 pub struct CondBranch {
-    pub(crate) check: Vec<Expr>,
-    pub(crate) code: Vec<Expr>,
+    pub(crate) check: Exprs,
+    pub(crate) code: Exprs,
 }
 
 #[cfg_attr(test, derive(PartialEq))]
@@ -573,19 +872,19 @@ pub enum KeywordKind {
         branches: Vec<CondBranch>,
     },
     While {
-        check: Vec<Expr>,
-        code: Vec<Expr>,
+        check: Exprs,
+        code: Exprs,
     },
     FnDef {
         name: FnName,
         scope: FnScope,
-        code: Vec<Expr>,
+        code: Exprs,
         args: FnArgs,
         out_args: Option<Vec<FnArgDef>>,
     },
     Switch {
         cases: Vec<SwitchCase>,
-        default: Option<Vec<Expr>>,
+        default: Option<Exprs>,
     },
     DefinedGeneric(DefinedGenericBuilder),
     Require(String),
@@ -595,7 +894,7 @@ pub enum KeywordKind {
 #[derive(Clone, Debug)]
 pub struct SwitchCase {
     pub(crate) test: Value,
-    pub(crate) code: Vec<Expr>,
+    pub(crate) code: Exprs,
 }
 
 #[cfg_attr(test, derive(PartialEq))]
@@ -609,11 +908,30 @@ pub struct Expr {
 #[derive(Clone, Debug)]
 pub enum ExprCont {
     Immediate(Value),
-    FnCall(FnName),
+    FnCall(FnName, Resolved),
     Keyword(KeywordKind),
     IncludedCode(Code),
 }
 
+/// # What a called name refers to
+///
+/// Set by the resolution pass that runs after parsing. Names that can't be known before
+/// executing stay [`Dynamic`](Resolved::Dynamic) and are looked up for every call
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Resolved {
+    /// Not resolved yet, like code that didn't go through the [api](crate::api)
+    #[default]
+    Unresolved,
+    Builtin(Builtin),
+    /// Slot of an argument of the function or closure
+    Arg(u32),
+    /// Function defined in the code, looked up without checking the arguments
+    Fn(Symbol),
+    /// Argument, function or hook, looked up when called
+    Dynamic(Symbol),
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum ControlFlow {
     Continue,
     Break,
@@ -657,6 +975,10 @@ pub enum TokenCont {
     Block(Vec<Token>),
     IncludedBlock(TokenBlock),
     EndOfBlock,
+    /// Source text of a token, only made for the [formatter](crate::format)
+    Verbatim(String),
+    /// Text after a `#`, only made for the [formatter](crate::format)
+    Comment(String),
 }
 
 /// # Array of tokens and their source
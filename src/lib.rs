@@ -1,10 +1,22 @@
+// lets the code made by `#[derive(StckValue)]` name the crate from inside it
+extern crate self as stck;
+
 // Avaliabe to user
 pub mod api;
 pub mod cache;
+pub mod check;
+pub mod convert;
 pub mod error;
+pub mod format;
 pub mod internals;
+pub mod precompiled;
 pub mod prelude;
+pub mod stdlib;
 pub use error::Error;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "serde")]
+pub use serialize::{SerdeError, from_value, to_value};
 
 // Avaliabe internally
 pub(crate) use error::{
@@ -16,7 +28,9 @@ pub(crate) use types::*;
 mod display;
 mod parse;
 mod preproc;
+mod resolve;
 mod runtime;
+mod symbol;
 mod token;
 mod types;
 
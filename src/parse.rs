@@ -6,6 +6,7 @@ pub struct Context<'p> {
     code: Vec<Token>,
     source: &'p Path,
     ungotten: Option<Token>, // token to be re-parsed by different state
+    last_span: LineRange,
 }
 
 #[derive(Debug)]
@@ -15,7 +16,7 @@ pub enum State {
     MakeIfs(Vec<CondBranch>),
     MakeIfsCode {
         branches: Vec<CondBranch>,
-        check: Vec<Expr>,
+        check: Exprs,
     },
 
     MakeFnArgs(FnScope),
@@ -27,7 +28,7 @@ pub enum State {
     MakeSwitchCode(Vec<SwitchCase>, Value),
 
     MakeWhile,
-    MakeWhileCode(Vec<Expr>),
+    MakeWhileCode(Exprs),
 
     MakeClosureBlockOrOutArgs(Vec<FnArgDef>),
     MakeClosureBlock(Vec<FnArgDef>, Option<Vec<FnArgDef>>),
@@ -35,6 +36,11 @@ pub enum State {
 
 impl<'p> Context<'p> {
     pub fn parse_block(&mut self) -> Result<Vec<Expr>, StckError> {
+        self.parse_exprs()
+            .map_err(|e| e.spanned(self.source, self.last_span.clone()))
+    }
+
+    fn parse_exprs(&mut self) -> Result<Vec<Expr>, StckError> {
         use ExprCont as E;
         use State::*;
         use TokenCont::*;
@@ -63,11 +69,11 @@ impl<'p> Context<'p> {
             state = match (state, cont) {
                 (Nothing, EndOfBlock) => Nothing,
                 (Nothing, Ident(n)) => {
-                    push_expr!(E::FnCall(n));
+                    push_expr!(E::FnCall(n, Resolved::Unresolved));
                     Nothing
                 }
                 (Nothing, Str(x)) => {
-                    push_expr!(E::Immediate(Value::Str(x)));
+                    push_expr!(E::Immediate(Value::from(x)));
                     Nothing
                 }
                 (Nothing, Number(x)) => {
@@ -112,7 +118,7 @@ impl<'p> Context<'p> {
                     let parsed_code = inner_ctx.parse_block()?;
                     push_expr!(E::IncludedCode(Code {
                         source: code.source,
-                        exprs: parsed_code,
+                        exprs: parsed_code.into(),
                     }));
                     s
                 }
@@ -130,7 +136,7 @@ impl<'p> Context<'p> {
                 }
                 (MakeClosureBlock(args, outs), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     let closure = Closure {
                         code,
                         trc: TypeResolutionBuilder::new().into(),
@@ -143,17 +149,17 @@ impl<'p> Context<'p> {
 
                 (Nothing, Keyword(RawKeyword::Switch)) => MakeSwitch(vec![]),
                 (MakeSwitch(cases), Char(c)) => MakeSwitchCode(cases, Value::Char(c)),
-                (MakeSwitch(cases), Str(v)) => MakeSwitchCode(cases, Value::Str(v)),
+                (MakeSwitch(cases), Str(v)) => MakeSwitchCode(cases, Value::from(v)),
                 (MakeSwitch(cases), Number(v)) => MakeSwitchCode(cases, Value::Num(v)),
                 (MakeSwitchCode(mut cases, test), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     cases.push(SwitchCase { test, code });
                     MakeSwitch(cases)
                 }
                 (MakeSwitch(cases), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     push_expr!(E::Keyword(KeywordKind::Switch {
                         cases,
                         default: Some(code),
@@ -178,7 +184,7 @@ impl<'p> Context<'p> {
                 (Nothing, Keyword(RawKeyword::Ifs)) => MakeIfs(vec![]),
                 (MakeIfs(branches), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let check = inner_ctx.parse_block()?;
+                    let check: Exprs = inner_ctx.parse_block()?.into();
                     MakeIfsCode { branches, check }
                 }
                 (
@@ -189,7 +195,7 @@ impl<'p> Context<'p> {
                     Block(code),
                 ) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     branches.push(CondBranch { check, code });
                     MakeIfs(branches)
                 }
@@ -224,7 +230,7 @@ impl<'p> Context<'p> {
                 }
                 (MakeFnBlock(scope, args, name, out_args), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     let fndef = E::Keyword(KeywordKind::FnDef {
                         name,
                         scope,
@@ -239,12 +245,12 @@ impl<'p> Context<'p> {
                 (Nothing, Keyword(RawKeyword::While)) => MakeWhile,
                 (MakeWhile, Block(check)) => {
                     let mut inner_ctx = Context::new(check, self.source);
-                    let check = inner_ctx.parse_block()?;
+                    let check: Exprs = inner_ctx.parse_block()?.into();
                     MakeWhileCode(check)
                 }
                 (MakeWhileCode(check), Block(code)) => {
                     let mut inner_ctx = Context::new(code, self.source);
-                    let code = inner_ctx.parse_block()?;
+                    let code: Exprs = inner_ctx.parse_block()?.into();
                     push_expr!(E::Keyword(KeywordKind::While { check, code }));
                     Nothing
                 }
@@ -267,10 +273,12 @@ impl<'p> Context<'p> {
     }
 
     fn next(&mut self) -> Option<Token> {
-        match self.ungotten.take() {
+        let token = match self.ungotten.take() {
             None => self.code.pop(),
             x => x,
-        }
+        }?;
+        self.last_span = token.span.clone();
+        Some(token)
     }
 
     pub fn new(mut tokens: Vec<Token>, source: &'p Path) -> Self {
@@ -279,6 +287,7 @@ impl<'p> Context<'p> {
             source,
             code: tokens,
             ungotten: None,
+            last_span: LineRange::default(),
         }
     }
 }
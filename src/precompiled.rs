@@ -0,0 +1,811 @@
+//! # Precompiled code
+//!
+//! Parsed [code](Code) is stored in a binary format that doesn't depend on how it's laid out in
+//! memory, so it stays readable until [`FORMAT`] changes. [`CodeCache`] keeps it on disk, so
+//! hosts that load many scripts only parse the ones that changed
+//!
+//! ```rust
+//! let tokens = stck::api::get_tokens_str("5 2 -\n", "Precompiled", &mut stck::cache::NoCache).unwrap();
+//! let code = stck::api::parse_raw_tokens(tokens).unwrap();
+//! let bytes = stck::precompiled::encode(&code);
+//! let code = stck::precompiled::decode(&bytes).unwrap();
+//! let ctx = stck::api::execute_raw_code(&code).unwrap();
+//! assert_eq!(ctx.get_stack(), [stck::internals::Value::Num(3)]);
+//! ```
+
+use crate::cache::FileCacher;
+use crate::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// # Version of the binary format
+pub const FORMAT: u32 = 1;
+const MAGIC: &[u8; 8] = b"stckcode";
+/// Deepest nesting of blocks, values and types accepted by [`decode`]
+const MAX_DEPTH: usize = 256;
+// tells apart the partial entries written at the same time by the threads of the process
+static PARTIAL_WRITES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// # Error while reading precompiled code
+#[derive(thiserror::Error, Debug)]
+pub enum DecodeError {
+    #[error("Not precompiled stck code")]
+    NotPrecompiled,
+    #[error("Code was precompiled with format {0}, this version reads format {FORMAT}")]
+    Format(u32),
+    #[error("Precompiled code ends early")]
+    UnexpectedEnd,
+    #[error("Precompiled code has an invalid {0}")]
+    Invalid(&'static str),
+}
+
+/// # Store code in the binary format
+///
+/// The same code is always stored as the same bytes
+#[must_use]
+pub fn encode(code: &Code) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.header();
+    encoder.code(code);
+    encoder.0
+}
+
+/// # Read code stored by [`encode`]
+pub fn decode(bytes: &[u8]) -> Result<Code, DecodeError> {
+    let mut decoder = Decoder::new(bytes);
+    decoder.header()?;
+    decoder.code_to_end()
+}
+
+/// # Directory of precompiled scripts
+///
+/// Each script is stored with the files it included and a hash of their contents, so it's only
+/// [loaded](CodeCache::load) while none of them changed. Files are read through the given
+/// [cacher](FileCacher), like when parsing, and the embedded standard library only changes
+/// along with the crate's version
+///
+/// ```rust,no_run
+/// use stck::cache::CacheHelper;
+/// use stck::precompiled::CodeCache;
+/// let cache = CodeCache::new("target/stck-cache");
+/// let code = cache.load_or_parse("main.stck", &mut CacheHelper::new()).unwrap();
+/// stck::api::execute_raw_code(&code).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CodeCache {
+    dir: PathBuf,
+}
+
+impl CodeCache {
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// # Precompiled code of the script, if it and its includes didn't change
+    pub fn load(&self, path: impl AsRef<Path>, file_cache: &mut impl FileCacher) -> Option<Code> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(self.entry(path)).ok()?;
+        let mut decoder = Decoder::new(&bytes);
+        decoder.header().ok()?;
+        let key = decoder.u64().ok()?;
+        let files = decoder.list(Decoder::path).ok()?;
+        // scripts with the same entry are told apart by their path
+        if files.first().map(PathBuf::as_path) != Some(path) {
+            return None;
+        }
+        let mut hashes = HashMap::new();
+        for file in &files {
+            let content = file_cache.read_file(file).ok()?;
+            hashes.insert(file.clone(), hash_file(content.as_ref()));
+        }
+        if content_key(&files, &hashes)? != key {
+            return None;
+        }
+        decoder.code_to_end().ok()
+    }
+
+    /// # Load the script if it's fresh, otherwise parse and store it
+    ///
+    /// Failing to store the script isn't an error, it's just parsed again next time
+    pub fn load_or_parse(
+        &self,
+        path: impl AsRef<Path>,
+        file_cache: &mut impl FileCacher,
+    ) -> Result<Code, Error> {
+        let path = path.as_ref();
+        if let Some(code) = self.load(path, file_cache) {
+            return Ok(code);
+        }
+        let mut recording = Recording {
+            inner: file_cache,
+            files: vec![path.to_path_buf()],
+            hashes: HashMap::new(),
+        };
+        let code = api::get_project_code(path, &mut recording)?;
+        if let Some(key) = content_key(&recording.files, &recording.hashes) {
+            let _ = self.store(key, &recording.files, &code);
+        }
+        Ok(code)
+    }
+
+    fn store(&self, key: u64, files: &[PathBuf], code: &Code) -> std::io::Result<()> {
+        let mut encoder = Encoder::default();
+        encoder.header();
+        encoder.u64(key);
+        encoder.list(files, |e, file| e.path(file));
+        encoder.code(code);
+        std::fs::create_dir_all(&self.dir)?;
+        // written whole and then moved, so other processes and threads never read half an entry
+        let entry = self.entry(&files[0]);
+        let writer = PARTIAL_WRITES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let partial = entry.with_extension(format!("part{}-{writer}", std::process::id()));
+        std::fs::write(&partial, encoder.0)?;
+        std::fs::rename(partial, entry)
+    }
+
+    fn entry(&self, path: &Path) -> PathBuf {
+        let mut hash = ContentHash::new();
+        hash.str(&path.to_string_lossy());
+        self.dir.join(format!("{:016x}.stckc", hash.0))
+    }
+}
+
+/// # Cacher that hashes the files as they are read and keeps the ones included
+///
+/// Hashing while reading means a file that changes during parsing can't be stored with the
+/// hash of its new contents
+struct Recording<'c, C> {
+    inner: &'c mut C,
+    files: Vec<PathBuf>,
+    hashes: HashMap<PathBuf, u64>,
+}
+
+impl<C: FileCacher> FileCacher for Recording<'_, C> {
+    type FileRecord<'s>
+        = C::FileRecord<'s>
+    where
+        Self: 's;
+    fn read_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Self::FileRecord<'_>, std::io::Error> {
+        let path = path.as_ref();
+        let file = self.inner.read_file(path)?;
+        self.hashes
+            .insert(path.to_path_buf(), hash_file(file.as_ref()));
+        Ok(file)
+    }
+
+    fn is_file(&mut self, path: impl AsRef<Path>) -> bool {
+        self.inner.is_file(path)
+    }
+
+    fn is_dir(&mut self, path: impl AsRef<Path>) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn resolve_include(&mut self, dir: &Path, path: &Path) -> Option<PathBuf> {
+        self.inner.resolve_include(dir, path)
+    }
+
+    fn record_include(&mut self, from: &Path, included: &Path) {
+        self.files.push(included.to_path_buf());
+        self.inner.record_include(from, included);
+    }
+}
+
+/// # FNV-1a, which unlike the std hasher is the same on every version and platform
+struct ContentHash(u64);
+
+impl ContentHash {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(&(s.len() as u64).to_le_bytes());
+        self.bytes(s.as_bytes());
+    }
+}
+
+fn hash_file(content: &str) -> u64 {
+    let mut hash = ContentHash::new();
+    hash.str(content);
+    hash.0
+}
+
+// `None` if a file wasn't read
+fn content_key(files: &[PathBuf], hashes: &HashMap<PathBuf, u64>) -> Option<u64> {
+    let mut hash = ContentHash::new();
+    hash.str(env!("CARGO_PKG_VERSION"));
+    hash.bytes(&FORMAT.to_le_bytes());
+    for file in files {
+        hash.str(&file.to_string_lossy());
+        hash.bytes(&hashes.get(file)?.to_le_bytes());
+    }
+    Some(hash.0)
+}
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn header(&mut self) {
+        self.0.extend(MAGIC);
+        self.u32(FORMAT);
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend(v.to_le_bytes());
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(u8::from(v));
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.0.extend(s.as_bytes());
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.str(&path.to_string_lossy());
+    }
+
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T)) {
+        self.u64(items.len() as u64);
+        for x in items {
+            item(self, x);
+        }
+    }
+
+    fn option<T>(&mut self, v: Option<&T>, some: impl FnOnce(&mut Self, &T)) {
+        match v {
+            None => self.u8(0),
+            Some(v) => {
+                self.u8(1);
+                some(self, v);
+            }
+        }
+    }
+
+    // items are sorted by their bytes, since sets and maps have no order
+    fn sorted(&mut self, mut items: Vec<Vec<u8>>) {
+        items.sort_unstable();
+        self.u64(items.len() as u64);
+        for item in items {
+            self.0.extend(item);
+        }
+    }
+
+    fn code(&mut self, code: &Code) {
+        self.path(&code.source);
+        self.exprs(&code.exprs);
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        self.list(exprs, Self::expr);
+    }
+
+    fn expr(&mut self, Expr { span, cont }: &Expr) {
+        self.u64(span.start as u64);
+        self.u64(span.end as u64);
+        match cont {
+            ExprCont::Immediate(v) => {
+                self.u8(0);
+                self.value(v);
+            }
+            // names are resolved again when decoded, so bad bytes can't point outside the
+            // arguments
+            ExprCont::FnCall(name, _) => {
+                self.u8(1);
+                self.str(name);
+            }
+            ExprCont::Keyword(kw) => {
+                self.u8(2);
+                self.keyword(kw);
+            }
+            ExprCont::IncludedCode(code) => {
+                self.u8(3);
+                self.code(code);
+            }
+        }
+    }
+
+    fn keyword(&mut self, kw: &KeywordKind) {
+        match kw {
+            KeywordKind::IntoClosure { fn_name } => {
+                self.u8(0);
+                self.str(fn_name);
+            }
+            KeywordKind::Break => self.u8(1),
+            KeywordKind::Return => self.u8(2),
+            KeywordKind::BubbleError => self.u8(3),
+            KeywordKind::Ifs { branches } => {
+                self.u8(4);
+                self.list(branches, |e, branch| {
+                    e.exprs(&branch.check);
+                    e.exprs(&branch.code);
+                });
+            }
+            KeywordKind::While { check, code } => {
+                self.u8(5);
+                self.exprs(check);
+                self.exprs(code);
+            }
+            KeywordKind::FnDef {
+                name,
+                scope,
+                code,
+                args,
+                out_args,
+            } => {
+                self.u8(6);
+                self.str(name);
+                self.u8(match scope {
+                    FnScope::Global => 0,
+                    FnScope::Local => 1,
+                    FnScope::Isolated => 2,
+                });
+                self.exprs(code);
+                match args {
+                    FnArgs::AllStack => self.u8(0),
+                    FnArgs::Args(args) => {
+                        self.u8(1);
+                        self.arg_defs(args);
+                    }
+                }
+                self.option(out_args.as_ref(), |e, outs| e.arg_defs(outs));
+            }
+            KeywordKind::Switch { cases, default } => {
+                self.u8(7);
+                self.list(cases, |e, case| {
+                    e.value(&case.test);
+                    e.exprs(&case.code);
+                });
+                self.option(default.as_ref(), |e, code| e.exprs(code));
+            }
+            KeywordKind::DefinedGeneric(generic) => {
+                self.u8(8);
+                self.str(&generic.name);
+                self.bool(generic.viral);
+                let allow = generic.allow.iter().map(|t| {
+                    let mut encoder = Encoder::default();
+                    encoder.type_tester(t);
+                    encoder.0
+                });
+                self.sorted(allow.collect());
+            }
+            KeywordKind::Require(module) => {
+                self.u8(9);
+                self.str(module);
+            }
+        }
+    }
+
+    fn arg_defs(&mut self, args: &[FnArgDef]) {
+        self.list(args, |e, arg| {
+            e.str(&arg.name);
+            e.option(arg.type_check.as_ref(), Self::type_tester);
+        });
+    }
+
+    fn value(&mut self, v: &Value) {
+        match v {
+            Value::Char(c) => {
+                self.u8(0);
+                self.u32(u32::from(*c));
+            }
+            Value::Str(s) => {
+                self.u8(1);
+                self.str(s);
+            }
+            Value::Num(n) => {
+                self.u8(2);
+                self.u64(*n as u64);
+            }
+            Value::Bool(b) => {
+                self.u8(3);
+                self.bool(*b);
+            }
+            Value::Array(xs) => {
+                self.u8(4);
+                self.list(xs, Self::value);
+            }
+            Value::Map(m) => {
+                self.u8(5);
+                let entries = m.iter().map(|(k, v)| {
+                    let mut encoder = Encoder::default();
+                    encoder.str(k);
+                    encoder.value(v);
+                    encoder.0
+                });
+                self.sorted(entries.collect());
+            }
+            Value::Result(r) => {
+                self.u8(6);
+                let (is_ok, v) = match r.as_ref() {
+                    Ok(v) => (true, v),
+                    Err(v) => (false, v),
+                };
+                self.bool(is_ok);
+                self.value(v);
+            }
+            Value::Option(v) => {
+                self.u8(7);
+                self.option(v.as_deref(), Self::value);
+            }
+            Value::Closure(cl) => {
+                self.u8(8);
+                self.closure(cl);
+            }
+            Value::Float(f) => {
+                self.u8(9);
+                self.u64(f.to_bits());
+            }
+        }
+    }
+
+    // closures of the code have no types resolved yet, nor a parent to take arguments from
+    fn closure(&mut self, cl: &Closure) {
+        self.arg_defs(&cl.request_args.next);
+        self.list(&cl.request_args.filled, |e, (name, v)| {
+            e.str(name);
+            e.value(v);
+        });
+        self.option(cl.output_types.as_ref(), |e, outs| {
+            e.list(&outs.outputs, |e, t| {
+                e.option(t.as_ref(), Self::type_tester);
+            });
+        });
+        self.exprs(&cl.code);
+    }
+
+    fn type_tester(&mut self, t: &TypeTester) {
+        match t {
+            TypeTester::Generic(name) => {
+                self.u8(0);
+                self.str(name);
+            }
+            TypeTester::Float => self.u8(1),
+            TypeTester::Any => self.u8(2),
+            TypeTester::Char => self.u8(3),
+            TypeTester::Str => self.u8(4),
+            TypeTester::Num => self.u8(5),
+            TypeTester::Bool => self.u8(6),
+            TypeTester::ArrayAny => self.u8(7),
+            TypeTester::MapAny => self.u8(8),
+            TypeTester::ResultAny => self.u8(9),
+            TypeTester::OptionAny => self.u8(10),
+            TypeTester::ClosureAny => self.u8(11),
+            TypeTester::Array(t) => {
+                self.u8(12);
+                self.type_tester(t);
+            }
+            TypeTester::Map(t) => {
+                self.u8(13);
+                self.type_tester(t);
+            }
+            TypeTester::Result(ts) => {
+                self.u8(14);
+                self.type_tester(&ts.0);
+                self.type_tester(&ts.1);
+            }
+            TypeTester::Option(t) => {
+                self.u8(15);
+                self.type_tester(t);
+            }
+            TypeTester::Closure(ins, outs) => {
+                self.u8(16);
+                self.fn_part(ins);
+                self.fn_part(outs);
+            }
+        }
+    }
+
+    fn fn_part(&mut self, part: &TypedFnPart) {
+        match part {
+            TypedFnPart::Any => self.u8(0),
+            TypedFnPart::Typed(ts) => {
+                self.u8(1);
+                self.list(ts, Self::type_tester);
+            }
+        }
+    }
+}
+
+struct Decoder<'b> {
+    bytes: &'b [u8],
+    depth: usize,
+}
+
+type DResult<T> = Result<T, DecodeError>;
+
+impl<'b> Decoder<'b> {
+    fn new(bytes: &'b [u8]) -> Self {
+        Self { bytes, depth: 0 }
+    }
+
+    fn header(&mut self) -> DResult<()> {
+        if self.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(DecodeError::NotPrecompiled);
+        }
+        match self.u32()? {
+            FORMAT => Ok(()),
+            format => Err(DecodeError::Format(format)),
+        }
+    }
+
+    fn take(&mut self, n: usize) -> DResult<&'b [u8]> {
+        if n > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> DResult<[u8; N]> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("took the array's length"))
+    }
+
+    fn u8(&mut self) -> DResult<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> DResult<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> DResult<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn bool(&mut self) -> DResult<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid("boolean")),
+        }
+    }
+
+    // every item takes at least a byte, so longer lengths can't be right
+    fn len(&mut self) -> DResult<usize> {
+        match usize::try_from(self.u64()?) {
+            Ok(len) if len <= self.bytes.len() => Ok(len),
+            _ => Err(DecodeError::UnexpectedEnd),
+        }
+    }
+
+    fn str(&mut self) -> DResult<String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("string"))
+    }
+
+    fn path(&mut self) -> DResult<PathBuf> {
+        self.str().map(PathBuf::from)
+    }
+
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> DResult<T>) -> DResult<Vec<T>> {
+        let len = self.len()?;
+        (0..len).map(|_| item(self)).collect()
+    }
+
+    fn option<T>(&mut self, some: impl FnOnce(&mut Self) -> DResult<T>) -> DResult<Option<T>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => some(self).map(Some),
+            _ => Err(DecodeError::Invalid("option")),
+        }
+    }
+
+    // values, blocks and types are the only ones that nest, so they're the ones counted
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> DResult<T>) -> DResult<T> {
+        if self.depth == MAX_DEPTH {
+            return Err(DecodeError::Invalid("nesting depth"));
+        }
+        self.depth += 1;
+        let nested = f(self);
+        self.depth -= 1;
+        nested
+    }
+
+    fn code_to_end(&mut self) -> DResult<Code> {
+        let mut code = self.code()?;
+        if !self.bytes.is_empty() {
+            return Err(DecodeError::Invalid("ending"));
+        }
+        resolve::resolve(&mut code);
+        Ok(code)
+    }
+
+    fn code(&mut self) -> DResult<Code> {
+        let source = self.path()?;
+        let exprs = self.exprs()?;
+        Ok(Code { source, exprs })
+    }
+
+    fn exprs(&mut self) -> DResult<Exprs> {
+        self.nested(|d| d.list(Self::expr).map(Exprs::from))
+    }
+
+    fn expr(&mut self) -> DResult<Expr> {
+        let start = self.len_value()?;
+        let end = self.len_value()?;
+        let cont = match self.u8()? {
+            0 => ExprCont::Immediate(self.value()?),
+            1 => ExprCont::FnCall(self.str()?, Resolved::Unresolved),
+            2 => ExprCont::Keyword(self.keyword()?),
+            3 => ExprCont::IncludedCode(self.code()?),
+            _ => return Err(DecodeError::Invalid("expression")),
+        };
+        Ok(Expr {
+            span: LineRange { start, end },
+            cont,
+        })
+    }
+
+    // a number stored as u64 that has to fit in a usize, like a line
+    fn len_value(&mut self) -> DResult<usize> {
+        usize::try_from(self.u64()?).map_err(|_| DecodeError::Invalid("line"))
+    }
+
+    fn keyword(&mut self) -> DResult<KeywordKind> {
+        Ok(match self.u8()? {
+            0 => KeywordKind::IntoClosure {
+                fn_name: self.str()?,
+            },
+            1 => KeywordKind::Break,
+            2 => KeywordKind::Return,
+            3 => KeywordKind::BubbleError,
+            4 => KeywordKind::Ifs {
+                branches: self.list(|d| {
+                    Ok(CondBranch {
+                        check: d.exprs()?,
+                        code: d.exprs()?,
+                    })
+                })?,
+            },
+            5 => KeywordKind::While {
+                check: self.exprs()?,
+                code: self.exprs()?,
+            },
+            6 => KeywordKind::FnDef {
+                name: self.str()?,
+                scope: match self.u8()? {
+                    0 => FnScope::Global,
+                    1 => FnScope::Local,
+                    2 => FnScope::Isolated,
+                    _ => return Err(DecodeError::Invalid("function scope")),
+                },
+                code: self.exprs()?,
+                args: match self.u8()? {
+                    0 => FnArgs::AllStack,
+                    1 => FnArgs::Args(self.arg_defs()?),
+                    _ => return Err(DecodeError::Invalid("function arguments")),
+                },
+                out_args: self.option(Self::arg_defs)?,
+            },
+            7 => KeywordKind::Switch {
+                cases: self.list(|d| {
+                    Ok(SwitchCase {
+                        test: d.value()?,
+                        code: d.exprs()?,
+                    })
+                })?,
+                default: self.option(Self::exprs)?,
+            },
+            8 => KeywordKind::DefinedGeneric(DefinedGenericBuilder {
+                name: self.str()?,
+                viral: self.bool()?,
+                allow: self.list(Self::type_tester)?.into_iter().collect(),
+            }),
+            9 => KeywordKind::Require(self.str()?),
+            _ => return Err(DecodeError::Invalid("keyword")),
+        })
+    }
+
+    fn arg_defs(&mut self) -> DResult<Vec<FnArgDef>> {
+        self.list(|d| Ok(FnArgDef::new(d.str()?, d.option(Self::type_tester)?)))
+    }
+
+    fn value(&mut self) -> DResult<Value> {
+        self.nested(Self::nested_value)
+    }
+
+    fn nested_value(&mut self) -> DResult<Value> {
+        Ok(match self.u8()? {
+            0 => Value::Char(char::from_u32(self.u32()?).ok_or(DecodeError::Invalid("char"))?),
+            1 => Value::from(self.str()?),
+            2 => Value::Num(self.u64()? as isize),
+            3 => Value::Bool(self.bool()?),
+            4 => Value::from(self.list(Self::value)?),
+            5 => {
+                let entries = self.list(|d| Ok((d.str()?, d.value()?)))?;
+                Value::from(entries.into_iter().collect::<HashMap<_, _>>())
+            }
+            6 => {
+                let is_ok = self.bool()?;
+                let v = self.value()?;
+                Value::from(if is_ok { Ok(v) } else { Err(v) })
+            }
+            7 => Value::Option(self.option(Self::value)?.map(Box::new)),
+            8 => Value::from(self.closure()?),
+            9 => Value::Float(f64::from_bits(self.u64()?)),
+            _ => return Err(DecodeError::Invalid("value")),
+        })
+    }
+
+    fn closure(&mut self) -> DResult<Closure> {
+        let next = self.arg_defs()?;
+        let filled = self.list(|d| Ok((d.str()?, d.value()?)))?;
+        let output_types = self.option(|d| {
+            let outputs = d.list(|d| d.option(Self::type_tester))?;
+            Ok(TypedOutputs { outputs })
+        })?;
+        Ok(Closure {
+            trc: TypeResolutionBuilder::new().into(),
+            code: self.exprs()?,
+            request_args: ClosurePartialArgs::from_parts(next, filled),
+            output_types,
+        })
+    }
+
+    fn type_tester(&mut self) -> DResult<TypeTester> {
+        self.nested(Self::nested_type_tester)
+    }
+
+    fn nested_type_tester(&mut self) -> DResult<TypeTester> {
+        Ok(match self.u8()? {
+            0 => TypeTester::Generic(self.str()?),
+            1 => TypeTester::Float,
+            2 => TypeTester::Any,
+            3 => TypeTester::Char,
+            4 => TypeTester::Str,
+            5 => TypeTester::Num,
+            6 => TypeTester::Bool,
+            7 => TypeTester::ArrayAny,
+            8 => TypeTester::MapAny,
+            9 => TypeTester::ResultAny,
+            10 => TypeTester::OptionAny,
+            11 => TypeTester::ClosureAny,
+            12 => TypeTester::Array(Box::new(self.type_tester()?)),
+            13 => TypeTester::Map(Box::new(self.type_tester()?)),
+            14 => TypeTester::Result(Box::new((self.type_tester()?, self.type_tester()?))),
+            15 => TypeTester::Option(Box::new(self.type_tester()?)),
+            16 => TypeTester::Closure(self.fn_part()?, self.fn_part()?),
+            _ => return Err(DecodeError::Invalid("type")),
+        })
+    }
+
+    fn fn_part(&mut self) -> DResult<TypedFnPart> {
+        match self.u8()? {
+            0 => Ok(TypedFnPart::Any),
+            1 => self.list(Self::type_tester).map(TypedFnPart::Typed),
+            _ => Err(DecodeError::Invalid("function type")),
+        }
+    }
+}
@@ -3,5 +3,6 @@
 pub use crate::api::{self, *};
 pub use crate::{
     cache::{self, CacheHelper},
+    convert::{FromValue, IntoValue},
     internals::{self, Code, RuntimeContext},
 };
@@ -23,12 +23,14 @@ pub enum ProcCommand {
 }
 
 pub struct Context<'p> {
+    source: &'p Path,
     dir: &'p Path,
 }
 
 impl<'p> Context<'p> {
-    pub fn new(dir: &'p Path) -> Self {
-        Context { dir }
+    pub fn new(source: &'p Path) -> Self {
+        let dir = source.parent().unwrap_or(Path::new("."));
+        Context { source, dir }
     }
 }
 
@@ -53,21 +55,11 @@ impl<'p> Context<'p> {
         let mut out = Vec::with_capacity(code.len());
         for Token { cont, span } in code {
             match cont {
+                TokenCont::Keyword(RawKeyword::Include { .. })
+                    if !if_stack.last().is_none_or(|s| s.reading) => {}
                 TokenCont::Keyword(RawKeyword::Include { path }) => {
-                    let include_path = self.dir.join(path);
-                    let metadata = include_path
-                        .metadata()
-                        .ok()
-                        .ok_or(StckError::CantReadFile(include_path.clone()))?;
-                    let included_tokens = if metadata.is_dir() {
-                        api::get_tokens_with_procvars(
-                            include_path.join("stck.stck"),
-                            proc_vars,
-                            file_cache,
-                        )
-                    } else {
-                        api::get_tokens_with_procvars(include_path, proc_vars, file_cache)
-                    }?;
+                    let included_tokens =
+                        self.include(&path, span.clone(), proc_vars, file_cache)?;
                     let included_tokens = TokenCont::IncludedBlock(included_tokens);
                     let included_tokens = Token {
                         cont: included_tokens,
@@ -76,7 +68,8 @@ impl<'p> Context<'p> {
                     out.push(included_tokens);
                 }
                 TokenCont::Keyword(RawKeyword::Pragma { command }) => {
-                    manage_pragma(&mut if_stack, &command, proc_vars, span)?;
+                    manage_pragma(&mut if_stack, &command, proc_vars, span.clone())
+                        .map_err(|e| e.spanned(self.source, span))?;
                 }
                 x if if_stack.last().is_none_or(|s| s.reading) => {
                     out.push(Token { cont: x, span });
@@ -86,6 +79,39 @@ impl<'p> Context<'p> {
         }
         Ok(out)
     }
+
+    fn include<S: std::hash::BuildHasher>(
+        &'p self,
+        path: &Path,
+        span: LineRange,
+        proc_vars: &mut HashSet<String, S>,
+        file_cache: &mut impl FileCacher,
+    ) -> Result<TokenBlock, Error> {
+        let include_path = match stdlib::resolve(path, self.dir) {
+            Some(std_path) if stdlib::file(&std_path).is_some() => std_path,
+            Some(std_path) => {
+                return Err(StckError::CantReadFile(std_path)
+                    .spanned(self.source, span)
+                    .into());
+            }
+            None => {
+                let include_path = file_cache.resolve_include(self.dir, path);
+                // a missing file is recorded too, so it's noticed once it's created
+                let tried = include_path.clone().unwrap_or_else(|| self.dir.join(path));
+                file_cache.record_include(self.source, &tried);
+                include_path
+                    .ok_or(StckError::CantReadFile(tried))
+                    .map_err(|e| e.spanned(self.source, span.clone()))?
+            }
+        };
+        api::get_tokens_with_procvars(&include_path, proc_vars, file_cache).map_err(|e| match e {
+            // errors inside the included file already have their span
+            Error::Anoter(StckError::CantReadFile(p)) if p == include_path => {
+                StckError::CantReadFile(p).spanned(self.source, span).into()
+            }
+            e => e,
+        })
+    }
 }
 
 fn manage_pragma<S: std::hash::BuildHasher>(
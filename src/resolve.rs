@@ -0,0 +1,220 @@
+//! # Name resolution
+//!
+//! Runs after parsing, classifying the names called by the code with the same precedence as
+//! the runtime: builtins, then the arguments of the function or closure the name is in, then
+//! user functions and hooks. Arguments are resolved to their slot, and the remaining names are
+//! interned and looked up when called
+//!
+//! The top level can run inside the frame of a hook that executes code, so its arguments, and
+//! the ones closures made there inherit, are only known when executed
+
+use crate::check::{Diagnostic, DiagnosticKind};
+use crate::*;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+/// # Resolve the names called by the code
+pub(crate) fn resolve(code: &mut Code) {
+    let mut fns = HashSet::new();
+    collect_fns(&code.exprs, &mut fns);
+    let resolver = Resolver { fns };
+    resolver.block(&mut code.exprs, &Scope::default());
+}
+
+/// # Functions and arguments named like a builtin, which is called instead of them
+pub(crate) fn conflicts(code: &Code) -> Vec<Diagnostic> {
+    let mut found = Vec::new();
+    find_conflicts(&code.exprs, &code.source, &mut found);
+    found
+}
+
+/// # Names of the argument slots visible to a block
+#[derive(Default)]
+struct Scope {
+    args: Vec<String>,
+    /// If there are no slots besides `args`
+    complete: bool,
+}
+
+impl Scope {
+    fn function(args: &FnArgs) -> Self {
+        let mut scope = Scope {
+            args: Vec::new(),
+            complete: true,
+        };
+        if let FnArgs::Args(args) = args {
+            scope.extend(args.iter().map(FnArgDef::get_name));
+        }
+        scope
+    }
+
+    // closure arguments come before the ones of its parent, see `Args`
+    fn closure(&self, closure: &Closure) -> Self {
+        let mut scope = Scope {
+            args: Vec::new(),
+            complete: self.complete,
+        };
+        let declared = closure.get_args().get_unfilled_args().iter().rev();
+        scope.extend(declared.map(FnArgDef::get_name));
+        scope.args.extend(self.args.iter().cloned());
+        scope
+    }
+
+    // a repeated name keeps its first slot
+    fn extend<'a>(&mut self, names: impl Iterator<Item = &'a str>) {
+        for name in names {
+            if !self.args.iter().any(|arg| arg == name) {
+                self.args.push(name.to_string());
+            }
+        }
+    }
+}
+
+struct Resolver {
+    /// Every function defined in the code
+    fns: HashSet<FnName>,
+}
+
+impl Resolver {
+    fn block(&self, exprs: &mut Exprs, scope: &Scope) {
+        for expr in exprs_mut(exprs) {
+            match &mut expr.cont {
+                ExprCont::FnCall(name, resolved) => *resolved = self.name(name, scope),
+                ExprCont::Immediate(Value::Closure(cl)) => {
+                    let inner = scope.closure(cl);
+                    self.block(&mut cl.code, &inner);
+                }
+                ExprCont::Immediate(_) => {}
+                ExprCont::IncludedCode(code) => self.block(&mut code.exprs, scope),
+                ExprCont::Keyword(kw) => self.keyword(kw, scope),
+            }
+        }
+    }
+
+    fn keyword(&self, kw: &mut KeywordKind, scope: &Scope) {
+        match kw {
+            KeywordKind::FnDef { code, args, .. } => self.block(code, &Scope::function(args)),
+            KeywordKind::Ifs { branches } => {
+                for branch in branches {
+                    self.block(&mut branch.check, scope);
+                    self.block(&mut branch.code, scope);
+                }
+            }
+            KeywordKind::While { check, code } => {
+                self.block(check, scope);
+                self.block(code, scope);
+            }
+            KeywordKind::Switch { cases, default } => {
+                for case in cases {
+                    self.block(&mut case.code, scope);
+                }
+                if let Some(default) = default {
+                    self.block(default, scope);
+                }
+            }
+            KeywordKind::IntoClosure { .. }
+            | KeywordKind::Break
+            | KeywordKind::Return
+            | KeywordKind::BubbleError
+            | KeywordKind::DefinedGeneric(_)
+            | KeywordKind::Require(_) => {}
+        }
+    }
+
+    fn name(&self, name: &str, scope: &Scope) -> Resolved {
+        if let Some(builtin) = Builtin::from_name(name) {
+            return Resolved::Builtin(builtin);
+        }
+        if let Some(slot) = scope.args.iter().position(|arg| arg == name) {
+            return Resolved::Arg(slot as u32);
+        }
+        let symbol = Symbol::intern(name);
+        if scope.complete && self.fns.contains(name) {
+            Resolved::Fn(symbol)
+        } else {
+            Resolved::Dynamic(symbol)
+        }
+    }
+}
+
+/// # Expressions of a block, copied first if they are shared
+fn exprs_mut(exprs: &mut Exprs) -> &mut [Expr] {
+    if Arc::get_mut(exprs).is_none() {
+        *exprs = exprs.iter().cloned().collect();
+    }
+    Arc::get_mut(exprs).expect("expressions were just copied")
+}
+
+/// # Blocks inside of an expression
+fn blocks(expr: &Expr) -> Vec<&Exprs> {
+    match &expr.cont {
+        ExprCont::Immediate(Value::Closure(cl)) => vec![&cl.code],
+        ExprCont::Keyword(KeywordKind::FnDef { code, .. }) => vec![code],
+        ExprCont::Keyword(KeywordKind::Ifs { branches }) => branches
+            .iter()
+            .flat_map(|branch| [&branch.check, &branch.code])
+            .collect(),
+        ExprCont::Keyword(KeywordKind::While { check, code }) => vec![check, code],
+        ExprCont::Keyword(KeywordKind::Switch { cases, default }) => cases
+            .iter()
+            .map(|case| &case.code)
+            .chain(default.as_ref())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn collect_fns(exprs: &[Expr], fns: &mut HashSet<FnName>) {
+    for expr in exprs {
+        match &expr.cont {
+            ExprCont::Keyword(KeywordKind::FnDef { name, .. }) => {
+                fns.insert(name.clone());
+            }
+            ExprCont::IncludedCode(code) => collect_fns(&code.exprs, fns),
+            _ => {}
+        }
+        for block in blocks(expr) {
+            collect_fns(block, fns);
+        }
+    }
+}
+
+fn find_conflicts(exprs: &[Expr], source: &Path, found: &mut Vec<Diagnostic>) {
+    let is_builtin = |name: &str| Builtin::from_name(name).is_some();
+    for expr in exprs {
+        let mut report = |kind| {
+            found.push(Diagnostic {
+                source: source.to_path_buf(),
+                span: expr.span.clone(),
+                kind,
+            });
+        };
+        let args: Vec<_> = match &expr.cont {
+            ExprCont::Keyword(KeywordKind::FnDef { name, args, .. }) => {
+                if is_builtin(name) {
+                    let name = name.clone();
+                    report(DiagnosticKind::ShadowedFn { name });
+                }
+                match args {
+                    FnArgs::Args(args) => args.iter().collect(),
+                    FnArgs::AllStack => Vec::new(),
+                }
+            }
+            ExprCont::Immediate(Value::Closure(cl)) => {
+                cl.get_args().get_unfilled_args().iter().rev().collect()
+            }
+            _ => Vec::new(),
+        };
+        for arg in args.into_iter().filter(|arg| is_builtin(arg.get_name())) {
+            let name = arg.get_name().to_string();
+            report(DiagnosticKind::ShadowedArg { name });
+        }
+        if let ExprCont::IncludedCode(code) = &expr.cont {
+            find_conflicts(&code.exprs, &code.source, found);
+        }
+        for block in blocks(expr) {
+            find_conflicts(block, source, found);
+        }
+    }
+}
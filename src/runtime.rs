@@ -1,12 +1,37 @@
 mod builtins;
+pub use builtins::{BUILTINS, Builtin};
+mod bytecode;
+pub(crate) use bytecode::Chunk;
+use bytecode::{Bodies, Op};
+mod capability;
+mod debug;
+mod hook;
+mod interrupt;
+mod limits;
 pub mod module;
+mod output;
+mod scope;
 mod stack;
+mod task;
+pub use capability::{Capabilities, Capability};
+pub use debug::{Breakpoints, DebugHook, Debugger, Pause, PauseReason, Step};
+pub use hook::{Hook, TypedHook};
+pub use interrupt::{Interrupt, Interruption};
+use limits::Budget;
+pub use limits::Limits;
+pub use output::Capture;
+use output::Outputs;
+use scope::FnTable;
 use stack::*;
+use task::{Action, Call, Control, Finish, Machine};
+pub use task::{Task, TaskState};
 
 use crate::*;
 use std::boxed::Box;
 use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(thiserror::Error, Debug)]
 enum RuntimeError {
@@ -16,71 +41,212 @@ enum RuntimeError {
     RuntimeRaw(#[from] RuntimeErrorKind),
 }
 
-type Rtk = crate::error::RuntimeErrorKind;
-type CResult<T> = std::result::Result<T, error::RuntimeErrorCtx>;
-type MixedResult<T> = std::result::Result<T, RuntimeError>;
-
-#[derive(Clone, Debug)]
-pub enum Hook {
-    Raw(fn(&mut runtime::Context, &Path)),
-    WithError(fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>),
-}
-
-impl Hook {
-    pub fn call(&self, ctx: &mut runtime::Context, source: &Path) -> Result<(), RuntimeErrorKind> {
+impl RuntimeError {
+    // the error happened while executing the expression
+    fn at(self, err_ctx: ErrCtx) -> RuntimeErrorCtx {
         match self {
-            Hook::Raw(c) => {
-                c(ctx, source);
-                Ok(())
-            }
-            Hook::WithError(c) => c(ctx, source),
+            RuntimeError::RuntimeRaw(e) => RuntimeErrorCtx::new(err_ctx, e),
+            RuntimeError::RuntimeCtx(e) => e.append_stack(err_ctx),
         }
     }
 }
 
-impl From<fn(&mut runtime::Context, &Path)> for Hook {
-    fn from(value: fn(&mut runtime::Context, &Path)) -> Self {
-        Hook::Raw(value)
-    }
-}
-impl From<fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>> for Hook {
-    fn from(value: fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>) -> Self {
-        Hook::WithError(value)
-    }
+type Rtk = crate::error::RuntimeErrorKind;
+type CResult<T> = std::result::Result<T, error::RuntimeErrorCtx>;
+type MixedResult<T> = std::result::Result<T, RuntimeError>;
+
+/// # State of the host shared by every frame of an execution
+///
+/// Moved into the frame of each called function, and back once it returns
+#[derive(Default, Debug)]
+struct Shared {
+    budget: Budget,
+    capabilities: Capabilities,
+    debugger: Option<Debugger>,
+    outputs: Outputs,
+    bodies: Bodies,
+    yield_requested: bool,
 }
 
+/// # Source of the errors in calls made from rust
+const HOST_SOURCE: &str = "<host>";
+
 #[derive(Default, Debug)]
 pub struct Context {
-    vars: HashMap<String, Value>,
-    fns: HashMap<FnName, FnDef>,
+    // shared with the frames of the called functions, and copied when they write to it
+    vars: Arc<HashMap<String, Value>>,
+    fns: FnTable,
     pub stack: Stack,
-    args: Option<HashMap<ArgName, FnArg>>,
-    rust_fns: HashMap<FnName, Hook>,
+    args: Option<Args>,
+    rust_fns: Arc<HashMap<Symbol, Hook>>,
     trc: TypeResolutionBuilder,
-    enabled_modules: HashSet<String>,
+    enabled_modules: Arc<HashSet<String>>,
+    shared: Shared,
 }
 
 impl Context {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            fns: HashMap::new(),
-            vars: HashMap::new(),
+            fns: FnTable::default(),
+            vars: Arc::default(),
             stack: Stack::new(),
-            rust_fns: HashMap::new(),
+            rust_fns: Arc::default(),
             args: None,
             trc: TypeResolutionBuilder::new(),
-            enabled_modules: HashSet::new(),
+            enabled_modules: Arc::default(),
+            shared: Shared::default(),
         }
     }
 
+    /// # Limit the resources used by scripts
+    ///
+    /// See [Limits]
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.set_limits(limits);
+        self
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.shared.budget.limits = limits;
+    }
+
+    /// # Restrict the builtins that reach outside of the script
+    ///
+    /// See [Capabilities]
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.set_capabilities(capabilities);
+        self
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.shared.capabilities = capabilities;
+    }
+
+    #[must_use]
+    pub fn get_capabilities(&self) -> Capabilities {
+        self.shared.capabilities
+    }
+
+    /// # Fail if the [capability](Capability) was denied by the host
+    ///
+    /// Meant for hooks that reach outside of the script, like the builtin `sh`
+    pub fn require_capability(&self, capability: Capability) -> Result<(), RuntimeErrorKind> {
+        if self.shared.capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(RuntimeErrorKind::CapabilityDenied(capability))
+        }
+    }
+
+    /// # Pause the execution on breakpoints and steps
+    ///
+    /// See [Debugger]
+    #[must_use]
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.set_debugger(Some(debugger));
+        self
+    }
+
+    pub fn set_debugger(&mut self, debugger: Option<Debugger>) {
+        self.shared.debugger = debugger;
+    }
+
+    pub fn take_debugger(&mut self) -> Option<Debugger> {
+        self.shared.debugger.take()
+    }
+
+    /// # Write the output of `print` somewhere other than the standard output
+    ///
+    /// See [Capture] to keep the output in memory
+    #[must_use]
+    pub fn with_output(mut self, out: impl Write + Send + 'static) -> Self {
+        self.set_output(out);
+        self
+    }
+
+    pub fn set_output(&mut self, out: impl Write + Send + 'static) {
+        self.shared.outputs.out = Box::new(out);
+    }
+
+    /// # Write the output of the `debug$*` builtins somewhere other than the standard error
+    #[must_use]
+    pub fn with_debug_output(mut self, debug: impl Write + Send + 'static) -> Self {
+        self.set_debug_output(debug);
+        self
+    }
+
+    pub fn set_debug_output(&mut self, debug: impl Write + Send + 'static) {
+        self.shared.outputs.debug = Box::new(debug);
+    }
+
+    /// # Output used by `print`, for hooks that write text
+    pub fn output(&mut self) -> &mut (dyn Write + Send) {
+        &mut self.shared.outputs.out
+    }
+
+    /// # Output used by the `debug$*` builtins
+    pub fn debug_output(&mut self) -> &mut (dyn Write + Send) {
+        &mut self.shared.outputs.debug
+    }
+
+    /// # Pause the [Task] being advanced after the current expression
+    ///
+    /// Meant for hooks that wait on the host. Ignored by
+    /// [`execute_entire_code`](Context::execute_entire_code)
+    pub fn request_yield(&mut self) {
+        self.shared.yield_requested = true;
+    }
+
+    /// # Handle to stop the execution from another thread
+    ///
+    /// See [Interrupt]
+    #[must_use]
+    pub fn interrupt_handle(&self) -> Interrupt {
+        self.shared.budget.interrupt.clone()
+    }
+
+    /// # Amount of expressions executed since the last [reset](Context::reset_usage)
+    #[must_use]
+    pub fn executed_exprs(&self) -> usize {
+        self.shared.budget.executed_exprs()
+    }
+
+    /// # Reset the counters used by the [limits](Limits)
+    ///
+    /// Useful to give each script a fresh budget when reusing the context. The timeout starts
+    /// again from now
+    pub fn reset_usage(&mut self) {
+        self.shared.budget.reset();
+    }
+
     pub fn add_module(&mut self, module: module::Module) {
-        self.rust_fns.extend(module.funcs);
-        self.enabled_modules.insert(module.name);
+        let funcs = module.funcs.into_iter();
+        let funcs = funcs.map(|(name, hook)| (Symbol::intern(&name), hook));
+        Arc::make_mut(&mut self.rust_fns).extend(funcs);
+        Arc::make_mut(&mut self.enabled_modules).insert(module.name);
     }
 
     pub fn add_rust_hook(&mut self, RustStckFn { name, code }: RustStckFn) -> Option<Hook> {
-        self.rust_fns.insert(name, Hook::Raw(code))
+        Arc::make_mut(&mut self.rust_fns).insert(Symbol::intern(&name), Hook::Raw(code))
+    }
+
+    pub fn add_hook(&mut self, name: impl Into<String>, hook: Hook) -> Option<Hook> {
+        Arc::make_mut(&mut self.rust_fns).insert(Symbol::intern(&name.into()), hook)
+    }
+
+    /// # Register a rust closure with typed arguments
+    ///
+    /// See [`Hook::typed`]
+    pub fn add_typed_fn<Args>(
+        &mut self,
+        name: impl Into<String>,
+        closure: impl TypedHook<Args>,
+    ) -> Option<Hook> {
+        let name = name.into();
+        self.add_hook(name.clone(), Hook::typed(name, closure))
     }
 
     #[must_use]
@@ -93,78 +259,162 @@ impl Context {
         &self.vars
     }
 
+    /// # Arguments of the function or closure being executed
+    ///
+    /// `None` on the top level code and in functions that take the entire stack
+    #[must_use]
+    pub fn get_args(&self) -> Option<impl Iterator<Item = (&str, &Value)>> {
+        let args = self.args.as_ref()?;
+        Some(args.iter())
+    }
+
+    /// # Names and arguments of every user-defined function in this context
+    pub fn get_fns(&self) -> impl Iterator<Item = (&str, &FnArgs)> {
+        self.fns
+            .iter()
+            .map(|(name, def)| (name.as_str(), &def.args))
+    }
+
+    #[must_use]
+    pub fn get_modules(&self) -> &HashSet<String> {
+        &self.enabled_modules
+    }
+
     #[must_use]
     pub fn take_stack(self) -> Stack {
         self.stack
     }
 
-    fn frame_fn(
-        fns: HashMap<FnName, FnDef>,
-        vars: HashMap<String, Value>,
-        args_ins: FnArgsInsCap,
-        rust_fns: HashMap<FnName, Hook>,
-        trc: TypeResolutionBuilder,
-        enabled_modules: HashSet<String>,
-    ) -> Self {
-        let (stack, args) = match args_ins {
-            FnArgsInsCap::AllStack(xs) => (Stack::new_with(xs), None),
-            FnArgsInsCap::Args(args) => (Stack::new(), Some(args)),
-        };
+    // frame of a called function, sharing everything but the variables with its caller
+    fn frame(&self, vars: Arc<HashMap<String, Value>>, stack: Stack, args: Option<Args>) -> Self {
         Self {
             vars,
-            fns,
+            fns: self.fns.clone(),
             stack,
             args,
-            rust_fns,
-            trc,
-            enabled_modules,
+            rust_fns: self.rust_fns.clone(),
+            trc: self.trc.clone(),
+            enabled_modules: self.enabled_modules.clone(),
+            shared: Shared::default(),
         }
     }
 
-    fn frame_closure(
-        fns: HashMap<FnName, FnDef>,
-        vars: HashMap<String, Value>,
-        args: HashMap<ArgName, FnArg>,
-        rust_fns: HashMap<FnName, Hook>,
-        trc: TypeResolutionBuilder,
-        enabled_modules: HashSet<String>,
-    ) -> Self {
-        Self {
-            enabled_modules,
-            trc,
-            rust_fns,
-            fns,
-            vars,
-            args: Some(args),
-            stack: Stack::new(),
-        }
+    fn enter_frame(shared: &mut Shared, frame: &mut Context) -> Result<(), RuntimeErrorKind> {
+        shared.budget.enter_call()?;
+        std::mem::swap(shared, &mut frame.shared);
+        Ok(())
+    }
+
+    fn leave_frame(shared: &mut Shared, frame: &mut Context) {
+        std::mem::swap(shared, &mut frame.shared);
+        shared.budget.leave_call();
     }
 
-    pub fn execute_entire_code(&mut self, Code { source, exprs }: &Code) -> CResult<ControlFlow> {
-        for expr in exprs {
-            match self.execute_expr(expr, source)? {
-                ControlFlow::Continue => {}
-                c => return Ok(c),
+    pub fn execute_entire_code(&mut self, code: &Code) -> CResult<ControlFlow> {
+        self.shared.budget.start();
+        let mut machine = Machine::new(code);
+        machine.run_to_end(self)
+    }
+
+    /// # Call a user-defined function from rust, returning its outputs
+    ///
+    /// The arguments are given in the order they were declared, and are checked like in a call
+    /// made by stck. Functions without an argument list get all of them as their stack. Only
+    /// functions defined with `(fn)` are called, not builtins or hooks
+    ///
+    /// ```rust
+    /// use stck::internals::{RuntimeContext, Value};
+    /// let token_block = stck::api::get_tokens_str("(fn) [ a b ] sub { a b - }\n", "Callbacks", &mut stck::cache::Isolated::new()).unwrap();
+    /// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+    /// let mut ctx = RuntimeContext::new();
+    /// ctx.execute_entire_code(&code).unwrap();
+    /// let outputs = ctx.call_fn("sub", vec![Value::Num(5), Value::Num(2)]).unwrap();
+    /// assert_eq!(outputs, [Value::Num(3)]);
+    /// ```
+    pub fn call_fn(&mut self, name: &str, args: Vec<Value>) -> CResult<Vec<Value>> {
+        let name = name.to_string();
+        let origin = ExprCont::FnCall(name.clone(), Resolved::Unresolved);
+        // a name no symbol holds anymore can't have been defined
+        let symbol = Symbol::get(&name);
+        self.call_from_host(origin, args, |ctx, _| {
+            let user_fn = symbol.as_ref().and_then(|symbol| ctx.fns.get(symbol));
+            if let Some(FnArgs::Args(declared)) = user_fn.map(|f| &f.args)
+                && declared.len() < ctx.stack.len()
+            {
+                return Err(Rtk::TooManyArgs {
+                    name: name.clone(),
+                    expected: declared.len(),
+                    got: ctx.stack.len(),
+                }
+                .into());
             }
-        }
-        Ok(ControlFlow::Continue)
+            match symbol
+                .as_ref()
+                .and_then(|symbol| ctx.try_call_user_fn(symbol))
+            {
+                Some(call) => Ok(Some(call?)),
+                None => Err(Rtk::MissingUserFunction(name.clone()).into()),
+            }
+        })
     }
 
-    fn execute_code(&mut self, code: &[Expr], source: &Path) -> CResult<ControlFlow> {
-        for expr in code {
-            match self.execute_expr(expr, source)? {
-                ControlFlow::Continue => {}
-                c => return Ok(c),
+    /// # Call a closure from rust, returning its outputs
+    ///
+    /// The arguments fill the closure like `@` does, so giving less arguments than it needs
+    /// returns the partially filled closure
+    pub fn call_closure(&mut self, closure: Closure, args: Vec<Value>) -> CResult<Vec<Value>> {
+        let origin = ExprCont::FnCall("@".to_string(), Resolved::Builtin(Builtin::Fill));
+        self.call_from_host(origin, args, |ctx, source| {
+            let expected = closure.get_unfilled_args_count();
+            let args = ctx.stack.take();
+            if expected < args.len() {
+                return Err(Rtk::TooManyArgs {
+                    name: "closure".to_string(),
+                    expected,
+                    got: args.len(),
+                }
+                .into());
             }
-        }
-        Ok(ControlFlow::Continue)
+            let mut closure = closure;
+            for arg in args {
+                match closure.fill(arg)? {
+                    ClosureCurry::Partial(cl) => closure = cl,
+                    ClosureCurry::Full(cl) => return Ok(Some(ctx.call_user_closure(cl, source))),
+                }
+            }
+            ctx.stack.push_this(closure);
+            Ok(None)
+        })
     }
 
-    fn execute_check(&mut self, code: &[Expr], source: &Path) -> MixedResult<bool> {
-        let old_stack_size = self.stack.len();
-        for expr in code {
-            self.execute_expr(expr, source)?;
-        }
+    // executes the call made with the arguments as the stack, and takes its outputs
+    fn call_from_host(
+        &mut self,
+        origin: ExprCont,
+        args: Vec<Value>,
+        make_call: impl FnOnce(&mut Self, &Path) -> MixedResult<Option<Call>>,
+    ) -> CResult<Vec<Value>> {
+        let origin = Expr {
+            span: LineRange::default(),
+            cont: origin,
+        };
+        let source: Arc<Path> = Arc::from(Path::new(HOST_SOURCE));
+        self.shared.budget.start();
+        let caller_stack = std::mem::replace(&mut self.stack, Stack::new_with(args));
+        let result = match make_call(self, &source) {
+            Ok(Some(call)) => match Machine::call(self, origin.clone(), source.clone(), call) {
+                Ok(mut machine) => machine.run_to_end(self).map(|_| ()),
+                Err(e) => Err(RuntimeError::from(e).at(ErrCtx::new(&source, &origin))),
+            },
+            Ok(None) => Ok(()),
+            Err(e) => Err(e.at(ErrCtx::new(&source, &origin))),
+        };
+        let outputs = std::mem::replace(&mut self.stack, caller_stack);
+        result.map(|()| outputs.into_vec())
+    }
+
+    /// # Result of a check block, that should leave a single boolean on the stack
+    fn check_result(&mut self, old_stack_size: usize) -> Result<bool, RuntimeErrorKind> {
         let new_stack_size = self.stack.len();
         let new_should_stack_size = old_stack_size + 1;
         let correct_size = new_should_stack_size == new_stack_size;
@@ -179,27 +429,27 @@ impl Context {
         }?;
         match check {
             Value::Bool(b) if correct_size => Ok(b),
-            got => Err(RuntimeErrorKind::WrongTypeOnCheck { got }.into()),
+            got => Err(RuntimeErrorKind::WrongTypeOnCheck { got }),
         }
     }
 
-    fn execute_expr(&mut self, expr: &Expr, source: &Path) -> CResult<ControlFlow> {
-        match self.execute_expr_internal(expr, source) {
-            Ok(c) => Ok(c),
-            Err(RuntimeError::RuntimeRaw(e)) => {
-                Err(RuntimeErrorCtx::new(ErrCtx::new(source, expr), e))
-            }
-            Err(RuntimeError::RuntimeCtx(c)) => Err(c.append_stack(ErrCtx::new(source, expr))),
+    /// # Pause on the debugger and count the expression
+    fn begin_expr(&mut self, expr: &Expr, source: &Path) -> Result<(), RuntimeErrorKind> {
+        if let Some(mut debugger) = self.shared.debugger.take() {
+            debugger.before_expr(self, expr, source, self.shared.budget.depth());
+            self.shared.debugger = Some(debugger);
         }
+        self.shared.budget.tick()
     }
 
-    fn execute_expr_internal(&mut self, expr: &Expr, source: &Path) -> MixedResult<ControlFlow> {
-        match &expr.cont {
-            ExprCont::FnCall(name) => self.execute_fn(name, source)?,
-            ExprCont::Keyword(kw) => {
-                return self.execute_kw(kw, source);
-            }
-            ExprCont::Immediate(Value::Closure(cl)) => {
+    fn execute_op(&mut self, op: Op, chunk: &Chunk, source: &Arc<Path>) -> MixedResult<Action> {
+        match op {
+            Op::Nop => {}
+            Op::Push(idx) => self.stack.push(chunk.value(idx).clone()),
+            Op::Closure(idx) => {
+                let Value::Closure(cl) = chunk.value(idx) else {
+                    unreachable!("closure operation on another value");
+                };
                 let cl = cl.clone();
                 if let Some(args) = &self.args {
                     cl.set_parent_args(args.clone()).map_err(|old| {
@@ -211,176 +461,145 @@ impl Context {
                 }
                 self.stack.push(Value::Closure(cl));
             }
-            ExprCont::Immediate(v) => self.stack.push(v.clone()),
-            ExprCont::IncludedCode(Code { source, exprs }) => {
-                self.execute_code(exprs, source)?;
+            Op::Builtin(builtin) => {
+                if let Some(call) = self.execute_builtin(builtin, source)? {
+                    return Ok(Action::Call(Box::new(call)));
+                }
             }
-        }
-        Ok(ControlFlow::Continue)
-    }
-
-    fn execute_kw(&mut self, kw: &KeywordKind, source: &Path) -> MixedResult<ControlFlow> {
-        Ok(match kw {
-            KeywordKind::Require(module_name) => {
-                return if !self.enabled_modules.contains(module_name) {
-                    Err(RuntimeErrorKind::MissingModule(module_name.to_owned()).into())
-                } else {
-                    Ok(ControlFlow::Continue)
-                };
+            Op::Arg(slot) => {
+                let args = self
+                    .args
+                    .as_ref()
+                    .expect("argument slot outside of a function");
+                self.stack.push(args.slot(slot).clone());
+            }
+            Op::CallFn(idx) => {
+                let name = chunk.symbol(idx);
+                if let Some(call) = self.try_call_user_fn(name) {
+                    return Ok(Action::Call(Box::new(call?)));
+                }
+                // a function defined by the code can be missing when its definition was skipped
+                self.execute_hook(name, source)?;
             }
-            KeywordKind::DefinedGeneric(trc) => {
-                self.trc.add_generic(trc.clone());
-                ControlFlow::Continue
+            Op::Call(idx) => {
+                if let Some(call) = self.execute_fn(chunk.symbol(idx), source)? {
+                    return Ok(Action::Call(Box::new(call)));
+                }
             }
-            KeywordKind::IntoClosure { fn_name } => {
+            Op::Require(idx) => {
+                let module_name = chunk.symbol(idx).as_str();
+                if !self.enabled_modules.contains(module_name) {
+                    return Err(RuntimeErrorKind::MissingModule(module_name.to_owned()).into());
+                }
+            }
+            Op::DefineGeneric(idx) => self.trc.add_generic(chunk.generic(idx).clone()),
+            Op::IntoClosure(idx) => {
+                let fn_name = chunk.symbol(idx);
                 let fndef = self
                     .fns
                     .get(fn_name)
                     .ok_or(RuntimeErrorKind::MissingUserFunction(
                         fn_name.as_str().to_string(),
                     ))?;
+                let body = fndef.body.clone();
                 let closure = fndef
                     .clone()
                     .into_closure(fn_name.as_str(), self.trc.clone())?;
+                self.shared.bodies.insert(&closure.code, body);
                 self.stack.push_this(closure);
-                ControlFlow::Continue
             }
-            KeywordKind::BubbleError => {
+            Op::BubbleError(to) => {
                 let e = stack_pop!((self.stack) -> result as "result" for "(!) keyword")?;
                 match e {
                     Err(x) => {
                         self.stack.push_this(Err(x));
-                        ControlFlow::Return
-                    }
-                    Ok(x) => {
-                        self.stack.push_this(x);
-                        ControlFlow::Continue
+                        return Ok(Action::Jump(to));
                     }
+                    Ok(x) => self.stack.push_this(x),
                 }
             }
-            KeywordKind::Return => ControlFlow::Return,
-            KeywordKind::Break => ControlFlow::Break,
-            KeywordKind::Switch { cases, default } => {
+            Op::Switch(idx) => {
                 let cmp = self
                     .stack
                     .pop()
                     .ok_or(RuntimeErrorKind::SwitchCaseWithNoValue)?;
-                for case in cases {
-                    if case.test == cmp {
-                        return self
-                            .execute_code(&case.code, source)
-                            .map_err(RuntimeError::from);
-                    }
-                }
-                match default {
-                    Some(code) => self.execute_code(code, source)?,
-                    None => ControlFlow::Continue,
-                }
+                return Ok(Action::Jump(chunk.switch(idx, &cmp)));
             }
-            KeywordKind::Ifs { branches } => {
-                for branch in branches {
-                    if self.execute_check(&branch.check, source)? {
-                        return self
-                            .execute_code(&branch.code, source)
-                            .map_err(RuntimeError::from);
-                    }
-                }
-                ControlFlow::Continue
-            }
-            KeywordKind::While { check, code } => {
-                while self.execute_check(check, source)? {
-                    match self.execute_code(code, source)? {
-                        ControlFlow::Break => break,
-                        ControlFlow::Return => return Ok(ControlFlow::Return),
-                        ControlFlow::Continue => {}
-                    }
-                }
-                ControlFlow::Continue
-            }
-            KeywordKind::FnDef {
-                name,
-                scope,
-                code,
-                args,
-                out_args,
-            } => {
+            Op::DefineFn(idx) => {
+                let proto = chunk.fn_proto(idx);
                 self.fns.insert(
-                    name.clone(),
+                    proto.name.clone(),
                     FnDef::new(
-                        scope.clone(),
-                        code.clone(),
-                        args.clone(),
-                        out_args.clone().map(TypedOutputs::from),
-                        source.to_path_buf(),
+                        proto.scope.clone(),
+                        proto.code.clone(),
+                        proto.args.clone(),
+                        proto.output_types.clone(),
+                        source.clone(),
+                        proto.body.clone(),
                     ),
                 );
-                ControlFlow::Continue
             }
-        })
-    }
-
-    fn execute_fn(&mut self, name: &FnName, source: &Path) -> MixedResult<()> {
-        // builtin fn should handle stack pop and push
-        // and are always given precedence
-        match self.try_execute_builtin(name.as_str(), source) {
-            Ok(Some(())) => return Ok(()),
-            Ok(None) => {}
-            Err(e) => return Err(e),
+            Op::Expr(_) | Op::Mark | Op::Test { .. } | Op::Jump(_) | Op::Exit(_) => {
+                unreachable!("control operations are executed by the machine")
+            }
         }
+        Ok(Action::Next)
+    }
 
-        if let Some(arg) = self.try_get_arg(name) {
+    // builtins are always given precedence, so they were resolved when compiling
+    fn execute_fn(&mut self, name: &Symbol, source: &Path) -> MixedResult<Option<Call>> {
+        if let Some(arg) = self.try_get_arg(name.as_str()) {
             // try_get_arg should not pop from the stack and has higher precedence than user-defined funcs.
             // this was done to avoid confusion if an outer-scoped function was used instead of an argument
             self.stack.push(arg);
-        } else if let Some(rets) = self.try_execute_user_fn(name) {
-            // try_execute_user_fn should handle stack pop
+        } else if let Some(call) = self.try_call_user_fn(name) {
+            // try_call_user_fn should handle stack pop
             // and have the lowest precedence, since they traverse the scopes
-            self.stack.pushn(rets?);
-        } else if let Some(res) = self.try_execute_rust_hook(name, source) {
-            res?;
+            return Ok(Some(call?));
         } else {
-            return Err(Rtk::MissingIdent(name.clone()).into());
+            self.execute_hook(name, source)?;
         }
-        Ok(())
+        Ok(None)
     }
 
-    fn try_execute_user_closure(
-        &mut self,
-        closure: FullClosure,
-        source: &Path,
-    ) -> MixedResult<Vec<Value>> {
-        let mut cl_ctx = Context::frame_closure(
-            self.fns.clone(),
-            self.vars.clone(),
-            closure.request_args,
-            self.rust_fns.clone(),
-            self.trc.clone(),
-            self.enabled_modules.clone(),
-        );
-        cl_ctx.execute_code(&closure.code, source)?;
-        let output = cl_ctx.take_stack().into_vec();
-        // TODO: use TRC instance from closure
-        let mut trc: TypeResolutionContext = self.trc.clone().into();
-        if let Some(output_types) = closure.output_types {
-            match trc.check_outputs(&output_types, &output) {
-                Err(TypedOutputError::TypeError(expected, got)) => {
-                    Err(RuntimeErrorKind::Type(expected, Box::new(got)))
-                }
-                Err(TypedOutputError::OutputCountError { expected, got }) => {
-                    Err(RuntimeErrorKind::OutputClosureCount { expected, got })
-                }
-                Ok(()) => Ok(()),
-            }?;
+    fn execute_hook(&mut self, name: &Symbol, source: &Path) -> Result<(), RuntimeErrorKind> {
+        match self.try_execute_rust_hook(name, source) {
+            Some(res) => res,
+            None => Err(Rtk::MissingIdent(name.as_str().to_string())),
         }
-        Ok(output)
     }
 
-    fn try_execute_user_fn(&mut self, name: &FnName) -> Option<MixedResult<Vec<Value>>> {
+    fn fill_closure(&mut self, fn_name: &str, source: &Path) -> MixedResult<Option<Call>> {
+        let v = stack_pop!((self.stack) -> * as "value" for fn_name)?;
+        let cl = stack_pop!((self.stack) -> closure as "closure" for fn_name)?;
+        match cl.fill(v)? {
+            ClosureCurry::Partial(cl) => {
+                self.stack.push_this(cl);
+                Ok(None)
+            }
+            ClosureCurry::Full(cl) => Ok(Some(self.call_user_closure(cl, source))),
+        }
+    }
+
+    fn call_user_closure(&mut self, closure: FullClosure, source: &Path) -> Call {
+        let cl_ctx = self.frame(self.vars.clone(), Stack::new(), Some(closure.request_args));
+        // closures don't know where they were made, so they run as part of their caller
+        let body = Control::new(self.shared.bodies.get(&closure.code), Arc::from(source));
+        Call::new(
+            cl_ctx,
+            body,
+            Finish::Closure {
+                output_types: closure.output_types,
+            },
+        )
+    }
+
+    fn try_call_user_fn(&mut self, name: &Symbol) -> Option<MixedResult<Call>> {
         let user_fn = self.fns.get(name)?;
         let mut trc: TypeResolutionContext = self.trc.clone().into();
 
         let vars = match user_fn.scope {
-            FnScope::Isolated => HashMap::new(),
+            FnScope::Isolated => Arc::default(),
             _ => self.vars.clone(),
         };
 
@@ -417,46 +636,77 @@ impl Context {
             }
             FnArgs::AllStack => FnArgsInsCap::AllStack(self.stack.take()),
         };
-        let mut fn_ctx = Context::frame_fn(
-            self.fns.clone(),
-            vars,
-            args,
-            self.rust_fns.clone(),
-            self.trc.clone(),
-            self.enabled_modules.clone(),
-        );
-
-        // handle (return) kw and RT errors inside functions
-        if let Err(e) = fn_ctx.execute_code(&user_fn.code, &user_fn.source) {
-            return Some(Err(e.into()));
-        }
+        let (stack, args) = match args {
+            FnArgsInsCap::AllStack(xs) => (Stack::new_with(xs), None),
+            FnArgsInsCap::Args(args) => (Stack::new(), Some(args)),
+        };
+        let fn_ctx = self.frame(vars, stack, args);
+        let body = Control::new(user_fn.body.clone(), user_fn.source.clone());
+        let finish = Finish::Fn {
+            name: name.clone(),
+            global: matches!(user_fn.scope, FnScope::Global),
+            output_types: user_fn.output_types.clone(),
+            trc,
+        };
+        Some(Ok(Call::new(fn_ctx, body, finish)))
+    }
 
-        if let FnScope::Global = user_fn.scope {
-            self.vars.extend(fn_ctx.vars);
-        }
-        let output = fn_ctx.stack.into_vec();
-        if let Some(out_tt) = &user_fn.output_types {
-            let err = match trc.check_outputs(out_tt, &output) {
-                Ok(()) => None,
-                Err(TypedOutputError::TypeError(t, v)) => Some(Rtk::Type(t, Box::new(v))),
-                Err(TypedOutputError::OutputCountError { expected, got }) => {
-                    Some(Rtk::OutputCount {
-                        fn_name: name.clone(),
-                        expected,
-                        got,
-                    })
+    /// # Give the results of a finished function or closure to its caller
+    fn finish_call(&mut self, finish: Finish, frame: Context) -> Result<(), RuntimeErrorKind> {
+        let output = match finish {
+            Finish::Fn {
+                name,
+                global,
+                output_types,
+                mut trc,
+            } => {
+                // the frame started with the variables of the caller, which didn't change since
+                if global {
+                    self.vars = frame.vars;
                 }
-            };
-            if let Some(err) = err {
-                return Some(Err(err.into()));
+                let output = frame.stack.into_vec();
+                if let Some(out_tt) = &output_types {
+                    match trc.check_outputs(out_tt, &output) {
+                        Ok(()) => {}
+                        Err(TypedOutputError::TypeError(t, v)) => {
+                            return Err(Rtk::Type(t, Box::new(v)));
+                        }
+                        Err(TypedOutputError::OutputCountError { expected, got }) => {
+                            return Err(Rtk::OutputCount {
+                                fn_name: name.as_str().to_string(),
+                                expected,
+                                got,
+                            });
+                        }
+                    }
+                }
+                output
+            }
+            Finish::Closure { output_types } => {
+                let output = frame.take_stack().into_vec();
+                // TODO: use TRC instance from closure
+                let mut trc: TypeResolutionContext = self.trc.clone().into();
+                if let Some(output_types) = output_types {
+                    match trc.check_outputs(&output_types, &output) {
+                        Err(TypedOutputError::TypeError(expected, got)) => {
+                            Err(RuntimeErrorKind::Type(expected, Box::new(got)))
+                        }
+                        Err(TypedOutputError::OutputCountError { expected, got }) => {
+                            Err(RuntimeErrorKind::OutputClosureCount { expected, got })
+                        }
+                        Ok(()) => Ok(()),
+                    }?;
+                }
+                output
             }
-        }
-        Some(Ok(output))
+        };
+        self.stack.pushn(output);
+        Ok(())
     }
 
-    fn try_get_arg(&mut self, name: &ArgName) -> Option<Value> {
+    fn try_get_arg(&mut self, name: &str) -> Option<Value> {
         if let Some(args) = &self.args {
-            args.get(name).map(|arg| arg.0.clone())
+            args.get(name).cloned()
         } else {
             None
         }
@@ -464,42 +714,52 @@ impl Context {
 
     fn try_execute_rust_hook(
         &mut self,
-        name: &FnName,
+        name: &Symbol,
         source: &Path,
     ) -> Option<Result<(), RuntimeErrorKind>> {
         let rfn = self.rust_fns.get(name)?.clone();
         Some(rfn.call(self, source))
     }
 
-    fn try_execute_builtin(&mut self, fn_name: &str, source: &Path) -> MixedResult<Option<()>> {
-        match fn_name {
+    // builtins should handle stack pop and push
+    fn execute_builtin(&mut self, builtin: Builtin, source: &Path) -> MixedResult<Option<Call>> {
+        let fn_name = builtin.name();
+        match builtin {
+            // calling a closure needs a new frame, so it's the only builtin that makes a call
+            Builtin::Fill => return self.fill_closure(fn_name, source),
             // seq system
-            "print" => {
+            Builtin::Print => {
                 let cont = self
                     .stack
                     .pop_this(Value::get_str)
                     .expect("`print` needs [string]")
                     .expect("`print`'s [string] needs to be a string");
-                print!("{cont}");
+                write!(self.output(), "{cont}").map_err(Rtk::Output)?;
             }
-            "sys$exit" => {
+            Builtin::SysExit => {
+                self.require_capability(Capability::Exit)?;
                 let code = stack_pop!(
                     (self.stack) -> num as "exit_code" for fn_name
                 )?;
                 std::process::exit(code as i32);
             }
-            "sys$argv" => {
-                let args: Vec<_> = std::env::args().map(Value::Str).collect();
+            Builtin::SysArgv => {
+                self.require_capability(Capability::Argv)?;
+                let args: Vec<_> = std::env::args().map(Value::from).collect();
                 self.stack.push_this(args);
             }
-            "sh" => {
+            Builtin::Sh => {
+                self.require_capability(Capability::Spawn)?;
                 let shell_cmd = stack_pop!(
                     (self.stack) -> str as "command" for fn_name
                 )?;
-                let out = builtins::sh(&shell_cmd).map(Value::Num).map_err(Value::Str);
+                let out = builtins::sh(&shell_cmd, &self.shared.budget)?
+                    .map(Value::Num)
+                    .map_err(Value::from);
                 self.stack.push_this(out);
             }
-            "write-to" => {
+            Builtin::WriteTo => {
+                self.require_capability(Capability::FileWrite)?;
                 let file = stack_pop!(
                     (self.stack) -> str as "file" for fn_name
                 )?;
@@ -508,12 +768,12 @@ impl Context {
                 )?;
                 let out = builtins::write_to(&cont, &file)
                     .map(Value::Num)
-                    .map_err(Value::Str);
+                    .map_err(Value::from);
                 self.stack.push_this(out);
             }
 
             // seq math seq logic
-            "-" => {
+            Builtin::Sub => {
                 let rhs = stack_pop!(
                     (self.stack) -> num as "rhs" for fn_name
                 )?;
@@ -522,7 +782,7 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs - rhs);
             }
-            ".-" => {
+            Builtin::FloatSub => {
                 let rhs = stack_pop!(
                     (self.stack) -> float as "rhs" for fn_name
                 )?;
@@ -531,7 +791,7 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs - rhs);
             }
-            "*" => {
+            Builtin::Mul => {
                 let rhs = stack_pop!(
                     (self.stack) -> num as "rhs" for fn_name
                 )?;
@@ -540,7 +800,7 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs * rhs);
             }
-            ".*" => {
+            Builtin::FloatMul => {
                 let rhs = stack_pop!(
                     (self.stack) -> float as "rhs" for fn_name
                 )?;
@@ -549,7 +809,7 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs * rhs);
             }
-            "≃" => {
+            Builtin::Similar => {
                 use Value::*;
                 let rhs = stack_pop!((self.stack) -> * as "rhs" for fn_name)?;
                 let lhs = stack_pop!((self.stack) -> * as "lhs" for fn_name)?;
@@ -567,7 +827,7 @@ impl Context {
                 }?;
                 self.stack.push_this(eq);
             }
-            "=" => {
+            Builtin::Eq => {
                 use Value::*;
                 let rhs = stack_pop!((self.stack) -> * as "rhs" for fn_name)?;
                 let lhs = stack_pop!((self.stack) -> * as "lhs" for fn_name)?;
@@ -585,7 +845,7 @@ impl Context {
                 };
                 self.stack.push_this(eq);
             }
-            ">" => {
+            Builtin::Greater => {
                 use Value::*;
                 let rhs = stack_pop!((self.stack) -> * as "rhs" for fn_name)?;
                 let lhs = stack_pop!((self.stack) -> * as "lhs" for fn_name)?;
@@ -600,7 +860,7 @@ impl Context {
                 };
                 self.stack.push_this(eq);
             }
-            "%" => {
+            Builtin::Rem => {
                 let rhs = stack_pop!(
                     (self.stack) -> num as "rhs" for fn_name
                 )?;
@@ -609,7 +869,7 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs % rhs);
             }
-            "%." => {
+            Builtin::FloatRem => {
                 let rhs = stack_pop!(
                     (self.stack) -> float as "rhs" for fn_name
                 )?;
@@ -618,34 +878,20 @@ impl Context {
                 )?;
                 self.stack.push_this(lhs % rhs);
             }
-            "@" => {
-                let v = stack_pop!((self.stack) -> * as "value" for fn_name)?;
-                let cl = stack_pop!((self.stack) -> closure as "closure" for fn_name)?;
-                match cl.fill(v)? {
-                    ClosureCurry::Partial(cl) => {
-                        self.stack.push_this(cl);
-                    }
-                    ClosureCurry::Full(cl) => {
-                        let result = self.try_execute_user_closure(cl, source)?;
-                        self.stack.pushn(result);
-                    }
-                }
-            }
-
             // seq variables
-            "stack$len" => {
+            Builtin::StackLen => {
                 self.stack.push_this(self.stack.len() as isize);
             }
-            "set" => {
+            Builtin::Set => {
                 let name = stack_pop!(
                     (self.stack) -> str as "name" for fn_name
                 )?;
                 let value = stack_pop!(
                     (self.stack) -> * as "value" for fn_name
                 )?;
-                self.vars.insert(name, value);
+                Arc::make_mut(&mut self.vars).insert(name, value);
             }
-            "get" => {
+            Builtin::Get => {
                 let name = stack_pop!(
                     (self.stack) -> str as "name" for fn_name
                 )?;
@@ -660,7 +906,7 @@ impl Context {
             }
 
             // seq error handeling
-            "!" => {
+            Builtin::Unwrap => {
                 let may = stack_pop!((self.stack) -> * as "Monad" for fn_name)?;
                 match may {
                     Value::Result(r) => match *r {
@@ -685,33 +931,33 @@ impl Context {
                     }
                 }
             }
-            "ok" => {
+            Builtin::Ok => {
                 let v = self.stack.pop().expect("`ok` needs [value]");
                 self.stack.push_this(Ok(v));
             }
-            "err" => {
+            Builtin::Err => {
                 let v = self.stack.pop().expect("`err` needs [value]");
                 self.stack.push_this(Err(v));
             }
-            "none" => {
+            Builtin::None => {
                 self.stack.push_this(None);
             }
-            "some" => {
+            Builtin::Some => {
                 let v = stack_pop!((self.stack) -> * as "v" for fn_name)?;
                 self.stack.push_this(Some(v));
             }
-            "&result$is-ok" => {
+            Builtin::ResultIsOk => {
                 let is_ok = stack_pop!((self.stack) -> &result as "result" for fn_name)?.is_ok();
                 self.stack.push_this(is_ok);
             }
-            "&option$is-some" => {
+            Builtin::OptionIsSome => {
                 let is_some =
                     stack_pop!((self.stack) -> &option as "option" for fn_name)?.is_some();
                 self.stack.push_this(is_some);
             }
 
             // seq string
-            "%%" => {
+            Builtin::Format => {
                 let fmt = self
                     .stack
                     .pop_this(Value::get_str)
@@ -720,7 +966,7 @@ impl Context {
                 let out = builtins::fmt(&fmt, &mut self.stack);
                 self.stack.push_this(out?);
             }
-            "&str$has-prefix" => {
+            Builtin::StrHasPrefix => {
                 let prefix = stack_pop!(
                     (self.stack) -> str as "prefix" for fn_name
                 )?;
@@ -728,13 +974,13 @@ impl Context {
                 let has = s.starts_with(&prefix);
                 self.stack.push_this(has);
             }
-            "str$trim" => {
+            Builtin::StrTrim => {
                 let v = stack_pop!(
                     (self.stack) -> str as "string" for fn_name
                 )?;
                 self.stack.push_this(v.trim().to_owned());
             }
-            "str$remove-prefix" => {
+            Builtin::StrRemovePrefix => {
                 let prefix = self
                     .stack
                     .pop_this(Value::get_str)
@@ -748,23 +994,23 @@ impl Context {
                 let out = st.strip_prefix(&prefix).map(String::from).unwrap_or(st);
                 self.stack.push_this(out);
             }
-            "str$into-arr" => {
+            Builtin::StrIntoArr => {
                 let string = stack_pop!((self.stack) -> str as "string" for fn_name)?;
                 let chars: Vec<_> = string.chars().map(Value::from).collect();
                 self.stack.push_this(chars);
             }
 
             // seq array
-            "&arr$len" => {
+            Builtin::ArrLen => {
                 let arr_len = stack_pop!((self.stack) -> &arr as "array" for fn_name)?.len();
                 self.stack.push_this(arr_len as isize);
             }
-            "arr$reverse" => {
-                let mut arr = stack_pop!((self.stack) -> arr as "arr" for fn_name)?;
-                arr.reverse();
+            Builtin::ArrReverse => {
+                let mut arr = stack_pop!((self.stack) -> shared_arr as "arr" for fn_name)?;
+                Arc::make_mut(&mut arr).reverse();
                 self.stack.push_this(arr);
             }
-            "arr$unpack" => {
+            Builtin::ArrUnpack => {
                 let arr = self
                     .stack
                     .pop_this(Value::get_arr)
@@ -774,7 +1020,7 @@ impl Context {
                 self.stack.pushn(arr);
                 self.stack.push_this(len as isize);
             }
-            "arr$pack-n" => {
+            Builtin::ArrPackN => {
                 let count = stack_pop!((self.stack) -> num as "count" for fn_name)?;
                 let xs = self.stack.popn(count as usize).ok_or_else(|| {
                     let got = self.stack.len() as isize;
@@ -787,20 +1033,20 @@ impl Context {
                 })?;
                 self.stack.push_this(xs);
             }
-            "arr$new" => {
+            Builtin::ArrNew => {
                 self.stack.push_this(Vec::new());
             }
-            "arr$append" => {
+            Builtin::ArrAppend => {
                 let mut arr = self
                     .stack
-                    .pop_this(Value::get_arr)
+                    .pop_this(Value::get_shared_arr)
                     .expect("arr$append` needs [value array]")
                     .expect("arr$append` [array] must be an array");
                 let any = self.stack.pop().expect("`arr$append` needs [value array]");
-                arr.push(any);
+                Arc::make_mut(&mut arr).push(any);
                 self.stack.push_this(arr);
             }
-            "arr$join" => {
+            Builtin::ArrJoin => {
                 let joiner = stack_pop!((self.stack) -> str as "joiner" for fn_name)?;
                 let arr = stack_pop!((self.stack) -> arr as "array" for fn_name)?;
                 let arr = arr
@@ -816,18 +1062,18 @@ impl Context {
                     })?;
                 self.stack.push_this(arr.join(&joiner));
             }
-            "arr$pop" => {
-                let mut arr = stack_pop!((self.stack) -> arr as "array" for fn_name)?;
-                let v = arr.pop();
+            Builtin::ArrPop => {
+                let mut arr = stack_pop!((self.stack) -> shared_arr as "array" for fn_name)?;
+                let v = Arc::make_mut(&mut arr).pop();
                 self.stack.push_this(arr);
                 self.stack.push_this(v);
             }
 
             // seq map
-            "map$new" => {
+            Builtin::MapNew => {
                 self.stack.push_this(HashMap::new());
             }
-            "map$insert-kv" => {
+            Builtin::MapInsertKv => {
                 let value = stack_pop!(
                     (self.stack) -> * as "value" for fn_name
                 )?;
@@ -835,12 +1081,12 @@ impl Context {
                     (self.stack) -> str as "key" for fn_name
                 )?;
                 let mut map = stack_pop!(
-                    (self.stack) -> map as "map" for fn_name
+                    (self.stack) -> shared_map as "map" for fn_name
                 )?;
-                map.insert(key, value);
+                Arc::make_mut(&mut map).insert(key, value);
                 self.stack.push_this(map);
             }
-            "map$get" => {
+            Builtin::MapGet => {
                 let key = stack_pop!(
                     (self.stack) -> str as "key" for fn_name
                 )?;
@@ -851,47 +1097,43 @@ impl Context {
             }
 
             // seq type
-            "type$is-str" => {
+            Builtin::IsStr => {
                 let is_type = stack_pop!((self.stack) -> str as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-num" => {
+            Builtin::IsNum => {
                 let is_type = stack_pop!((self.stack) -> num as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-bool" => {
+            Builtin::IsBool => {
                 let is_type = stack_pop!((self.stack) -> bool as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-array" => {
+            Builtin::IsArray => {
                 let is_type = stack_pop!((self.stack) -> arr as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-map" => {
+            Builtin::IsMap => {
                 let is_type = stack_pop!((self.stack) -> map as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-result" => {
+            Builtin::IsResult => {
                 let is_type = stack_pop!((self.stack) -> result as "value" for fn_name)?.is_ok();
                 self.stack.push_this(is_type);
             }
-            "type$is-option" => {
+            Builtin::IsOption => {
                 let is_type = stack_pop!((self.stack) -> option as "value" for fn_name).is_ok();
                 self.stack.push_this(is_type);
             }
 
             // seq debug
-            "debug$stack" => eprintln!("{:?}", self.stack),
-            "debug$vars" => eprintln!("{:?}", self.vars),
-            "debug$args" => eprintln!("{:?}", self.args),
-            "debug$fns" => eprintln!("{:?}", self.fns),
-            "debug$modules" => eprintln!("{:?}", self.enabled_modules),
-            "debug$generics" => eprintln!("{:?}", self.trc),
-
-            _ => {
-                return Ok(None);
-            }
+            Builtin::DebugStack => self.shared.outputs.write_debug(&self.stack)?,
+            Builtin::DebugVars => self.shared.outputs.write_debug(&self.vars)?,
+            Builtin::DebugArgs => self.shared.outputs.write_debug(&self.args)?,
+            Builtin::DebugFns => self.shared.outputs.write_debug(&self.fns)?,
+            Builtin::DebugModules => self.shared.outputs.write_debug(&self.enabled_modules)?,
+            Builtin::DebugGenerics => self.shared.outputs.write_debug(&self.trc)?,
         }
-        Ok(Some(()))
+        Ok(None)
     }
 }
@@ -2,20 +2,128 @@ use super::*;
 #[cfg(not(test))]
 use std::process::Command;
 
+macro_rules! builtins {
+    ($($variant:ident => $name:literal,)*) => {
+        /// # Names of every builtin function
+        ///
+        /// Builtins take precedence over arguments, user functions and hooks with the same name
+        pub const BUILTINS: &[&str] = &[$($name),*];
+
+        /// # A builtin function, resolved before the code is executed
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Builtin {
+            $($variant),*
+        }
+
+        impl Builtin {
+            #[must_use]
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+            #[must_use]
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+builtins! {
+    Print => "print",
+    SysExit => "sys$exit",
+    SysArgv => "sys$argv",
+    Sh => "sh",
+    WriteTo => "write-to",
+    Sub => "-",
+    FloatSub => ".-",
+    Mul => "*",
+    FloatMul => ".*",
+    Similar => "≃",
+    Eq => "=",
+    Greater => ">",
+    Rem => "%",
+    FloatRem => "%.",
+    Fill => "@",
+    StackLen => "stack$len",
+    Set => "set",
+    Get => "get",
+    Unwrap => "!",
+    Ok => "ok",
+    Err => "err",
+    None => "none",
+    Some => "some",
+    ResultIsOk => "&result$is-ok",
+    OptionIsSome => "&option$is-some",
+    Format => "%%",
+    StrHasPrefix => "&str$has-prefix",
+    StrTrim => "str$trim",
+    StrRemovePrefix => "str$remove-prefix",
+    StrIntoArr => "str$into-arr",
+    ArrLen => "&arr$len",
+    ArrReverse => "arr$reverse",
+    ArrUnpack => "arr$unpack",
+    ArrPackN => "arr$pack-n",
+    ArrNew => "arr$new",
+    ArrAppend => "arr$append",
+    ArrJoin => "arr$join",
+    ArrPop => "arr$pop",
+    MapNew => "map$new",
+    MapInsertKv => "map$insert-kv",
+    MapGet => "map$get",
+    IsStr => "type$is-str",
+    IsNum => "type$is-num",
+    IsBool => "type$is-bool",
+    IsArray => "type$is-array",
+    IsMap => "type$is-map",
+    IsResult => "type$is-result",
+    IsOption => "type$is-option",
+    DebugStack => "debug$stack",
+    DebugVars => "debug$vars",
+    DebugArgs => "debug$args",
+    DebugFns => "debug$fns",
+    DebugModules => "debug$modules",
+    DebugGenerics => "debug$generics",
+}
+
 #[cfg(test)]
-pub(super) fn sh(shell_cmd: &str) -> Result<isize, String> {
+pub(super) fn sh(
+    shell_cmd: &str,
+    budget: &Budget,
+) -> Result<Result<isize, String>, RuntimeErrorKind> {
+    budget.check_interrupt()?;
     eprintln!("[CMD] {shell_cmd}");
-    Ok(0)
+    Ok(Ok(0))
 }
 
+/// # Run the command, killing it if the script is interrupted while waiting
 #[cfg(not(test))]
-pub(super) fn sh(shell_cmd: &str) -> Result<isize, String> {
-    Command::new("sh")
-        .arg("-c")
-        .arg(shell_cmd)
-        .status()
-        .map(|s| s.code().unwrap_or(256) as isize)
-        .map_err(|e| e.to_string())
+pub(super) fn sh(
+    shell_cmd: &str,
+    budget: &Budget,
+) -> Result<Result<isize, String>, RuntimeErrorKind> {
+    let mut child = match Command::new("sh").arg("-c").arg(shell_cmd).spawn() {
+        Ok(child) => child,
+        Err(e) => return Ok(Err(e.to_string())),
+    };
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(Ok(status.code().unwrap_or(256) as isize)),
+            Ok(None) => {}
+            Err(e) => return Ok(Err(e.to_string())),
+        }
+        if let Err(e) = budget.check_interrupt() {
+            // the process may have just exited
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,488 @@
+use super::Builtin;
+use crate::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// # Single instruction of a [Chunk]
+///
+/// Every expression starts with [`Op::Expr`], followed by the operation that executes it.
+/// Blocks are laid out after it, so control flow is made of jumps between positions of the
+/// chunk
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Op {
+    /// Start the expression of the origin, executing the next operation as part of it
+    Expr(u32),
+    Nop,
+    Push(u32),
+    /// Push the closure constant, capturing the arguments of the frame
+    Closure(u32),
+    Builtin(Builtin),
+    /// Push the argument in the slot
+    Arg(u32),
+    /// Call the user function, or the hook, named by the symbol
+    CallFn(u32),
+    /// Push the argument, or call the user function or hook, named by the symbol
+    Call(u32),
+    IntoClosure(u32),
+    DefineFn(u32),
+    DefineGeneric(u32),
+    Require(u32),
+    Switch(u32),
+    /// Jump when the result on the stack is an error
+    BubbleError(u32),
+    /// Remember the size of the stack before a check block
+    Mark,
+    /// Take the result of the check block made by `owner`, jumping to `skip` when false
+    Test {
+        owner: u32,
+        skip: u32,
+    },
+    Jump(u32),
+    /// Leave the chunk, ending the code or the call
+    Exit(ControlFlow),
+}
+
+/// # Expression an operation was compiled from
+///
+/// Holds its block instead of a copy, and links to the expression holding the block, so
+/// errors get the same trace as the code
+#[derive(Debug)]
+struct Origin {
+    exprs: Exprs,
+    index: usize,
+    // none when it's the source of the chunk
+    source: Option<u32>,
+    parent: Option<u32>,
+}
+
+/// # Function defined by a chunk, with its compiled body
+#[derive(Debug)]
+pub(super) struct FnProto {
+    pub(super) name: Symbol,
+    pub(super) scope: FnScope,
+    pub(super) code: Exprs,
+    pub(super) args: FnArgs,
+    pub(super) output_types: Option<TypedOutputs>,
+    pub(super) body: Arc<Chunk>,
+}
+
+#[derive(Debug)]
+struct Switch {
+    cases: Box<[(Value, u32)]>,
+    default: u32,
+}
+
+/// # Compiled block of code
+///
+/// Made from the top level code, and the body of every function and closure. Values and
+/// definitions used by the operations are kept in tables, so an [Op] stays small.
+/// Chunks don't know their file, since closures run as part of their caller, so sources
+/// are given when they are executed
+#[derive(Debug)]
+pub(crate) struct Chunk {
+    ops: Box<[Op]>,
+    origins: Box<[Origin]>,
+    consts: Box<[Value]>,
+    symbols: Box<[Symbol]>,
+    fns: Box<[FnProto]>,
+    switches: Box<[Switch]>,
+    generics: Box<[DefinedGenericBuilder]>,
+    sources: Box<[Arc<Path>]>,
+}
+
+impl Chunk {
+    pub(super) fn compile(exprs: &Exprs) -> Arc<Self> {
+        let mut compiler = Compiler::default();
+        let flows = Flows {
+            brk: compiler.label(),
+            ret: compiler.label(),
+        };
+        let scope = Scope {
+            source: None,
+            parent: None,
+            flows,
+            check: false,
+        };
+        compiler.block(exprs, scope);
+        compiler.emit(Op::Exit(ControlFlow::Continue));
+        compiler.bind(flows.brk);
+        compiler.emit(Op::Exit(ControlFlow::Break));
+        compiler.bind(flows.ret);
+        compiler.emit(Op::Exit(ControlFlow::Return));
+        Arc::new(compiler.finish())
+    }
+
+    /// # Chunk that only holds the expression a call made from rust is reported at
+    pub(super) fn host(origin: Expr) -> Arc<Self> {
+        let origin = Origin {
+            exprs: Arc::from([origin]),
+            index: 0,
+            source: None,
+            parent: None,
+        };
+        Arc::new(Self {
+            origins: Box::new([origin]),
+            ..Compiler::default().finish()
+        })
+    }
+
+    /// # Operation at the position, leaving the chunk once past the end
+    pub(super) fn op(&self, pc: usize) -> Op {
+        self.ops
+            .get(pc)
+            .copied()
+            .unwrap_or(Op::Exit(ControlFlow::Continue))
+    }
+
+    pub(super) fn expr(&self, origin: u32) -> &Expr {
+        let origin = &self.origins[origin as usize];
+        &origin.exprs[origin.index]
+    }
+
+    pub(super) fn source<'a>(&'a self, origin: u32, base: &'a Arc<Path>) -> &'a Arc<Path> {
+        match self.origins[origin as usize].source {
+            Some(source) => &self.sources[source as usize],
+            None => base,
+        }
+    }
+
+    pub(super) fn parent(&self, origin: u32) -> Option<u32> {
+        self.origins[origin as usize].parent
+    }
+
+    pub(super) fn value(&self, idx: u32) -> &Value {
+        &self.consts[idx as usize]
+    }
+
+    pub(super) fn symbol(&self, idx: u32) -> &Symbol {
+        &self.symbols[idx as usize]
+    }
+
+    pub(super) fn fn_proto(&self, idx: u32) -> &FnProto {
+        &self.fns[idx as usize]
+    }
+
+    pub(super) fn generic(&self, idx: u32) -> &DefinedGenericBuilder {
+        &self.generics[idx as usize]
+    }
+
+    /// # Position of the case matching the value
+    pub(super) fn switch(&self, idx: u32, value: &Value) -> u32 {
+        let switch = &self.switches[idx as usize];
+        switch
+            .cases
+            .iter()
+            .find(|(test, _)| test == value)
+            .map_or(switch.default, |(_, pc)| *pc)
+    }
+}
+
+/// # Compiled bodies of the closures called by a context
+///
+/// Closures are values made when the code is parsed, so their bodies are compiled when
+/// first called. Keyed by their block, which is kept alive so its address isn't reused
+#[derive(Default, Debug)]
+pub(super) struct Bodies(HashMap<(usize, usize), (Exprs, Arc<Chunk>)>);
+
+impl Bodies {
+    fn key(code: &Exprs) -> (usize, usize) {
+        (Arc::as_ptr(code).cast::<Expr>().addr(), code.len())
+    }
+
+    pub(super) fn get(&mut self, code: &Exprs) -> Arc<Chunk> {
+        let (_, body) = self
+            .0
+            .entry(Self::key(code))
+            .or_insert_with(|| (code.clone(), Chunk::compile(code)));
+        body.clone()
+    }
+
+    /// # Reuse the body of a function turned into a closure
+    pub(super) fn insert(&mut self, code: &Exprs, body: Arc<Chunk>) {
+        self.0.insert(Self::key(code), (code.clone(), body));
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Label(u32);
+
+/// # Where the control flows of a block jump to
+#[derive(Clone, Copy)]
+struct Flows {
+    brk: Label,
+    ret: Label,
+}
+
+#[derive(Clone, Copy)]
+struct Scope {
+    source: Option<u32>,
+    parent: Option<u32>,
+    flows: Flows,
+    // control flows don't leave a check block
+    check: bool,
+}
+
+#[derive(Default)]
+struct Compiler {
+    ops: Vec<Op>,
+    labels: Vec<Option<u32>>,
+    origins: Vec<Origin>,
+    consts: Vec<Value>,
+    symbols: Vec<Symbol>,
+    fns: Vec<FnProto>,
+    switches: Vec<Switch>,
+    generics: Vec<DefinedGenericBuilder>,
+    sources: Vec<Arc<Path>>,
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() as u32 - 1)
+    }
+
+    fn bind(&mut self, Label(label): Label) {
+        self.labels[label as usize] = Some(self.ops.len() as u32);
+    }
+
+    fn jump(&mut self, to: Label) {
+        self.emit(Op::Jump(to.0));
+    }
+
+    fn push_const(&mut self, value: Value) -> u32 {
+        self.consts.push(value);
+        self.consts.len() as u32 - 1
+    }
+
+    fn push_symbol(&mut self, symbol: Symbol) -> u32 {
+        self.symbols.push(symbol);
+        self.symbols.len() as u32 - 1
+    }
+
+    fn block(&mut self, exprs: &Exprs, scope: Scope) {
+        for index in 0..exprs.len() {
+            self.origins.push(Origin {
+                exprs: exprs.clone(),
+                index,
+                source: scope.source,
+                parent: scope.parent,
+            });
+            let origin = self.origins.len() as u32 - 1;
+            let after = scope.check.then(|| self.label());
+            let flows = match after {
+                Some(after) => Flows {
+                    brk: after,
+                    ret: after,
+                },
+                None => scope.flows,
+            };
+            let inner = Scope {
+                parent: Some(origin),
+                flows,
+                check: false,
+                ..scope
+            };
+            self.emit(Op::Expr(origin));
+            self.expr(&exprs[index], origin, inner);
+            if let Some(after) = after {
+                self.bind(after);
+            }
+        }
+    }
+
+    // `inner` is the scope of the blocks of the expression
+    fn expr(&mut self, expr: &Expr, origin: u32, inner: Scope) {
+        match &expr.cont {
+            ExprCont::Immediate(value @ Value::Closure(_)) => {
+                let idx = self.push_const(value.clone());
+                self.emit(Op::Closure(idx));
+            }
+            ExprCont::Immediate(value) => {
+                let idx = self.push_const(value.clone());
+                self.emit(Op::Push(idx));
+            }
+            ExprCont::FnCall(name, resolved) => {
+                let op = match resolved {
+                    Resolved::Builtin(builtin) => Op::Builtin(*builtin),
+                    Resolved::Arg(slot) => Op::Arg(*slot),
+                    Resolved::Fn(symbol) => Op::CallFn(self.push_symbol(symbol.clone())),
+                    Resolved::Dynamic(symbol) => Op::Call(self.push_symbol(symbol.clone())),
+                    Resolved::Unresolved => match Builtin::from_name(name) {
+                        Some(builtin) => Op::Builtin(builtin),
+                        None => Op::Call(self.push_symbol(Symbol::intern(name))),
+                    },
+                };
+                self.emit(op);
+            }
+            ExprCont::IncludedCode(Code { source, exprs }) => {
+                self.emit(Op::Nop);
+                self.sources.push(Arc::from(source.as_path()));
+                let end = self.label();
+                let included = Scope {
+                    source: Some(self.sources.len() as u32 - 1),
+                    flows: Flows { brk: end, ret: end },
+                    ..inner
+                };
+                self.block(exprs, included);
+                self.bind(end);
+            }
+            ExprCont::Keyword(kw) => self.keyword(kw, origin, inner),
+        }
+    }
+
+    fn keyword(&mut self, kw: &KeywordKind, origin: u32, inner: Scope) {
+        match kw {
+            KeywordKind::Require(module_name) => {
+                let idx = self.push_symbol(Symbol::intern(module_name));
+                self.emit(Op::Require(idx));
+            }
+            KeywordKind::DefinedGeneric(trc) => {
+                self.generics.push(trc.clone());
+                self.emit(Op::DefineGeneric(self.generics.len() as u32 - 1));
+            }
+            KeywordKind::IntoClosure { fn_name } => {
+                let idx = self.push_symbol(Symbol::intern(fn_name));
+                self.emit(Op::IntoClosure(idx));
+            }
+            KeywordKind::BubbleError => self.emit(Op::BubbleError(inner.flows.ret.0)),
+            KeywordKind::Return => self.jump(inner.flows.ret),
+            KeywordKind::Break => self.jump(inner.flows.brk),
+            KeywordKind::Switch { cases, default } => {
+                let end = self.label();
+                let labels: Vec<_> = cases.iter().map(|_| self.label()).collect();
+                let default_label = default.as_ref().map_or(end, |_| self.label());
+                self.switches.push(Switch {
+                    cases: cases
+                        .iter()
+                        .zip(&labels)
+                        .map(|(case, label)| (case.test.clone(), label.0))
+                        .collect(),
+                    default: default_label.0,
+                });
+                self.emit(Op::Switch(self.switches.len() as u32 - 1));
+                for (case, label) in cases.iter().zip(labels) {
+                    self.bind(label);
+                    self.block(&case.code, inner);
+                    self.jump(end);
+                }
+                if let Some(default) = default {
+                    self.bind(default_label);
+                    self.block(default, inner);
+                }
+                self.bind(end);
+            }
+            KeywordKind::Ifs { branches } => {
+                if branches.is_empty() {
+                    self.emit(Op::Nop);
+                    return;
+                }
+                let end = self.label();
+                for branch in branches {
+                    let skip = self.label();
+                    self.emit(Op::Mark);
+                    self.block(
+                        &branch.check,
+                        Scope {
+                            check: true,
+                            ..inner
+                        },
+                    );
+                    self.emit(Op::Test {
+                        owner: origin,
+                        skip: skip.0,
+                    });
+                    self.block(&branch.code, inner);
+                    self.jump(end);
+                    self.bind(skip);
+                }
+                self.bind(end);
+            }
+            KeywordKind::While { check, code } => {
+                let top = self.label();
+                let end = self.label();
+                self.bind(top);
+                self.emit(Op::Mark);
+                self.block(
+                    check,
+                    Scope {
+                        check: true,
+                        ..inner
+                    },
+                );
+                self.emit(Op::Test {
+                    owner: origin,
+                    skip: end.0,
+                });
+                let flows = Flows {
+                    brk: end,
+                    ret: inner.flows.ret,
+                };
+                self.block(code, Scope { flows, ..inner });
+                self.jump(top);
+                self.bind(end);
+            }
+            KeywordKind::FnDef {
+                name,
+                scope,
+                code,
+                args,
+                out_args,
+            } => {
+                self.fns.push(FnProto {
+                    name: Symbol::intern(name),
+                    scope: scope.clone(),
+                    code: code.clone(),
+                    args: args.clone(),
+                    output_types: out_args.clone().map(TypedOutputs::from),
+                    body: Chunk::compile(code),
+                });
+                self.emit(Op::DefineFn(self.fns.len() as u32 - 1));
+            }
+        }
+    }
+
+    fn finish(self) -> Chunk {
+        let labels = self.labels;
+        let at = |label: u32| labels[label as usize].expect("jump to an unbound label");
+        let ops = self
+            .ops
+            .into_iter()
+            .map(|op| match op {
+                Op::Jump(to) => Op::Jump(at(to)),
+                Op::BubbleError(to) => Op::BubbleError(at(to)),
+                Op::Test { owner, skip } => Op::Test {
+                    owner,
+                    skip: at(skip),
+                },
+                op => op,
+            })
+            .collect();
+        let switches = self
+            .switches
+            .into_iter()
+            .map(|switch| Switch {
+                cases: switch
+                    .cases
+                    .into_iter()
+                    .map(|(test, to)| (test, at(to)))
+                    .collect(),
+                default: at(switch.default),
+            })
+            .collect();
+        Chunk {
+            ops,
+            origins: self.origins.into(),
+            consts: self.consts.into(),
+            symbols: self.symbols.into(),
+            fns: self.fns.into(),
+            switches,
+            generics: self.generics.into(),
+            sources: self.sources.into(),
+        }
+    }
+}
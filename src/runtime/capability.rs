@@ -0,0 +1,92 @@
+/// # An operation that reaches outside of the script
+///
+/// Each builtin that spawns processes, touches the filesystem or the host process needs its
+/// capability to be allowed by the context's [policy](Capabilities)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// `sh`
+    Spawn,
+    /// `io$read_file`
+    FileRead,
+    /// `write-to`, `io$write_file` and `io$append_file`
+    FileWrite,
+    /// `sys$exit`
+    Exit,
+    /// `sys$argv`
+    Argv,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 5] = [
+        Capability::Spawn,
+        Capability::FileRead,
+        Capability::FileWrite,
+        Capability::Exit,
+        Capability::Argv,
+    ];
+    fn bit(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+impl std::fmt::Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Capability::Spawn => "process spawning",
+            Capability::FileRead => "filesystem reads",
+            Capability::FileWrite => "filesystem writes",
+            Capability::Exit => "process exit",
+            Capability::Argv => "process arguments",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// # Sandbox policy of a [runtime context](crate::runtime::Context)
+///
+/// Everything is allowed by default, to keep scripts from the host working as before
+///
+/// ```rust
+/// use stck::internals::{Capabilities, Capability, RuntimeContext};
+/// let policy = Capabilities::all().deny(Capability::Spawn).deny(Capability::Exit);
+/// let ctx = RuntimeContext::new().with_capabilities(policy);
+/// assert!(!ctx.get_capabilities().allows(Capability::Spawn));
+/// assert!(ctx.get_capabilities().allows(Capability::Argv));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    allowed: u8,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    #[must_use]
+    pub fn all() -> Self {
+        Capabilities {
+            allowed: Capability::ALL.iter().fold(0, |acc, c| acc | c.bit()),
+        }
+    }
+    #[must_use]
+    pub fn none() -> Self {
+        Capabilities { allowed: 0 }
+    }
+    #[must_use]
+    pub fn allow(mut self, cap: Capability) -> Self {
+        self.allowed |= cap.bit();
+        self
+    }
+    #[must_use]
+    pub fn deny(mut self, cap: Capability) -> Self {
+        self.allowed &= !cap.bit();
+        self
+    }
+    #[must_use]
+    pub fn allows(&self, cap: Capability) -> bool {
+        self.allowed & cap.bit() != 0
+    }
+}
@@ -0,0 +1,188 @@
+use super::Context;
+use crate::Expr;
+use std::path::{Path, PathBuf};
+
+/// # How execution should go on after a [pause](Pause)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Run until the next breakpoint
+    Continue,
+    /// Pause at the next expression, even inside called functions and closures
+    Into,
+    /// Pause at the next expression of the current function, or of its callers
+    Over,
+    /// Pause once the current function returns
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Breakpoint,
+    Step,
+}
+
+/// # Execution paused before an expression
+///
+/// Gives the [debug hook](DebugHook) access to the paused [context](Context), to inspect its
+/// stack, arguments and variables
+pub struct Pause<'a> {
+    pub reason: PauseReason,
+    pub source: &'a Path,
+    pub expr: &'a Expr,
+    /// Amount of nested function and closure calls, zero on the top level code
+    pub depth: usize,
+    pub ctx: &'a Context,
+}
+
+impl Pause<'_> {
+    /// # Line where the paused expression starts
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.expr.span.start
+    }
+}
+
+/// # Host side of a [debugger](Debugger)
+///
+/// Called on every pause, it can change the breakpoints and decides how execution goes on
+pub trait DebugHook: Send {
+    fn on_pause(&mut self, pause: &Pause<'_>, breakpoints: &mut Breakpoints) -> Step;
+}
+
+impl<F: FnMut(&Pause<'_>, &mut Breakpoints) -> Step + Send> DebugHook for F {
+    fn on_pause(&mut self, pause: &Pause<'_>, breakpoints: &mut Breakpoints) -> Step {
+        self(pause, breakpoints)
+    }
+}
+
+/// # Lines of source files that pause the execution
+///
+/// Files are matched by their last components, so `main.stck` matches `src/main.stck`
+#[derive(Debug, Default, Clone)]
+pub struct Breakpoints(Vec<(PathBuf, usize)>);
+
+impl Breakpoints {
+    /// # Add a breakpoint, returning false if it was already set
+    pub fn add(&mut self, file: impl Into<PathBuf>, line: usize) -> bool {
+        let bp = (file.into(), line);
+        if self.0.contains(&bp) {
+            false
+        } else {
+            self.0.push(bp);
+            true
+        }
+    }
+    /// # Remove a breakpoint, returning false if it wasn't set
+    pub fn remove(&mut self, file: impl AsRef<Path>, line: usize) -> bool {
+        let file = file.as_ref();
+        let len = self.0.len();
+        self.0.retain(|(f, l)| !(f == file && *l == line));
+        len != self.0.len()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, usize)> {
+        self.0.iter().map(|(f, l)| (f.as_path(), *l))
+    }
+    #[must_use]
+    pub fn matches(&self, source: &Path, line: usize) -> bool {
+        self.0
+            .iter()
+            .any(|(f, l)| *l == line && source.ends_with(f))
+    }
+}
+
+/// # Debugger attached to a [runtime context](Context)
+///
+/// Before executing each expression the debugger checks the breakpoints and the current
+/// [step](Step), and pauses by calling its [hook](DebugHook)
+///
+/// ```rust
+/// use stck::internals::{Debugger, RuntimeContext, Step, Value};
+/// let token_block = stck::api::get_tokens_str("1\n2\n3\n", "Debugged", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// let debugger = Debugger::new(|pause: &stck::internals::Pause, _: &mut _| {
+///     assert_eq!(pause.ctx.get_stack(), [Value::Num(1)]);
+///     Step::Continue
+/// }).with_breakpoint("Debugged", 2);
+/// let mut ctx = RuntimeContext::new().with_debugger(debugger);
+/// ctx.execute_entire_code(&code).unwrap();
+/// ```
+pub struct Debugger {
+    hook: Box<dyn DebugHook>,
+    breakpoints: Breakpoints,
+    step: Option<(Step, usize)>,
+    last_line: Option<(PathBuf, usize)>,
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.breakpoints)
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Debugger {
+    pub fn new(hook: impl DebugHook + 'static) -> Self {
+        Debugger {
+            hook: Box::new(hook),
+            breakpoints: Breakpoints::default(),
+            step: None,
+            last_line: None,
+        }
+    }
+    #[must_use]
+    pub fn with_breakpoint(mut self, file: impl Into<PathBuf>, line: usize) -> Self {
+        self.breakpoints.add(file, line);
+        self
+    }
+    /// # Pause before the first expression
+    #[must_use]
+    pub fn pause_on_start(mut self) -> Self {
+        self.step = Some((Step::Into, 0));
+        self
+    }
+    pub fn breakpoints(&mut self) -> &mut Breakpoints {
+        &mut self.breakpoints
+    }
+
+    fn should_pause(&self, source: &Path, line: usize, depth: usize) -> Option<PauseReason> {
+        let stepped = match self.step {
+            Some((Step::Into, _)) => true,
+            Some((Step::Over, from)) => depth <= from,
+            Some((Step::Out, from)) => depth < from,
+            Some((Step::Continue, _)) | None => false,
+        };
+        if stepped {
+            return Some(PauseReason::Step);
+        }
+        // only pause once when arriving at a line with many expressions
+        let same_line = self
+            .last_line
+            .as_ref()
+            .is_some_and(|(f, l)| *l == line && f == source);
+        (!same_line && self.breakpoints.matches(source, line)).then_some(PauseReason::Breakpoint)
+    }
+
+    pub(super) fn before_expr(&mut self, ctx: &Context, expr: &Expr, source: &Path, depth: usize) {
+        let line = expr.span.start;
+        if let Some(reason) = self.should_pause(source, line, depth) {
+            let pause = Pause {
+                reason,
+                source,
+                expr,
+                depth,
+                ctx,
+            };
+            let step = self.hook.on_pause(&pause, &mut self.breakpoints);
+            self.step = Some((step, depth));
+        }
+        let moved = self
+            .last_line
+            .as_ref()
+            .is_none_or(|(f, l)| *l != line || f != source);
+        if moved {
+            self.last_line = Some((source.to_path_buf(), line));
+        }
+    }
+}
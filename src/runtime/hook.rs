@@ -0,0 +1,206 @@
+use crate::convert::{FromValue, FromValueError, IntoValues};
+use crate::*;
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+type HookClosure = dyn FnMut(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind> + Send;
+
+#[derive(Clone)]
+pub enum Hook {
+    Raw(fn(&mut runtime::Context, &Path)),
+    WithError(fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>),
+    Closure(Arc<Mutex<HookClosure>>),
+}
+
+impl std::fmt::Debug for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hook::Raw(c) => f.debug_tuple("Raw").field(c).finish(),
+            Hook::WithError(c) => f.debug_tuple("WithError").field(c).finish(),
+            Hook::Closure(c) => f.debug_tuple("Closure").field(&Arc::as_ptr(c)).finish(),
+        }
+    }
+}
+
+impl Hook {
+    pub fn call(&self, ctx: &mut runtime::Context, source: &Path) -> Result<(), RuntimeErrorKind> {
+        match self {
+            Hook::Raw(c) => {
+                c(ctx, source);
+                Ok(())
+            }
+            Hook::WithError(c) => c(ctx, source),
+            Hook::Closure(c) => {
+                let _running = Running::enter(c)?;
+                // other threads running the same hook are waited for, and a hook that panicked
+                // can still be called
+                let mut c = c.lock().unwrap_or_else(PoisonError::into_inner);
+                c(ctx, source)
+            }
+        }
+    }
+
+    /// # Hook from a rust closure
+    ///
+    /// Unlike [`Hook::Raw`] and [`Hook::WithError`], the closure can capture host state. It must be
+    /// `Send`, so contexts can be moved to other threads
+    pub fn from_closure(
+        closure: impl FnMut(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>
+        + Send
+        + 'static,
+    ) -> Self {
+        Hook::Closure(Arc::new(Mutex::new(closure)))
+    }
+
+    /// # Hook from a rust closure with typed arguments
+    ///
+    /// The arguments are popped from the stack, the first argument being the deepest value, and
+    /// checked against their [type](FromValue::type_tester). The outputs are pushed in order,
+    /// see [`IntoValues`]
+    ///
+    /// ```rust
+    /// use stck::internals::{RuntimeContext, StckHook, Value};
+    /// let token_block = stck::api::get_tokens_str("\"ab\" 3 repeat\n", "Typed hook", &mut stck::cache::Isolated::new()).unwrap();
+    /// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+    /// let mut ctx = RuntimeContext::new();
+    /// let separator = String::from("-");
+    /// ctx.add_hook("repeat", StckHook::typed("repeat", move |s: String, n: isize| {
+    ///     vec![s; n as usize].join(&separator)
+    /// }));
+    /// ctx.execute_entire_code(&code).unwrap();
+    /// assert_eq!(ctx.get_stack(), [Value::from("ab-ab-ab".to_string())]);
+    /// ```
+    pub fn typed<Args>(name: impl Into<String>, mut closure: impl TypedHook<Args>) -> Self {
+        let name = name.into();
+        let types = closure.arg_types();
+        Hook::from_closure(move |ctx, _| {
+            let args = pop_typed_args(&mut ctx.stack, &name, &types)?;
+            let outputs = closure.call_typed(args).map_err(|(this_arg, error)| {
+                RuntimeErrorKind::WrongTypeForHook {
+                    for_fn: name.clone(),
+                    args: display_types(&types),
+                    this_arg,
+                    error: Box::new(error),
+                }
+            })?;
+            ctx.stack.pushn(outputs);
+            Ok(())
+        })
+    }
+}
+
+thread_local! {
+    /// Closure hooks running on this thread, by address
+    static RUNNING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// # Marks a closure hook as running on this thread until dropped
+///
+/// A hook that calls itself would wait for its own lock forever, so it's an error instead
+struct Running(usize);
+
+impl Running {
+    fn enter(hook: &Arc<Mutex<HookClosure>>) -> Result<Self, RuntimeErrorKind> {
+        let addr = Arc::as_ptr(hook).cast::<()>() as usize;
+        RUNNING.with_borrow_mut(|running| {
+            if running.contains(&addr) {
+                return Err(RuntimeErrorKind::ReentrantHook);
+            }
+            running.push(addr);
+            Ok(Running(addr))
+        })
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.with_borrow_mut(|running| running.retain(|addr| *addr != self.0));
+    }
+}
+
+impl From<fn(&mut runtime::Context, &Path)> for Hook {
+    fn from(value: fn(&mut runtime::Context, &Path)) -> Self {
+        Hook::Raw(value)
+    }
+}
+impl From<fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>> for Hook {
+    fn from(value: fn(&mut runtime::Context, &Path) -> Result<(), RuntimeErrorKind>) -> Self {
+        Hook::WithError(value)
+    }
+}
+
+fn display_types(types: &[TypeTester]) -> String {
+    let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+    format!("[ {} ]", types.join(" "))
+}
+
+fn pop_typed_args(
+    stack: &mut Stack,
+    fn_name: &str,
+    types: &[TypeTester],
+) -> Result<Vec<Value>, RuntimeErrorKind> {
+    let args = stack
+        .popn(types.len())
+        .ok_or_else(|| RuntimeErrorKind::MissingValuesForHook {
+            for_fn: fn_name.to_string(),
+            args: display_types(types),
+            missing: types.len() - stack.len(),
+        })?;
+    let mut trc: TypeResolutionContext = TypeResolutionBuilder::new().into();
+    for (this_arg, (tt, got)) in types.iter().zip(&args).enumerate() {
+        if trc.check(tt, got).is_err() {
+            return Err(RuntimeErrorKind::WrongTypeForHook {
+                for_fn: fn_name.to_string(),
+                args: display_types(types),
+                this_arg,
+                error: Box::new(FromValueError::wrong_type(tt.clone(), got.clone())),
+            });
+        }
+    }
+    Ok(args)
+}
+
+/// # A rust closure usable as a [typed hook](Hook::typed)
+///
+/// Implemented for closures of up to six arguments that implement [`FromValue`], returning
+/// [`IntoValues`]
+pub trait TypedHook<Args>: Send + 'static {
+    fn arg_types(&self) -> Vec<TypeTester>;
+    /// # Call the closure with its arguments
+    ///
+    /// Fails with the index of the argument that couldn't be converted
+    fn call_typed(&mut self, args: Vec<Value>) -> Result<Vec<Value>, (usize, FromValueError)>;
+}
+
+macro_rules! typed_hook {
+    ($($t:ident $v:ident),*) => {
+        impl<F, R, $($t),*> TypedHook<($($t,)*)> for F
+        where
+            F: FnMut($($t),*) -> R + Send + 'static,
+            R: IntoValues,
+            $($t: FromValue),*
+        {
+            fn arg_types(&self) -> Vec<TypeTester> {
+                vec![$($t::type_tester()),*]
+            }
+            #[allow(unused_mut, unused_variables)]
+            fn call_typed(&mut self, args: Vec<Value>) -> Result<Vec<Value>, (usize, FromValueError)> {
+                let mut args = args.into_iter().enumerate();
+                $(
+                    let (this_arg, $v) = args.next().expect("arguments are popped by arity");
+                    let $v = $t::from_value($v).map_err(|e| (this_arg, e))?;
+                )*
+                Ok(self($($v),*).into_values())
+            }
+        }
+    };
+}
+
+typed_hook!();
+typed_hook!(A a);
+typed_hook!(A a, B b);
+typed_hook!(A a, B b, C c);
+typed_hook!(A a, B b, C c, D d);
+typed_hook!(A a, B b, C c, D d, E e);
+typed_hook!(A a, B b, C c, D d, E e, G g);
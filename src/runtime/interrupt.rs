@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// # Handle to stop a running script from another thread
+///
+/// Obtained with [`Context::interrupt_handle`](super::Context::interrupt_handle), every clone
+/// stops the same context. The script fails with [`Interrupted`](crate::RuntimeErrorKind::Interrupted)
+/// at the next expression, or while waiting on a long builtin like `sh`
+///
+/// ```rust
+/// use stck::internals::RuntimeContext;
+/// let token_block = stck::api::get_tokens_str("(while) { 1 1 = } {}\n", "Endless loop", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// let mut ctx = RuntimeContext::new();
+/// let handle = ctx.interrupt_handle();
+/// std::thread::spawn(move || handle.interrupt());
+/// assert!(ctx.execute_entire_code(&code).is_err());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Interrupt(Arc<AtomicBool>);
+
+impl Interrupt {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// # Let the context execute again
+    ///
+    /// The interruption isn't reset after stopping a script, so a reused context must clear it
+    pub fn clear(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// # Why a script was [interrupted](crate::RuntimeErrorKind::Interrupted)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// By an [Interrupt] handle
+    Handle,
+    /// By the [deadline](super::Limits::with_deadline)
+    Deadline,
+}
+
+impl std::fmt::Display for Interruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interruption::Handle => write!(f, "interrupted by the host"),
+            Interruption::Deadline => write!(f, "stopped by its deadline"),
+        }
+    }
+}
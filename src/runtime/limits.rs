@@ -0,0 +1,158 @@
+use super::{Interrupt, Interruption, Stack};
+use crate::RuntimeErrorKind;
+use std::time::{Duration, Instant};
+
+/// # Resource limits for untrusted scripts
+///
+/// Every limit is disabled by default. When a limit is reached the script stops with a
+/// dedicated [error](RuntimeErrorKind) pointing to the expression that exceeded it
+///
+/// ```rust
+/// use stck::internals::{Limits, RuntimeContext};
+/// let token_block = stck::api::get_tokens_str("(while) { 1 1 = } {}\n", "Endless loop", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// let mut ctx = RuntimeContext::new().with_limits(Limits::new().with_max_exprs(1000));
+/// assert!(ctx.execute_entire_code(&code).is_err());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Limits {
+    max_exprs: Option<usize>,
+    max_call_depth: Option<usize>,
+    max_stack_size: Option<usize>,
+    max_alloc: Option<usize>,
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+}
+
+impl Limits {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// # Maximum amount of expressions executed by the context
+    #[must_use]
+    pub fn with_max_exprs(mut self, max: usize) -> Self {
+        self.max_exprs = Some(max);
+        self
+    }
+    /// # Maximum depth of nested function and closure calls
+    #[must_use]
+    pub fn with_max_call_depth(mut self, max: usize) -> Self {
+        self.max_call_depth = Some(max);
+        self
+    }
+    /// # Maximum amount of values in a single stack
+    #[must_use]
+    pub fn with_max_stack_size(mut self, max: usize) -> Self {
+        self.max_stack_size = Some(max);
+        self
+    }
+    /// # Maximum [allocated size](crate::Value::alloc_size) of the values in a single stack
+    ///
+    /// Only the stacks are counted, not variables nor arguments. A string or collection shared
+    /// by several values of the stack is counted once
+    #[must_use]
+    pub fn with_max_alloc(mut self, max: usize) -> Self {
+        self.max_alloc = Some(max);
+        self
+    }
+    /// # Instant after which the script is [interrupted](Interruption::Deadline)
+    #[must_use]
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+    /// # Time after which the script is [interrupted](Interruption::Deadline)
+    ///
+    /// Counted from the start of [`execute_entire_code`](super::Context::execute_entire_code),
+    /// [`call_fn`](super::Context::call_fn), [`call_closure`](super::Context::call_closure)
+    /// or [`Task::new`](super::Task::new), and again from each
+    /// [`reset_usage`](super::Context::reset_usage)
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// # Usage of the [limits](Limits) by a running context
+///
+/// Moved into the frame of each called function, so nested calls share the same counters
+#[derive(Debug, Default)]
+pub(super) struct Budget {
+    pub(super) limits: Limits,
+    pub(super) interrupt: Interrupt,
+    exprs: usize,
+    depth: usize,
+    // when the timeout runs out, since the execution started
+    timeout_at: Option<Instant>,
+}
+
+impl Budget {
+    pub(super) fn reset(&mut self) {
+        self.exprs = 0;
+        self.depth = 0;
+        self.start();
+    }
+    pub(super) fn start(&mut self) {
+        self.timeout_at = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+    pub(super) fn executed_exprs(&self) -> usize {
+        self.exprs
+    }
+    pub(super) fn depth(&self) -> usize {
+        self.depth
+    }
+    pub(super) fn tick(&mut self) -> Result<(), RuntimeErrorKind> {
+        self.exprs += 1;
+        self.check_interrupt()?;
+        match self.limits.max_exprs {
+            Some(limit) if self.exprs > limit => Err(RuntimeErrorKind::ExprLimitExceeded { limit }),
+            _ => Ok(()),
+        }
+    }
+    pub(super) fn check_interrupt(&self) -> Result<(), RuntimeErrorKind> {
+        if self.interrupt.is_interrupted() {
+            return Err(RuntimeErrorKind::Interrupted(Interruption::Handle));
+        }
+        let deadline = match (self.limits.deadline, self.timeout_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(RuntimeErrorKind::Interrupted(Interruption::Deadline))
+            }
+            _ => Ok(()),
+        }
+    }
+    pub(super) fn enter_call(&mut self) -> Result<(), RuntimeErrorKind> {
+        match self.limits.max_call_depth {
+            Some(limit) if self.depth >= limit => {
+                Err(RuntimeErrorKind::CallDepthExceeded { limit })
+            }
+            _ => {
+                self.depth += 1;
+                Ok(())
+            }
+        }
+    }
+    pub(super) fn leave_call(&mut self) {
+        self.depth -= 1;
+    }
+    pub(super) fn check_stack(&self, stack: &mut Stack) -> Result<(), RuntimeErrorKind> {
+        if let Some(limit) = self.limits.max_stack_size {
+            let got = stack.len();
+            if got > limit {
+                return Err(RuntimeErrorKind::StackSizeExceeded { limit, got });
+            }
+        }
+        if let Some(limit) = self.limits.max_alloc {
+            let got = stack.alloc_size();
+            if got > limit {
+                return Err(RuntimeErrorKind::AllocLimitExceeded { limit, got });
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 mod io;
+mod json;
 pub mod oficial {
     pub use super::io::io_module;
+    pub use super::json::json_module;
 }
 
 use crate::{FnName, StckError};
 use std::collections::HashMap;
 
-use super::Hook;
+use super::{Hook, TypedHook};
 
 #[derive(Clone)]
 pub struct Module {
@@ -35,7 +37,25 @@ impl Module {
             Err(StckError::BuiltinModuleWithoutBang(name))
         }
     }
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn fn_names(&self) -> impl Iterator<Item = &str> {
+        self.funcs.keys().map(String::as_str)
+    }
     pub fn add_fn(&mut self, name: impl Into<String>, fnc: Hook) -> Option<Hook> {
         self.funcs.insert(name.into(), fnc)
     }
+    /// # Add a rust closure with typed arguments
+    ///
+    /// See [`Hook::typed`]
+    pub fn add_typed_fn<Args>(
+        &mut self,
+        name: impl Into<String>,
+        closure: impl TypedHook<Args>,
+    ) -> Option<Hook> {
+        let name = name.into();
+        self.add_fn(name.clone(), Hook::typed(name, closure))
+    }
 }
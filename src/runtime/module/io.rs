@@ -1,6 +1,6 @@
 use crate::{
     RuntimeContext, RuntimeErrorKind, StckError, Value,
-    runtime::{Hook, module::Module, sget, stack_pop},
+    runtime::{Capability, Hook, module::Module, sget, stack_pop},
 };
 use std::{io::Write, path::Path};
 
@@ -19,6 +19,7 @@ pub fn io_module() -> Result<Module, StckError> {
     let mut io_mod = Module::new_protected("#io".to_string())?;
 
     register!(io_mod, read_file as |ctx| {
+        ctx.require_capability(Capability::FileRead)?;
         let path = stack_pop!((ctx.stack) -> str as "file path" for "io$read-file")?;
         let content = std::fs::read_to_string(path);
         let r = match content {
@@ -30,6 +31,7 @@ pub fn io_module() -> Result<Module, StckError> {
     });
 
     register!(io_mod, write_file as |ctx| {
+        ctx.require_capability(Capability::FileWrite)?;
         let path = stack_pop!((ctx.stack) -> str as "file path" for "io$read-file")?;
         let content = stack_pop!((ctx.stack) -> str as "file content" for "io$read-file")?;
         let file = std::fs::File::create(path);
@@ -49,6 +51,7 @@ pub fn io_module() -> Result<Module, StckError> {
     });
 
     register!(io_mod, append_file as |ctx| {
+        ctx.require_capability(Capability::FileWrite)?;
         let path = stack_pop!((ctx.stack) -> str as "file path" for "io$read-file")?;
         let content = stack_pop!((ctx.stack) -> str as "file content" for "io$read-file")?;
         let file = std::fs::OpenOptions::new().append(true).open(path);
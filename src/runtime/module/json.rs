@@ -0,0 +1,361 @@
+use crate::{
+    RuntimeContext, RuntimeErrorKind, StckError, Value,
+    runtime::{Hook, module::Module, sget, stack_pop},
+};
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+/// Deepest nesting of arrays and objects accepted by `json$parse`
+const MAX_DEPTH: usize = 256;
+
+pub fn json_module() -> Result<Module, StckError> {
+    let mut json_mod = Module::new_protected("#json".to_string())?;
+    json_mod.add_fn("json$parse", Hook::WithError(parse));
+    json_mod.add_fn("json$stringify", Hook::WithError(stringify));
+    Ok(json_mod)
+}
+
+fn parse(ctx: &mut RuntimeContext, _: &Path) -> Result<(), RuntimeErrorKind> {
+    let text = stack_pop!((ctx.stack) -> str as "json text" for "json$parse")?;
+    let r = Parser::new(&text).parse().map_err(Value::from);
+    ctx.stack.push_this(r);
+    Ok(())
+}
+
+fn stringify(ctx: &mut RuntimeContext, _: &Path) -> Result<(), RuntimeErrorKind> {
+    let pretty = stack_pop!((ctx.stack) -> bool as "pretty" for "json$stringify")?;
+    let value = stack_pop!((ctx.stack) -> * as "value" for "json$stringify")?;
+    let mut out = String::new();
+    let indent = pretty.then_some(0);
+    let r = match write_value(&mut out, &value, indent) {
+        Ok(()) => Ok(Value::from(out)),
+        Err(e) => Err(Value::from(e)),
+    };
+    ctx.stack.push_this(r);
+    Ok(())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<Value, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(c) => Err(self.error(&format!("unexpected {c:?} after the value"))),
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        format!("{msg} at line {line} column {column}")
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected {expected:?}, found {c:?}"))),
+            None => Err(self.error(&format!("expected {expected:?}, found end of input"))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => self.string().map(Value::from),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Option(None)),
+            Some(c) => Err(self.error(&format!("unexpected {c:?}"))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Value, String>) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("too many nested values"));
+        }
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error(&format!("expected {word}")))
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut map = HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::from(map));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::from(map)),
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut xs = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::from(xs));
+        }
+        loop {
+            xs.push(self.value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::from(xs)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if c < ' ' => return Err(self.error("control character in string")),
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        Ok(match self.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = self.hex4()?;
+                let code = if (0xD800..0xDC00).contains(&high) {
+                    if !self.text[self.pos..].starts_with("\\u") {
+                        return Err(self.error("unpaired surrogate in string"));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("unpaired surrogate in string"));
+                    }
+                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))?
+            }
+            _ => return Err(self.error("invalid escape in string")),
+        })
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|d| d.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("checked hex digits"))
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn digits(&mut self) -> Result<(), String> {
+        let start = self.pos;
+        while let Some('0'..='9') = self.peek() {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            Err(self.error("expected digit in number"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        self.eat('-');
+        self.digits()?;
+        let mut is_float = false;
+        if self.eat('.') {
+            is_float = true;
+            self.digits()?;
+        }
+        if self.eat('e') || self.eat('E') {
+            is_float = true;
+            let _ = self.eat('+') || self.eat('-');
+            self.digits()?;
+        }
+        let raw = &self.text[start..self.pos];
+        // integers too big for a Num are kept as Floats
+        if !is_float && let Ok(n) = raw.parse() {
+            return Ok(Value::Num(n));
+        }
+        Ok(Value::Float(raw.parse().expect("checked float syntax")))
+    }
+}
+
+const INDENT: &str = "  ";
+
+fn newline(out: &mut String, indent: Option<usize>) {
+    if let Some(level) = indent {
+        out.push('\n');
+        for _ in 0..level {
+            out.push_str(INDENT);
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &Value, indent: Option<usize>) -> Result<(), String> {
+    let inner = indent.map(|level| level + 1);
+    match value {
+        Value::Str(s) => write_string(out, s),
+        Value::Char(c) => write_string(out, &c.to_string()),
+        Value::Num(n) => write!(out, "{n}").expect("writing to a string"),
+        Value::Float(f) if f.is_finite() => write!(out, "{f:?}").expect("writing to a string"),
+        Value::Float(f) => return Err(format!("Can't represent {f} in json")),
+        Value::Bool(b) => write!(out, "{b}").expect("writing to a string"),
+        Value::Option(None) => out.push_str("null"),
+        Value::Option(Some(v)) => write_value(out, v, indent)?,
+        Value::Array(xs) if xs.is_empty() => out.push_str("[]"),
+        Value::Array(xs) => {
+            out.push('[');
+            for (i, x) in xs.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                newline(out, inner);
+                write_value(out, x, inner)?;
+            }
+            newline(out, indent);
+            out.push(']');
+        }
+        Value::Map(m) if m.is_empty() => out.push_str("{}"),
+        Value::Map(m) => {
+            let mut entries: Vec<_> = m.iter().collect();
+            entries.sort_unstable_by_key(|(k, _)| *k);
+            write_object(out, entries, indent)?;
+        }
+        Value::Result(r) => {
+            let (tag, v) = match r.as_ref() {
+                Ok(v) => ("Ok".to_string(), v),
+                Err(v) => ("Err".to_string(), v),
+            };
+            write_object(out, vec![(&tag, v)], indent)?;
+        }
+        Value::Closure(_) => return Err("Can't represent a closure in json".to_string()),
+    }
+    Ok(())
+}
+
+fn write_object(
+    out: &mut String,
+    entries: Vec<(&String, &Value)>,
+    indent: Option<usize>,
+) -> Result<(), String> {
+    let inner = indent.map(|level| level + 1);
+    out.push('{');
+    for (i, (k, v)) in entries.into_iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        newline(out, inner);
+        write_string(out, k);
+        out.push(':');
+        if indent.is_some() {
+            out.push(' ');
+        }
+        write_value(out, v, inner)?;
+    }
+    newline(out, indent);
+    out.push('}');
+    Ok(())
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).expect("writing to a string"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,87 @@
+use crate::RuntimeErrorKind;
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// # Where a context writes `print` and the `debug$*` builtins
+///
+/// Standard output and error by default
+pub(super) struct Outputs {
+    pub(super) out: Box<dyn Write + Send>,
+    pub(super) debug: Box<dyn Write + Send>,
+}
+
+impl Default for Outputs {
+    fn default() -> Self {
+        Self {
+            out: Box::new(std::io::stdout()),
+            debug: Box::new(std::io::stderr()),
+        }
+    }
+}
+
+impl Outputs {
+    pub(super) fn write_debug(
+        &mut self,
+        value: &dyn std::fmt::Debug,
+    ) -> Result<(), RuntimeErrorKind> {
+        writeln!(self.debug, "{value:?}").map_err(RuntimeErrorKind::Output)
+    }
+}
+
+impl std::fmt::Debug for Outputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Outputs").finish_non_exhaustive()
+    }
+}
+
+/// # Output sink that keeps everything written to it
+///
+/// Clones share the same buffer, so one can be given to the context and another kept to read
+/// the output
+///
+/// ```rust
+/// use stck::internals::{Capture, RuntimeContext};
+/// let token_block = stck::api::get_tokens_str("\"hi\" print\n", "Captured", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// let out = Capture::new();
+/// let mut ctx = RuntimeContext::new().with_output(out.clone());
+/// ctx.execute_entire_code(&code).unwrap();
+/// assert_eq!(out.contents(), "hi");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Everything written so far, with invalid UTF-8 replaced
+    #[must_use]
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer()).into_owned()
+    }
+
+    /// # Take everything written so far, leaving the capture empty
+    #[must_use]
+    pub fn take(&self) -> String {
+        let buffer = std::mem::take(&mut *self.buffer());
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+
+    fn buffer(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// # Functions visible to a frame
+///
+/// Called functions share the table of their caller instead of copying it. Defining a
+/// function while the table is shared adds a layer on top of it, so the definition is only
+/// seen by the frame that made it and the functions it calls
+#[derive(Clone, Default)]
+pub(super) struct FnTable(Arc<FnLayer>);
+
+#[derive(Default)]
+struct FnLayer {
+    fns: HashMap<Symbol, FnDef>,
+    parent: Option<Arc<FnLayer>>,
+}
+
+impl FnTable {
+    pub(super) fn get(&self, name: &Symbol) -> Option<&FnDef> {
+        self.layers().find_map(|layer| layer.fns.get(name))
+    }
+
+    pub(super) fn insert(&mut self, name: Symbol, def: FnDef) {
+        if let Some(layer) = Arc::get_mut(&mut self.0) {
+            layer.fns.insert(name, def);
+            return;
+        }
+        let layer = FnLayer {
+            fns: HashMap::from([(name, def)]),
+            parent: Some(self.0.clone()),
+        };
+        self.0 = Arc::new(layer);
+    }
+
+    /// # Every visible function, without the ones shadowed by a newer definition
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&Symbol, &FnDef)> {
+        let mut seen = HashSet::new();
+        self.layers()
+            .flat_map(|layer| &layer.fns)
+            .filter(move |(name, _)| seen.insert(*name))
+    }
+
+    fn layers(&self) -> impl Iterator<Item = &FnLayer> {
+        std::iter::successors(Some(&*self.0), |layer| layer.parent.as_deref())
+    }
+}
+
+impl std::fmt::Debug for FnTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
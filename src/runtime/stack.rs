@@ -17,6 +17,12 @@ macro_rules! sget {
     (map) => {
         (Value::get_map, Value::get_ref_map, "Map")
     };
+    (shared_arr) => {
+        (Value::get_shared_arr, Value::get_ref_arr, "Array")
+    };
+    (shared_map) => {
+        (Value::get_shared_map, Value::get_ref_map, "Map")
+    };
     (result) => {
         (Value::get_result, Value::get_ref_result, "Result")
     };
@@ -33,7 +39,7 @@ macro_rules! stack_pop {
     (($stack:expr) -> $type:ident as $this_arg:literal for $fn_name:expr) => {
         $stack
             .pop_this(sget!($type).0)
-            .ok_or(RuntimeErrorKind::MissingValueForBuiltin{
+            .ok_or_else(|| RuntimeErrorKind::MissingValueForBuiltin{
                 for_fn: $fn_name.to_owned(),
                 args: format!( "[{}: {}]", $this_arg, sget!($type).2 ),
                 this_arg: $this_arg,
@@ -72,7 +78,7 @@ macro_rules! stack_pop {
     (($stack:expr) -> * as $this_arg:literal for $fn_name:expr) => {
         $stack
             .pop()
-            .ok_or(RuntimeErrorKind::MissingValueForBuiltin{
+            .ok_or_else(|| RuntimeErrorKind::MissingValueForBuiltin{
                 for_fn: $fn_name.to_owned(),
                 args: format!( "[{}]", $this_arg ),
                 this_arg: $this_arg,
@@ -81,7 +87,7 @@ macro_rules! stack_pop {
     (($stack:expr) -> &$type:ident as $this_arg:literal for $fn_name:expr) => {
         $stack
             .peek_this(sget!($type).1)
-            .ok_or(RuntimeErrorKind::MissingValueForBuiltin{
+            .ok_or_else(|| RuntimeErrorKind::MissingValueForBuiltin{
                 for_fn: $fn_name.to_owned(),
                 args: format!( "[{}: {}]", $this_arg, sget!($type).2 ),
                 this_arg: $this_arg,
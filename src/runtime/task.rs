@@ -0,0 +1,325 @@
+use super::bytecode::{Chunk, Op};
+use super::{CResult, Context, RuntimeError};
+use crate::*;
+use std::path::Path;
+use std::sync::Arc;
+
+/// # What executing an [operation](Op) asks of the [Machine]
+pub(super) enum Action {
+    Next,
+    Jump(u32),
+    /// Execute the body of a function or closure, on a new frame
+    Call(Box<Call>),
+}
+
+/// # A chunk being executed
+pub(super) struct Control {
+    chunk: Arc<Chunk>,
+    source: Arc<Path>,
+    pc: usize,
+    // origin of the expression being executed
+    current: Option<u32>,
+    // sizes of the stack before the check blocks being executed
+    marks: Vec<usize>,
+}
+
+impl Control {
+    pub(super) fn new(chunk: Arc<Chunk>, source: Arc<Path>) -> Self {
+        Self {
+            chunk,
+            source,
+            pc: 0,
+            current: None,
+            marks: Vec::new(),
+        }
+    }
+
+    fn next_op(&mut self) -> Op {
+        self.pc += 1;
+        self.chunk.op(self.pc - 1)
+    }
+
+    // source of the expression being executed
+    fn current_source(&self) -> &Arc<Path> {
+        match self.current {
+            Some(origin) => self.chunk.source(origin, &self.source),
+            None => &self.source,
+        }
+    }
+
+    // the expression being executed, and the ones holding its block
+    fn trace(&self, into: &mut Vec<ErrCtx>) {
+        let mut origin = self.current;
+        while let Some(at) = origin {
+            let source = self.chunk.source(at, &self.source);
+            into.push(ErrCtx::new(source, self.chunk.expr(at)));
+            origin = self.chunk.parent(at);
+        }
+    }
+}
+
+/// # A function or closure being executed, with its own frame
+pub(super) struct Call {
+    ctx: Context,
+    body: Control,
+    finish: Finish,
+}
+
+impl Call {
+    pub(super) fn new(ctx: Context, body: Control, finish: Finish) -> Self {
+        Self { ctx, body, finish }
+    }
+}
+
+/// # How the outputs of a [Call] are checked and given back to the caller
+pub(super) enum Finish {
+    Fn {
+        name: Symbol,
+        global: bool,
+        output_types: Option<TypedOutputs>,
+        trc: TypeResolutionContext,
+    },
+    Closure {
+        output_types: Option<TypedOutputs>,
+    },
+}
+
+enum Step {
+    /// Executed an expression
+    Executed,
+    /// Moved between blocks without executing an expression
+    Moved,
+    Finished(ControlFlow),
+}
+
+/// # Virtual machine executing the [compiled](Chunk) code
+///
+/// Keeps the calls being executed in an explicit stack instead of recursion, so the
+/// execution can stop after any expression and be resumed later
+pub(super) struct Machine {
+    root: Option<Control>,
+    calls: Vec<Call>,
+}
+
+impl Machine {
+    pub(super) fn new(code: &Code) -> Self {
+        let root = Control::new(Chunk::compile(&code.exprs), Arc::from(code.source()));
+        Self {
+            root: Some(root),
+            calls: Vec::new(),
+        }
+    }
+
+    /// # Execute a call made from rust, as if the `origin` expression made it
+    pub(super) fn call(
+        root: &mut Context,
+        origin: Expr,
+        source: Arc<Path>,
+        mut call: Call,
+    ) -> Result<Self, RuntimeErrorKind> {
+        Context::enter_frame(&mut root.shared, &mut call.ctx)?;
+        let origin = Control {
+            current: Some(0),
+            ..Control::new(Chunk::host(origin), source)
+        };
+        Ok(Self {
+            root: Some(origin),
+            calls: vec![call],
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// # Execute until the code finishes, returning its control flow
+    pub(super) fn run_to_end(&mut self, root: &mut Context) -> CResult<ControlFlow> {
+        loop {
+            match self.step(root) {
+                Ok(Step::Finished(flow)) => return Ok(flow),
+                Ok(Step::Executed | Step::Moved) => {}
+                Err(e) => return Err(self.unwind(root, e)),
+            }
+        }
+    }
+
+    /// # Execute up to `limit` expressions, or until a yield is requested
+    ///
+    /// Returns the control flow of the code if it finished
+    pub(super) fn run(&mut self, root: &mut Context, limit: usize) -> CResult<Option<ControlFlow>> {
+        self.swap_shared(root);
+        let mut executed = 0;
+        while executed < limit {
+            match self.step(root) {
+                Ok(Step::Executed) => executed += 1,
+                Ok(Step::Moved) => {}
+                Ok(Step::Finished(flow)) => return Ok(Some(flow)),
+                Err(e) => return Err(self.unwind(root, e)),
+            }
+            let (_, frame) = self.top(root);
+            if std::mem::take(&mut frame.shared.yield_requested) {
+                break;
+            }
+        }
+        self.swap_shared(root);
+        Ok(None)
+    }
+
+    // while paused the root keeps the shared state, which the frame of the current call holds
+    // while executing, so the context of a paused task has its settings
+    fn swap_shared(&mut self, root: &mut Context) {
+        if let Some(call) = self.calls.last_mut() {
+            std::mem::swap(&mut root.shared, &mut call.ctx.shared);
+        }
+    }
+
+    // the chunk being executed and its frame
+    fn top<'a>(&'a mut self, root: &'a mut Context) -> (Option<&'a mut Control>, &'a mut Context) {
+        match self.calls.last_mut() {
+            Some(call) => (Some(&mut call.body), &mut call.ctx),
+            None => (self.root.as_mut(), root),
+        }
+    }
+
+    fn step(&mut self, root: &mut Context) -> Result<Step, RuntimeError> {
+        let (Some(control), ctx) = self.top(root) else {
+            return Ok(Step::Finished(ControlFlow::Continue));
+        };
+        let mut op = control.next_op();
+        let step = if let Op::Expr(origin) = op {
+            control.current = Some(origin);
+            let source = control.chunk.source(origin, &control.source);
+            ctx.begin_expr(control.chunk.expr(origin), source)?;
+            op = control.next_op();
+            Step::Executed
+        } else {
+            Step::Moved
+        };
+        match op {
+            Op::Mark => control.marks.push(ctx.stack.len()),
+            Op::Test { owner, skip } => {
+                control.current = Some(owner);
+                let stack_size = control.marks.pop().expect("check block without a mark");
+                if !ctx.check_result(stack_size)? {
+                    control.pc = skip as usize;
+                }
+            }
+            Op::Jump(to) => control.pc = to as usize,
+            Op::Exit(flow) => return self.exit(root, flow),
+            Op::Expr(_) => unreachable!("expression without an operation"),
+            op => match ctx.execute_op(op, &control.chunk, control.current_source())? {
+                Action::Next => {}
+                Action::Jump(to) => control.pc = to as usize,
+                Action::Call(mut call) => {
+                    Context::enter_frame(&mut ctx.shared, &mut call.ctx)?;
+                    self.calls.push(*call);
+                    return Ok(step);
+                }
+            },
+        }
+        ctx.shared.budget.check_stack(&mut ctx.stack)?;
+        Ok(step)
+    }
+
+    // the chunk being executed ended with the flow
+    fn exit(&mut self, root: &mut Context, flow: ControlFlow) -> Result<Step, RuntimeError> {
+        let Some(mut call) = self.calls.pop() else {
+            self.root = None;
+            return Ok(Step::Finished(flow));
+        };
+        let (_, ctx) = self.top(root);
+        Context::leave_frame(&mut ctx.shared, &mut call.ctx);
+        ctx.finish_call(call.finish, call.ctx)?;
+        ctx.shared.budget.check_stack(&mut ctx.stack)?;
+        Ok(Step::Moved)
+    }
+
+    // the error happened on the current expression of the last chunk
+    fn unwind(&mut self, root: &mut Context, error: RuntimeError) -> RuntimeErrorCtx {
+        let mut trace = Vec::new();
+        while let Some(mut call) = self.calls.pop() {
+            call.body.trace(&mut trace);
+            let (_, ctx) = self.top(root);
+            Context::leave_frame(&mut ctx.shared, &mut call.ctx);
+        }
+        if let Some(control) = self.root.take() {
+            control.trace(&mut trace);
+        }
+        let mut trace = trace.into_iter();
+        let error = error.at(trace.next().expect("error outside of the code"));
+        trace.fold(error, RuntimeErrorCtx::append_stack)
+    }
+}
+
+/// # Result of [advancing](Task::advance) a [Task]
+#[derive(Debug)]
+pub enum TaskState {
+    /// The code has more expressions to execute
+    Pending,
+    /// The code finished, leaving this stack
+    Done(Stack),
+}
+
+/// # Execution of a [Code] that can be paused and resumed
+///
+/// Each call to [advance](Task::advance) executes some expressions, so many scripts can be
+/// interleaved on the same thread. Hooks can end the current advance early with
+/// [`Context::request_yield`]
+///
+/// ```rust
+/// use stck::internals::{RuntimeContext, Task, TaskState, Value};
+/// let token_block = stck::api::get_tokens_str("5 2 - 3 *\n", "Task", &mut stck::cache::Isolated::new()).unwrap();
+/// let code = stck::api::parse_raw_tokens(token_block).unwrap();
+/// let mut task = Task::new(RuntimeContext::new(), &code);
+/// assert!(matches!(task.advance(2), Ok(TaskState::Pending)));
+/// assert_eq!(task.context().get_stack(), [Value::Num(5), Value::Num(2)]);
+/// let Ok(TaskState::Done(stack)) = task.advance(10) else { panic!() };
+/// assert_eq!(stack.as_slice(), [Value::Num(9)]);
+/// ```
+pub struct Task {
+    ctx: Context,
+    machine: Machine,
+}
+
+impl Task {
+    #[must_use]
+    pub fn new(mut ctx: Context, code: &Code) -> Self {
+        ctx.shared.budget.start();
+        Self {
+            ctx,
+            machine: Machine::new(code),
+        }
+    }
+
+    /// # Execute up to `exprs` expressions
+    ///
+    /// Stops early if a hook [requested a yield](Context::request_yield). Once done, the stack
+    /// is moved out of the context, and advancing again gives an empty stack. An error also
+    /// finishes the task
+    pub fn advance(&mut self, exprs: usize) -> CResult<TaskState> {
+        match self.machine.run(&mut self.ctx, exprs)? {
+            None => Ok(TaskState::Pending),
+            Some(_) => Ok(TaskState::Done(std::mem::take(&mut self.ctx.stack))),
+        }
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.machine.is_finished()
+    }
+
+    /// # Context of the top level code
+    ///
+    /// While a function is being executed it has its own context, so this one only changes
+    /// once the function returns. Its settings, like the [limits](Limits) and
+    /// [capabilities](Capabilities), are the ones of the task even while paused in a call
+    #[must_use]
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    #[must_use]
+    pub fn into_context(self) -> Context {
+        self.ctx
+    }
+}
@@ -0,0 +1,672 @@
+//! # Serde integration for [values](Value)
+//!
+//! Enabled by the `serde` feature. Any serde data format can be read into or written from a
+//! [`Value`], and [`to_value`]/[`from_value`] convert rust types directly
+//!
+//! Structs and maps become [`Value::Map`], sequences and tuples become [`Value::Array`], `None`
+//! and `()` become `Value::Option(None)`. Enums are externally tagged: unit variants become their
+//! name as [`Value::Str`] and other variants a single entry map, except for [`Result`]s that
+//! become [`Value::Result`]
+
+use crate::*;
+use ::serde::de::{self, Deserialize, IntoDeserializer};
+use ::serde::ser::{self, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// # Error while converting between rust types and [values](Value)
+#[derive(thiserror::Error, Debug)]
+pub enum SerdeError {
+    #[error("{0}")]
+    Custom(String),
+    #[error("Closures can't be serialized")]
+    Closure,
+    #[error("Number {0} doesn't fit in a stck number")]
+    NumberOutOfRange(String),
+    #[error("Map keys must be strings, got {0}")]
+    KeyMustBeString(Value),
+}
+
+impl ser::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SerdeError::Custom(msg.to_string())
+    }
+}
+
+/// # Convert a rust value into a stck [value](Value)
+///
+/// ```rust
+/// #[derive(serde::Serialize)]
+/// struct Event { name: String, day: u8 }
+/// let v = stck::to_value(&Event { name: "birthday".to_string(), day: 12 }).unwrap();
+/// let map = v.get_map().unwrap();
+/// assert_eq!(map["day"], stck::internals::Value::Num(12));
+/// ```
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// # Convert a stck [value](Value) into a rust value
+///
+/// ```rust
+/// use stck::internals::Value;
+/// let v = Value::from(vec![Value::Num(1), Value::Num(2)]);
+/// let xs: Vec<u32> = stck::from_value(v).unwrap();
+/// assert_eq!(xs, [1, 2]);
+/// ```
+pub fn from_value<T: de::DeserializeOwned>(value: Value) -> Result<T, SerdeError> {
+    T::deserialize(value)
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+        match self {
+            Value::Char(c) => serializer.serialize_char(*c),
+            Value::Str(s) => serializer.serialize_str(s),
+            Value::Num(n) => serializer.serialize_i64(*n as i64),
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Array(xs) => {
+                let mut seq = serializer.serialize_seq(Some(xs.len()))?;
+                for x in xs.iter() {
+                    seq.serialize_element(x)?;
+                }
+                seq.end()
+            }
+            Value::Map(m) => {
+                let mut map = serializer.serialize_map(Some(m.len()))?;
+                for (k, v) in m.iter() {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+            Value::Result(r) => match r.as_ref() {
+                Ok(v) => serializer.serialize_newtype_variant("Result", 0, "Ok", v),
+                Err(v) => serializer.serialize_newtype_variant("Result", 1, "Err", v),
+            },
+            Value::Option(None) => serializer.serialize_none(),
+            Value::Option(Some(v)) => serializer.serialize_some(v.as_ref()),
+            Value::Closure(_) => Err(ser::Error::custom(SerdeError::Closure)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any stck value")
+    }
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        isize::try_from(v)
+            .map(Value::Num)
+            .map_err(|_| E::custom(SerdeError::NumberOutOfRange(v.to_string())))
+    }
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        isize::try_from(v)
+            .map(Value::Num)
+            .map_err(|_| E::custom(SerdeError::NumberOutOfRange(v.to_string())))
+    }
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Value, E> {
+        Ok(Value::Char(v))
+    }
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v.to_string()))
+    }
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        let v = Value::deserialize(deserializer)?;
+        Ok(Value::Option(Some(Box::new(v))))
+    }
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        Value::deserialize(deserializer)
+    }
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut xs = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(x) = seq.next_element()? {
+            xs.push(x);
+        }
+        Ok(Value::from(xs))
+    }
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut m = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((k, v)) = map.next_entry()? {
+            m.insert(k, v);
+        }
+        Ok(Value::from(m))
+    }
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        use de::VariantAccess;
+        let (variant, access): (String, _) = data.variant()?;
+        match variant.as_str() {
+            "Ok" => Ok(Value::from(Ok(access.newtype_variant()?))),
+            "Err" => Ok(Value::from(Err(access.newtype_variant()?))),
+            _ => Err(de::Error::unknown_variant(&variant, &["Ok", "Err"])),
+        }
+    }
+}
+
+struct ValueSerializer;
+
+fn number<T: TryInto<isize> + ToString + Copy>(n: T) -> Result<Value, SerdeError> {
+    n.try_into()
+        .map(Value::Num)
+        .map_err(|_| SerdeError::NumberOutOfRange(n.to_string()))
+}
+
+fn tagged(variant: &str, value: Value) -> Value {
+    Value::from(HashMap::from([(variant.to_string(), value)]))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_i128(self, v: i128) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<Value, SerdeError> {
+        number(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<Value, SerdeError> {
+        Ok(Value::Float(f64::from(v)))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Value, SerdeError> {
+        Ok(Value::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<Value, SerdeError> {
+        Ok(Value::Char(v))
+    }
+    fn serialize_str(self, v: &str) -> Result<Value, SerdeError> {
+        Ok(Value::from(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::Array(Arc::new(
+            v.iter().map(|b| Value::Num(isize::from(*b))).collect(),
+        )))
+    }
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        Ok(Value::Option(None))
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, SerdeError> {
+        Ok(Value::Option(Some(Box::new(to_value(value)?))))
+    }
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::Option(None))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        Ok(Value::from(variant.to_string()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        to_value(value)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        let value = to_value(value)?;
+        Ok(match (name, variant) {
+            ("Result", "Ok") => Value::from(Ok(value)),
+            ("Result", "Err") => Value::from(Err(value)),
+            _ => tagged(variant, value),
+        })
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerdeError> {
+        Ok(SerializeArray {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap {
+            variant: None,
+            entries: HashMap::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap, SerdeError> {
+        Ok(SerializeMap {
+            variant: Some(variant),
+            entries: HashMap::with_capacity(len),
+            key: None,
+        })
+    }
+}
+
+struct SerializeArray {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+    fn finish(self) -> Value {
+        let arr = Value::from(self.items);
+        match self.variant {
+            Some(variant) => tagged(variant, arr),
+            None => arr,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+struct SerializeMap {
+    variant: Option<&'static str>,
+    entries: HashMap<String, Value>,
+    key: Option<String>,
+}
+
+impl SerializeMap {
+    fn finish(self) -> Value {
+        let map = Value::from(self.entries);
+        match self.variant {
+            Some(variant) => tagged(variant, map),
+            None => map,
+        }
+    }
+}
+
+fn map_key(key: Value) -> Result<String, SerdeError> {
+    match key {
+        Value::Str(s) => Ok(Arc::unwrap_or_clone(s)),
+        Value::Char(c) => Ok(c.to_string()),
+        Value::Num(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        other => Err(SerdeError::KeyMustBeString(other)),
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(map_key(to_value(key)?)?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError::Custom("map value serialized before its key".into()))?;
+        self.entries.insert(key, to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.entries.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Value;
+    type Error = SerdeError;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.entries.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(self.finish())
+    }
+}
+
+impl IntoDeserializer<'_, SerdeError> for Value {
+    type Deserializer = Value;
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Char(c) => visitor.visit_char(c),
+            Value::Str(s) => visitor.visit_string(Arc::unwrap_or_clone(s)),
+            Value::Num(n) => visitor.visit_i64(n as i64),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Array(xs) => {
+                let mut seq = de::value::SeqDeserializer::new(Arc::unwrap_or_clone(xs).into_iter());
+                let out = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(out)
+            }
+            Value::Map(m) => {
+                let mut map = de::value::MapDeserializer::new(Arc::unwrap_or_clone(m).into_iter());
+                let out = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(out)
+            }
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(v)) => visitor.visit_some(*v),
+            Value::Result(r) => visitor.visit_enum(EnumDeserializer::from(*r)),
+            Value::Closure(_) => Err(SerdeError::Closure),
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(v)) => visitor.visit_some(*v),
+            v => visitor.visit_some(v),
+        }
+    }
+
+    fn deserialize_unit<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Option(None) => visitor.visit_unit(),
+            v => v.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self {
+            Value::Str(variant) => visitor.visit_enum(EnumDeserializer {
+                variant: Arc::unwrap_or_clone(variant),
+                value: None,
+            }),
+            Value::Map(m) if m.len() == 1 => {
+                let (variant, value) = Arc::unwrap_or_clone(m)
+                    .into_iter()
+                    .next()
+                    .expect("map has one entry");
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            Value::Result(r) => visitor.visit_enum(EnumDeserializer::from(*r)),
+            other => Err(de::Error::invalid_type(
+                de::Unexpected::Other(&TypeTester::from(&other).to_string()),
+                &"a string or a map with a single entry",
+            )),
+        }
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl From<Result<Value, Value>> for EnumDeserializer {
+    fn from(r: Result<Value, Value>) -> Self {
+        let (variant, value) = match r {
+            Ok(v) => ("Ok", v),
+            Err(v) => ("Err", v),
+        };
+        EnumDeserializer {
+            variant: variant.to_string(),
+            value: Some(value),
+        }
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+    type Variant = VariantDeserializer;
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), SerdeError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer(Option<Value>);
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = SerdeError;
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.0 {
+            None | Some(Value::Option(None)) => Ok(()),
+            Some(v) => Err(de::Error::invalid_type(
+                de::Unexpected::Other(&TypeTester::from(&v).to_string()),
+                &"a unit variant",
+            )),
+        }
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        match self.0 {
+            Some(v) => seed.deserialize(v),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a newtype variant",
+            )),
+        }
+    }
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Some(v) => de::Deserializer::deserialize_seq(v, visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a tuple variant",
+            )),
+        }
+    }
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.0 {
+            Some(v) => de::Deserializer::deserialize_map(v, visitor),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"a struct variant",
+            )),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! # Standard library embedded in the crate
+//!
+//! Included with `(include <std>)`, or one module at a time like `(include <std/arr>)`,
+//! without reading the filesystem. Every module includes the others it uses, and is only
+//! included once
+//!
+//! ```rust
+//! let token_block = stck::api::get_tokens_str("(include <std>)\n2 3 +\n", "With std", &mut stck::cache::Isolated::new()).unwrap();
+//! let code = stck::api::parse_raw_tokens(token_block).unwrap();
+//! let ctx = stck::api::execute_raw_code(&code).unwrap();
+//! assert_eq!(ctx.get_stack(), [stck::internals::Value::Num(5)]);
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// # Directory holding the modules
+///
+/// Sources of the included code start with it, like `<std>/arr.stck`
+pub const ROOT: &str = "<std>";
+
+const ENTRY: &str = "stck.stck";
+
+const FILES: &[(&str, &str)] = &[
+    ("stck.stck", include_str!("../stdlib/stck.stck")),
+    ("argv.stck", include_str!("../stdlib/argv.stck")),
+    ("arr.stck", include_str!("../stdlib/arr.stck")),
+    ("bool.stck", include_str!("../stdlib/bool.stck")),
+    ("error.stck", include_str!("../stdlib/error.stck")),
+    ("logic.stck", include_str!("../stdlib/logic.stck")),
+    ("map.stck", include_str!("../stdlib/map.stck")),
+    ("math.stck", include_str!("../stdlib/math.stck")),
+    ("print.stck", include_str!("../stdlib/print.stck")),
+    ("stack.stck", include_str!("../stdlib/stack.stck")),
+    ("string.stck", include_str!("../stdlib/string.stck")),
+    ("types.stck", include_str!("../stdlib/types.stck")),
+];
+
+/// # Names of the modules, usable as `<std/name>`
+pub fn modules() -> impl Iterator<Item = &'static str> {
+    FILES
+        .iter()
+        .filter(|(file, _)| *file != ENTRY)
+        .filter_map(|(file, _)| file.strip_suffix(".stck"))
+}
+
+/// # Contents of a file of the standard library
+///
+/// `None` if the path isn't inside the [root](ROOT) or the file doesn't exist
+#[must_use]
+pub fn file(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(ROOT).ok()?.to_str()?;
+    FILES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, cont)| *cont)
+}
+
+/// # Path of the file an `(include)` of the standard library reads
+///
+/// Handles `<std>` and `<std/module>` anywhere, and relative paths included by the
+/// standard library itself
+pub(crate) fn resolve(include: &Path, dir: &Path) -> Option<PathBuf> {
+    let Some(reserved) = include
+        .to_str()
+        .and_then(|path| path.strip_prefix('<')?.strip_suffix('>'))
+    else {
+        return dir.starts_with(ROOT).then(|| dir.join(include));
+    };
+    let file = match reserved.strip_prefix("std") {
+        Some("") => ENTRY.to_string(),
+        Some(module) => {
+            let module = module.strip_prefix('/')?;
+            if Path::new(module).extension().is_some() {
+                module.to_string()
+            } else {
+                format!("{module}.stck")
+            }
+        }
+        None => return None,
+    };
+    Some(Path::new(ROOT).join(file))
+}
@@ -0,0 +1,89 @@
+//! # Interned names
+//!
+//! Names of functions and hooks are interned, so the runtime compares and hashes them as
+//! pointers instead of strings. A name is freed once no symbol holds it anymore
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError, Weak};
+
+static SYMBOLS: LazyLock<Mutex<Interner>> = LazyLock::new(Mutex::default);
+
+#[derive(Default)]
+struct Interner {
+    names: HashMap<String, Weak<str>>,
+    // names that were freed are only forgotten once there are this many
+    prune_at: usize,
+}
+
+/// # An interned name
+///
+/// Two symbols are equal when they were interned from the same name. The name is shared by
+/// every symbol interned from it, and freed with the last one
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    #[must_use]
+    pub fn intern(name: &str) -> Self {
+        let mut symbols = symbols();
+        if let Some(name) = symbols.names.get(name).and_then(Weak::upgrade) {
+            return Symbol(name);
+        }
+        if symbols.names.len() >= symbols.prune_at {
+            symbols.names.retain(|_, name| name.strong_count() > 0);
+            symbols.prune_at = (symbols.names.len() * 2).max(64);
+        }
+        let symbol: Arc<str> = Arc::from(name);
+        symbols
+            .names
+            .insert(name.to_string(), Arc::downgrade(&symbol));
+        Symbol(symbol)
+    }
+
+    /// # Symbol of a name, if a symbol of it is still held
+    #[must_use]
+    pub fn get(name: &str) -> Option<Self> {
+        symbols()
+            .names
+            .get(name)
+            .and_then(Weak::upgrade)
+            .map(Symbol)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+// names are only ever added whole, so a panic while holding the lock can't leave it broken
+fn symbols() -> MutexGuard<'static, Interner> {
+    SYMBOLS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(Arc::as_ptr(&self.0).cast::<u8>(), state);
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
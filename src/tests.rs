@@ -1,5 +1,14 @@
+mod cache;
+mod check;
+mod convert;
+mod format;
+mod limits;
 mod parse;
+mod precompiled;
 mod runtime;
+#[cfg(feature = "serde")]
+mod serialize;
+mod stdlib;
 mod token;
 mod typing;
 
@@ -0,0 +1,206 @@
+use super::*;
+use crate::{
+    api,
+    cache::{FileCacher, Isolated, Overlay, VirtualTree},
+    error::{Error, StckError},
+    internals::Value,
+};
+use std::path::{Path, PathBuf};
+
+fn run(path: &str, file_cache: &mut impl FileCacher) -> Result<Vec<Value>, Error> {
+    let code = api::get_project_code(path, file_cache)?;
+    Ok(api::execute_raw_code(&code)?.get_stack().to_vec())
+}
+
+#[test]
+fn virtual_directory_include() -> Result<(), Error> {
+    let mut tree = VirtualTree::new()
+        .with_file(
+            "app/main.stck",
+            "(include lib)\n(include ./lib/../extra.stck)\n1 2 sub\n",
+        )
+        .with_file("app/lib/stck.stck", "(include sub.stck)\n")
+        .with_file("app/lib/sub.stck", "(fn) [ a b ] sub { a b - }\n")
+        .with_file("app/extra.stck", "10\n");
+    assert!(tree.contains_dir("app/lib"));
+    assert!(!tree.contains_dir("app/lib/sub.stck"));
+    let stack = run("app/main.stck", &mut tree)?;
+    test_eq!(got: stack, expected: [Value::Num(10), Value::Num(-1)]);
+    Ok(())
+}
+
+#[test]
+fn overlay_prefers_first_layer() -> Result<(), Error> {
+    let base = VirtualTree::new()
+        .with_file("main.stck", "(include dep.stck)\n1\n")
+        .with_file("dep.stck", "2\n");
+    let patch = VirtualTree::new().with_file("dep.stck", "3\n");
+    let mut cacher = Overlay::new(patch, base).then(Isolated::new());
+    let stack = run("main.stck", &mut cacher)?;
+    test_eq!(got: stack, expected: [Value::Num(3), Value::Num(1)]);
+    assert!(cacher.read_file("missing.stck").is_err());
+    Ok(())
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_bundle() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::cache::TarArchive;
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, script) in [
+        ("bundle/main.stck", "(include lib)\n4 twice\n"),
+        ("bundle/lib/stck.stck", "(fn) [ n ] twice { n 2 * }\n"),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, path, script.as_bytes())?;
+    }
+    let mut archive = TarArchive::from_reader(builder.into_inner()?.as_slice())?;
+    let stack = run("bundle/main.stck", &mut archive)?;
+    test_eq!(got: stack, expected: [Value::Num(8)]);
+    Ok(())
+}
+
+#[test]
+fn mocked_directory_includes() -> Result<(), Error> {
+    use crate::cache::MockFileCacher;
+    let mut mock = MockFileCacher::new();
+    for (path, content) in [
+        (
+            "proj/main.stck",
+            "(include lib)\n(include lib/nested)\n1 nested\n",
+        ),
+        ("proj/lib/stck.stck", "(include ../util.stck)\n"),
+        (
+            "proj/lib/nested/stck.stck",
+            "(fn) [ n ] nested { n util }\n",
+        ),
+        ("proj/util.stck", "(fn) [ n ] util { n 10 * }\n"),
+    ] {
+        mock.mock_file(path.into(), content.to_string());
+    }
+    assert!(mock.is_dir("proj/lib/nested"));
+    test_eq!(
+        got: mock.resolve_include(Path::new("proj"), Path::new("lib")),
+        expected: Some(PathBuf::from("proj/lib/stck.stck"))
+    );
+    let stack = run("proj/main.stck", &mut mock)?;
+    test_eq!(got: stack, expected: [Value::Num(10)]);
+    Ok(())
+}
+
+#[test]
+fn isolated_directory_includes() -> Result<(), Error> {
+    let mut isolated = Isolated::new();
+    isolated.allow_file("main.stck".into(), "(include lib)\n".to_string());
+    isolated.allow_file("lib/stck.stck".into(), "5\n".to_string());
+    let stack = run("main.stck", &mut isolated)?;
+    test_eq!(got: stack, expected: [Value::Num(5)]);
+
+    // exists on disk, but the isolated cacher doesn't know it
+    isolated.allow_file("escape.stck".into(), "\n(include examples)\n".to_string());
+    assert!(!isolated.is_dir("examples"));
+    let Err(Error::Anoter(err)) = run("escape.stck", &mut isolated) else {
+        panic!("included a directory from disk");
+    };
+    assert!(matches!(err.unspanned(), StckError::CantReadFile(p) if p == Path::new("examples")));
+    test_eq!(got: err.span().map(|(_, span)| span.start()), expected: Some(2));
+    Ok(())
+}
+
+#[test]
+fn directory_without_entry() {
+    let mut tree = VirtualTree::new()
+        .with_file("main.stck", "(include lib)\n")
+        .with_file("lib/other.stck", "1\n");
+    test_eq!(got: tree.resolve_include(Path::new(""), Path::new("lib")), expected: None::<PathBuf>);
+    assert!(run("main.stck", &mut tree).is_err());
+}
+
+#[test]
+fn tracking_changes() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::cache::TrackingCache;
+    use std::collections::BTreeSet;
+    use std::time::{Duration, SystemTime};
+    let dir = std::env::temp_dir().join(format!("stck-tracking-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib"))?;
+    let (main, entry, lib) = (
+        dir.join("main.stck"),
+        dir.join("lib/stck.stck"),
+        dir.join("lib/math.stck"),
+    );
+    std::fs::write(&main, "(include lib)\n1 double\n")?;
+    std::fs::write(&entry, "(include math.stck)\n")?;
+    std::fs::write(&lib, "(fn) [ n ] double { n 2 * }\n")?;
+    // file systems with coarse timestamps could miss a quick write
+    let write_later = |path: &Path, content: &str, secs: u64| -> std::io::Result<()> {
+        std::fs::write(path, content)?;
+        let file = std::fs::File::options().write(true).open(path)?;
+        file.set_modified(SystemTime::now() + Duration::from_secs(secs))
+    };
+
+    let mut cache = TrackingCache::new();
+    let stack = run(main.to_str().unwrap(), &mut cache)?;
+    test_eq!(got: stack, expected: [Value::Num(2)]);
+    test_eq!(got: cache.scripts(), expected: BTreeSet::from([main.clone()]));
+    test_eq!(got: cache.dependencies(&main), expected: BTreeSet::from([entry.clone(), lib.clone()]));
+    assert!(cache.refresh().is_empty());
+
+    // touching without changing the contents isn't a change
+    write_later(&lib, "(fn) [ n ] double { n 2 * }\n", 10)?;
+    assert!(cache.refresh().is_empty());
+
+    write_later(&lib, "(fn) [ n ] double { n 3 * }\n", 20)?;
+    test_eq!(got: cache.changed_files(), expected: BTreeSet::from([lib.clone()]));
+    test_eq!(got: cache.refresh(), expected: BTreeSet::from([main.clone()]));
+    let stack = run(main.to_str().unwrap(), &mut cache)?;
+    test_eq!(got: stack, expected: [Value::Num(3)]);
+    assert!(cache.stale_scripts().is_empty());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn tracking_missing_includes() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::cache::TrackingCache;
+    use std::collections::BTreeSet;
+    let dir = std::env::temp_dir().join(format!("stck-missing-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let (main, lib) = (dir.join("main.stck"), dir.join("lib"));
+    std::fs::write(&main, "(include lib)\n1 double\n")?;
+
+    let mut cache = TrackingCache::new();
+    assert!(run(main.to_str().unwrap(), &mut cache).is_err());
+    test_eq!(got: cache.dependencies(&main), expected: BTreeSet::from([lib.clone()]));
+    assert!(cache.refresh().is_empty());
+
+    // the include is found once the directory has its entry
+    std::fs::create_dir_all(&lib)?;
+    assert!(cache.refresh().is_empty());
+    std::fs::write(lib.join("stck.stck"), "(fn) [ n ] double { n 2 * }\n")?;
+    test_eq!(got: cache.refresh(), expected: BTreeSet::from([main.clone()]));
+    let stack = run(main.to_str().unwrap(), &mut cache)?;
+    test_eq!(got: stack, expected: [Value::Num(2)]);
+    let entry = lib.join("stck.stck");
+    test_eq!(got: cache.dependencies(&main), expected: BTreeSet::from([entry.clone()]));
+    assert!(cache.refresh().is_empty());
+
+    // a changed script keeps its includes until it's parsed again
+    std::fs::write(&main, "(include lib)\n2 double\n")?;
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+    std::fs::File::options()
+        .write(true)
+        .open(&main)?
+        .set_modified(later)?;
+    test_eq!(got: cache.refresh(), expected: BTreeSet::from([main.clone()]));
+    test_eq!(got: cache.scripts(), expected: BTreeSet::<PathBuf>::new());
+    test_eq!(got: cache.dependencies(&main), expected: BTreeSet::from([entry.clone()]));
+    let stack = run(main.to_str().unwrap(), &mut cache)?;
+    test_eq!(got: stack, expected: [Value::Num(4)]);
+    test_eq!(got: cache.scripts(), expected: BTreeSet::from([main.clone()]));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use super::*;
+use crate::{
+    api,
+    cache::NoCache,
+    check::{DiagnosticKind, check, check_with_stack},
+    internals::Value,
+};
+
+fn diagnostics(cont: &str) -> Vec<(usize, DiagnosticKind)> {
+    let tokens = api::get_tokens_str(cont, "check test", &mut NoCache).unwrap();
+    let code = api::parse_raw_tokens(tokens).unwrap();
+    check(&code)
+        .into_iter()
+        .map(|d| (d.span.start, d.kind))
+        .collect()
+}
+
+#[test]
+fn underflows() {
+    let got = diagnostics(
+        "(fn) [ a b ] sub { a b - }\n1 sub\n(fn) [ x ] f { x - }\n(fn) [] [ a b ] two { 1 2 }\ntwo - - -\n",
+    );
+    let expected = [
+        (
+            2,
+            DiagnosticKind::Underflow {
+                name: "sub".to_string(),
+                needs: 2,
+                has: 1,
+            },
+        ),
+        (
+            3,
+            DiagnosticKind::Underflow {
+                name: "-".to_string(),
+                needs: 2,
+                has: 1,
+            },
+        ),
+        (
+            5,
+            DiagnosticKind::Underflow {
+                name: "-".to_string(),
+                needs: 2,
+                has: 1,
+            },
+        ),
+    ];
+    test_eq!(got: got, expected: expected);
+}
+
+#[test]
+fn known_and_unknown_effects() {
+    // arr$pack-n with an immediate count and the host's stack are known
+    let got = diagnostics("1 2 3 2 arr$pack-n -\n");
+    assert!(got.is_empty(), "{got:?}");
+    let tokens = api::get_tokens_str("-\n", "check test", &mut NoCache).unwrap();
+    let code = api::parse_raw_tokens(tokens).unwrap();
+    assert!(check_with_stack(&code, 2).is_empty());
+    // rust hooks and closure calls push an unknown amount of values
+    let got = diagnostics("hook -\n[ x ] { x } 1 @ - -\n");
+    assert!(got.is_empty(), "{got:?}");
+}
+
+#[test]
+fn branches_and_checks() {
+    let got = diagnostics(
+        "(fn) [ x ] f {\n(ifs) { x 1 = } { 1 2 } { x 2 = } { 3 } {1 1 =} { 4 }\n}\n(while) { 1 } { }\n(while) { 1 1 = 2 } { }\n",
+    );
+    let expected = [
+        (
+            2,
+            DiagnosticKind::UnbalancedBranches {
+                deltas: vec![2, 1, 1],
+            },
+        ),
+        (4, DiagnosticKind::CheckNotBool { got: Value::Num(1) }),
+        (5, DiagnosticKind::CheckSize { delta: 2 }),
+    ];
+    test_eq!(got: got, expected: expected);
+}
+
+#[test]
+fn every_builtin_has_an_effect() {
+    for name in crate::internals::BUILTINS {
+        assert!(
+            crate::check::builtin_effect(name, Some(0)).is_some(),
+            "no effect for {name}"
+        );
+    }
+}
+
+#[test]
+fn shadowed_builtins() {
+    let got =
+        diagnostics("(fn) [ x ] - { x }\n(fn) [ print ] f { 1 print }\n[ sh ] { \"true\" sh }\n");
+    let expected = [
+        (
+            1,
+            DiagnosticKind::ShadowedFn {
+                name: "-".to_string(),
+            },
+        ),
+        (
+            2,
+            DiagnosticKind::ShadowedArg {
+                name: "print".to_string(),
+            },
+        ),
+        (
+            3,
+            DiagnosticKind::ShadowedArg {
+                name: "sh".to_string(),
+            },
+        ),
+    ];
+    test_eq!(got: got, expected: expected);
+    let got = diagnostics("(include <std>)\n");
+    let shadowed = got.iter().filter(|(_, kind)| {
+        matches!(
+            kind,
+            DiagnosticKind::ShadowedFn { .. } | DiagnosticKind::ShadowedArg { .. }
+        )
+    });
+    let shadowed = shadowed.count();
+    test_eq!(got: shadowed, expected: 0);
+}
@@ -0,0 +1,191 @@
+use super::*;
+use crate::convert::{FromValue, FromValueError, FromValueErrorKind, IntoValue};
+use crate::internals::Value;
+use std::collections::HashMap;
+
+fn error_path<T: FromValue + std::fmt::Debug>(value: Value) -> (String, FromValueErrorKind) {
+    let FromValueError { path, kind } = T::from_value(value).unwrap_err();
+    (path, kind)
+}
+
+#[test]
+fn collections_round_trip() {
+    let ports: Vec<u16> = vec![80, 443];
+    let value = ports.clone().into_value();
+    test_eq!(got: value, expected: Value::from(vec![Value::Num(80), Value::Num(443)]));
+    let back = Vec::<u16>::from_value(value).unwrap();
+    test_eq!(got: back, expected: ports);
+
+    let mut env = HashMap::new();
+    env.insert("home".to_string(), Some("/root".to_string()));
+    env.insert("user".to_string(), None);
+    let back = HashMap::<String, Option<String>>::from_value(env.clone().into_value()).unwrap();
+    test_eq!(got: back, expected: env);
+
+    let result: Result<isize, String> = Err("failed".to_string());
+    let back = Result::<isize, String>::from_value(result.clone().into_value()).unwrap();
+    test_eq!(got: back, expected: result);
+}
+
+#[test]
+fn large_integers_saturate() {
+    let largest = usize::try_from(isize::MAX).unwrap();
+    let value = largest.into_value();
+    test_eq!(got: value, expected: Value::Num(isize::MAX));
+    let back = usize::from_value(value).unwrap();
+    test_eq!(got: back, expected: largest);
+    let value = (largest + 1).into_value();
+    test_eq!(got: value, expected: Value::Num(isize::MAX));
+    let value = usize::MAX.into_value();
+    test_eq!(got: value, expected: Value::Num(isize::MAX));
+}
+
+#[test]
+fn conversion_errors_have_paths() {
+    let value = Value::from(vec![Value::Num(1), Value::Num(-1)]);
+    let (path, kind) = error_path::<Vec<u8>>(value);
+    test_eq!(got: path, expected: "[1]");
+    assert!(matches!(
+        kind,
+        FromValueErrorKind::WrongType {
+            expected: crate::TypeTester::Num,
+            ..
+        }
+    ));
+
+    let mut map = HashMap::new();
+    map.insert(
+        "a".to_string(),
+        Value::from(vec![Value::from("x".to_string())]),
+    );
+    let (path, _) = error_path::<HashMap<String, Vec<isize>>>(Value::from(map));
+    test_eq!(got: path, expected: "[\"a\"][0]");
+
+    let (path, kind) = error_path::<Option<bool>>(Value::Num(0));
+    test_eq!(got: path, expected: "");
+    assert!(matches!(kind, FromValueErrorKind::WrongType { .. }));
+}
+
+#[cfg(feature = "derive")]
+mod derive {
+    use super::*;
+    use crate::convert::StckValue;
+    use std::sync::Arc;
+
+    #[derive(StckValue, Debug, PartialEq, Clone)]
+    struct Server {
+        name: String,
+        ports: Vec<u16>,
+        admin: Option<User>,
+        r#type: Kind,
+    }
+
+    #[derive(StckValue, Debug, PartialEq, Clone)]
+    struct User(String);
+
+    #[derive(StckValue, Debug, PartialEq, Clone)]
+    struct Point(isize, isize);
+
+    #[derive(StckValue, Debug, PartialEq, Clone)]
+    enum Kind {
+        Local,
+        Remote { host: String, at: Point },
+        Proxy(Box<Kind>, u16),
+    }
+
+    fn server() -> Server {
+        Server {
+            name: "main".to_string(),
+            ports: vec![80],
+            admin: Some(User("root".to_string())),
+            r#type: Kind::Proxy(
+                Box::new(Kind::Remote {
+                    host: "example.org".to_string(),
+                    at: Point(1, 2),
+                }),
+                8080,
+            ),
+        }
+    }
+
+    #[test]
+    fn derived_round_trip() {
+        let value = server().into_value();
+        let Value::Map(map) = &value else {
+            panic!("structs become maps, got {value:?}");
+        };
+        test_eq!(got: map.get("admin"), expected: Some(&Value::Option(Some(Box::new(Value::from("root".to_string()))))));
+        let Some(Value::Map(kind)) = map.get("type") else {
+            panic!("enums become maps");
+        };
+        test_eq!(got: kind.get("tag"), expected: Some(&Value::from("Proxy".to_string())));
+        test_eq!(got: kind.get("1"), expected: Some(&Value::Num(8080)));
+
+        let back = Server::from_value(value).unwrap();
+        test_eq!(got: back, expected: server());
+
+        let local = Kind::from_value(Kind::Local.into_value()).unwrap();
+        test_eq!(got: local, expected: Kind::Local);
+    }
+
+    #[test]
+    fn derived_errors_name_the_field() {
+        let mut map = server().into_value().get_map().unwrap();
+        map.remove("admin");
+        let back = Server::from_value(Value::from(map.clone())).unwrap();
+        test_eq!(got: back.admin, expected: None::<User>);
+
+        let Some(Value::Map(kind)) = map.get_mut("type") else {
+            unreachable!()
+        };
+        let Some(Value::Map(remote)) = Arc::make_mut(kind).get_mut("0") else {
+            unreachable!()
+        };
+        Arc::make_mut(remote).insert("at".to_string(), Value::from(vec![Value::Num(1)]));
+        let (path, kind) = error_path::<Server>(Value::from(map.clone()));
+        test_eq!(got: path, expected: "type.0.at");
+        assert!(matches!(
+            kind,
+            FromValueErrorKind::WrongLength {
+                expected: 2,
+                got: 1
+            }
+        ));
+
+        map.remove("name");
+        let (path, kind) = error_path::<Server>(Value::from(map));
+        test_eq!(got: path, expected: "name");
+        assert!(matches!(kind, FromValueErrorKind::MissingField));
+
+        let mut tagged = HashMap::new();
+        tagged.insert("tag".to_string(), Value::from("Cloud".to_string()));
+        let (path, kind) = error_path::<Kind>(Value::from(tagged));
+        test_eq!(got: path, expected: "tag");
+        assert!(matches!(kind, FromValueErrorKind::UnknownVariant(v) if v == "Cloud"));
+    }
+
+    #[test]
+    fn derived_types_in_hooks() -> Result<(), crate::Error> {
+        use crate::{api, cache::NoCache, internals::RuntimeContext};
+        #[derive(StckValue)]
+        struct Listen {
+            ports: Vec<u16>,
+        }
+        let mut runtime = RuntimeContext::new();
+        runtime.add_typed_fn("describe", |listen: Listen| listen.ports.len());
+        let tokens = api::get_tokens_str(
+            "map$new \"ports\" 1 \"two\" 2 arr$pack-n map$insert-kv describe\n",
+            "derived hook",
+            &mut NoCache,
+        )?;
+        let err = runtime
+            .execute_entire_code(&api::parse_raw_tokens(tokens)?)
+            .err()
+            .map(|e| e.kind.to_string());
+        test_eq!(
+            got: err.as_deref(),
+            expected: Some("Function describe accepts [ map ]. But argument #0 `ports[1]` must be a num and got \"two\"")
+        );
+        Ok(())
+    }
+}
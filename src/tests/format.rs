@@ -0,0 +1,49 @@
+use super::*;
+use crate::format::{format, is_formatted};
+
+#[test]
+fn canonical_layout() {
+    let text = "
+#! comment at the start
+(fn)   [a b]   [out] name {
+      a b   (ifs)
+  { a } {
+     1,  2,
+        }
+            {true} { 'c' \"str  with  spaces\" }
+   a (switch) 1 {a}
+   # the default case
+   { b }
+   drop
+
+
+  [ x ] { x } }
+";
+    let expected = "#! comment at the start
+(fn) [ a b ] [ out ] name {
+	a b (ifs)
+		{ a } {
+			1, 2,
+		}
+		{ true } { 'c' \"str  with  spaces\" }
+	a (switch) 1 { a }
+		# the default case
+		{ b }
+	drop
+
+	[ x ] { x }
+}
+";
+    let got = format(text, "format test").unwrap();
+    test_eq!(got: got.as_str(), expected: expected);
+}
+
+#[test]
+fn formatting_is_stable() {
+    let text = "(fn) [] f {\n\t1 # one\n\n\t# two\n\t2\n}\nf {} drop\n";
+    assert!(is_formatted(text, "format test").unwrap());
+    let messy = "(fn)[]f{\n1 # one\n\n\n# two\n    2}\nf {  } drop";
+    let got = format(messy, "format test").unwrap();
+    test_eq!(got: got.as_str(), expected: text);
+    assert!(!is_formatted(messy, "format test").unwrap());
+}
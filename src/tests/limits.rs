@@ -0,0 +1,156 @@
+use super::*;
+use crate::{
+    api,
+    cache::Isolated,
+    error::{Error, RuntimeErrorKind},
+    internals::{Interruption, Limits, RuntimeContext},
+};
+
+fn execute_limited(cont: &str, limits: Limits) -> Result<RuntimeContext, Error> {
+    let tokens = api::get_tokens_str(cont, "limits test", &mut Isolated::new())?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let mut runtime = RuntimeContext::new().with_limits(limits);
+    runtime.execute_entire_code(&code)?;
+    Ok(runtime)
+}
+
+fn limit_error(result: Result<RuntimeContext, Error>) -> RuntimeErrorKind {
+    match result {
+        Err(Error::RuntimeError(e)) => *e.kind,
+        Err(e) => panic!("expected runtime error, got {e}"),
+        Ok(ctx) => panic!("expected runtime error, got stack {:?}", ctx.get_stack()),
+    }
+}
+
+#[test]
+fn expr_limit() {
+    let err = limit_error(execute_limited(
+        "(while) { 1 1 = } {}\n",
+        Limits::new().with_max_exprs(100),
+    ));
+    assert!(matches!(
+        err,
+        RuntimeErrorKind::ExprLimitExceeded { limit: 100 }
+    ));
+}
+
+#[test]
+fn call_depth_limit() {
+    let err = limit_error(execute_limited(
+        "(fn) [n] deep { n deep }\n0 deep\n",
+        Limits::new().with_max_call_depth(16),
+    ));
+    assert!(matches!(
+        err,
+        RuntimeErrorKind::CallDepthExceeded { limit: 16 }
+    ));
+}
+
+#[test]
+fn stack_and_alloc_limits() -> Result<(), Error> {
+    let err = limit_error(execute_limited(
+        "(while) { 1 1 = } { 1 }\n",
+        Limits::new().with_max_stack_size(10),
+    ));
+    assert!(matches!(
+        err,
+        RuntimeErrorKind::StackSizeExceeded { limit: 10, got: 11 }
+    ));
+
+    let err = limit_error(execute_limited(
+        "(while) { 1 1 = } { \"abcd\" str$into-arr }\n",
+        Limits::new().with_max_alloc(10),
+    ));
+    assert!(matches!(
+        err,
+        RuntimeErrorKind::AllocLimitExceeded { limit: 10, got: 12 }
+    ));
+
+    // copies of the same string share it, so it's counted once
+    let ctx = execute_limited(
+        "(fn) [ s ] three { s s s }\n\"abcd\" three\n",
+        Limits::new().with_max_alloc(10),
+    )?;
+    test_eq!(got: ctx.get_stack().len(), expected: 3);
+
+    let ctx = execute_limited("1 2 3\n", Limits::new().with_max_stack_size(3))?;
+    test_eq!(got: ctx.executed_exprs(), expected: 3);
+    Ok(())
+}
+
+#[test]
+fn deadline() {
+    let err = limit_error(execute_limited(
+        "(while) { 1 1 = } {}\n",
+        Limits::new().with_timeout(std::time::Duration::from_millis(20)),
+    ));
+    assert!(matches!(
+        err,
+        RuntimeErrorKind::Interrupted(Interruption::Deadline)
+    ));
+    test_eq!(got: err.to_string(), expected: "The script was stopped by its deadline");
+}
+
+#[test]
+fn timeout_starts_with_the_execution() -> Result<(), Error> {
+    use crate::internals::{Task, TaskState};
+    let tokens = api::get_tokens_str("5 2 -\n", "timeout test", &mut Isolated::new())?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let timeout = std::time::Duration::from_millis(20);
+    let mut runtime = RuntimeContext::new().with_limits(Limits::new().with_timeout(timeout));
+    std::thread::sleep(timeout * 2);
+    runtime.execute_entire_code(&code)?;
+
+    std::thread::sleep(timeout * 2);
+    let tokens = api::get_tokens_str(
+        "(fn) [ a ] f { a 1 - }\n",
+        "timeout test",
+        &mut Isolated::new(),
+    )?;
+    runtime.execute_entire_code(&api::parse_raw_tokens(tokens)?)?;
+    std::thread::sleep(timeout * 2);
+    let outputs = runtime.call_fn("f", vec![crate::internals::Value::Num(5)])?;
+    test_eq!(got: outputs, expected: [crate::internals::Value::Num(4)]);
+
+    std::thread::sleep(timeout * 2);
+    let mut task = Task::new(runtime, &code);
+    let state = task.advance(10)?;
+    assert!(matches!(state, TaskState::Done(_)));
+    Ok(())
+}
+
+#[test]
+fn interrupt_from_another_thread() -> Result<(), Error> {
+    let tokens = api::get_tokens_str(
+        "1 (while) { 1 1 = } {}\n",
+        "interrupt test",
+        &mut Isolated::new(),
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let mut runtime = RuntimeContext::new();
+    let handle = runtime.interrupt_handle();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        handle.interrupt();
+    });
+    let err = runtime.execute_entire_code(&code).err().unwrap();
+    interrupter.join().unwrap();
+    assert!(matches!(
+        *err.kind,
+        RuntimeErrorKind::Interrupted(Interruption::Handle)
+    ));
+    // stops inside the loop
+    test_eq!(got: err.ctx.expr.span.start(), expected: 1);
+    test_eq!(got: err.stack.len(), expected: 1);
+
+    // the interruption stays until cleared
+    runtime.stack.clear();
+    let tokens = api::get_tokens_str("\"sleep 9\" sh\n", "interrupt test", &mut Isolated::new())?;
+    let code = api::parse_raw_tokens(tokens)?;
+    runtime.reset_usage();
+    let err = runtime.execute_entire_code(&code).err().unwrap();
+    assert!(matches!(*err.kind, RuntimeErrorKind::Interrupted(_)));
+    runtime.interrupt_handle().clear();
+    runtime.execute_entire_code(&code)?;
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use std::path::PathBuf;
 
 use super::*;
-use crate::KeywordKind;
 use crate::LineRange;
+use crate::{Builtin, KeywordKind, Resolved, Symbol};
 
 #[test]
 fn parse_tokens() -> Result<(), crate::error::Error> {
@@ -38,11 +38,14 @@ fn parse_tokens() -> Result<(), crate::error::Error> {
             code: vec![
                 Expr {
                     span: LineRange::from_points(3, 3),
-                    cont: FnCall("inputs".to_string()),
+                    cont: FnCall(
+                        "inputs".to_string(),
+                        Resolved::Dynamic(Symbol::intern("inputs")),
+                    ),
                 },
                 Expr {
                     span: LineRange::from_points(3, 3),
-                    cont: FnCall("typed".to_string()),
+                    cont: FnCall("typed".to_string(), Resolved::Arg(0)),
                 },
                 Expr {
                     span: LineRange::from_points(3, 3),
@@ -50,20 +53,51 @@ fn parse_tokens() -> Result<(), crate::error::Error> {
                 },
                 Expr {
                     span: LineRange::from_points(3, 3),
-                    cont: FnCall("-".to_string()),
+                    cont: FnCall("-".to_string(), Resolved::Builtin(Builtin::Sub)),
                 },
                 Expr {
                     span: LineRange::from_points(3, 3),
-                    cont: FnCall("-".to_string()),
+                    cont: FnCall("-".to_string(), Resolved::Builtin(Builtin::Sub)),
                 },
-            ],
+            ]
+            .into(),
             out_args: Some(vec![crate::FnArgDef {
                 name: "sum".to_string(),
                 type_check: Some(crate::TypeTester::Num),
             }]),
         }),
     }];
+    let expr_expected: crate::Exprs = expr_expected.into();
     test_eq!(got: expr.exprs, expected: expr_expected);
 
     Ok(())
 }
+
+#[test]
+fn errors_have_spans() {
+    let text = "1 2\n(fn) [ a ] broken {\n  a\n(fn) [] \n";
+    let err = crate::api::get_tokens_str(text, "span test", &mut crate::cache::NoCache)
+        .and_then(crate::api::parse_raw_tokens)
+        .unwrap_err();
+    let crate::error::Error::Anoter(err) = err else {
+        panic!("expected a parse error, got {err}");
+    };
+    let (file, span) = err.span().expect("error without a span");
+    test_eq!(got: file, expected: std::path::Path::new("span test"));
+    assert!(span.start() >= 2, "span starts at {span}");
+}
+
+#[test]
+fn names_are_freed_with_the_code() -> Result<(), crate::error::Error> {
+    let name = "freed-with-the-code";
+    let text = format!("(fn) [] {name} {{ 1 }}\n{name}\n");
+    let tokens = crate::api::get_tokens_str(&text, "freed names", &mut crate::cache::NoCache)?;
+    let code = crate::api::parse_raw_tokens(tokens)?;
+    assert!(Symbol::get(name).is_some());
+    let again = Symbol::intern(name);
+    drop(code);
+    test_eq!(got: Symbol::get(name), expected: Some(again.clone()));
+    drop(again);
+    assert!(Symbol::get(name).is_none());
+    Ok(())
+}
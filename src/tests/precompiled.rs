@@ -0,0 +1,150 @@
+use super::*;
+use crate::{
+    api,
+    cache::VirtualTree,
+    error::Error,
+    internals::Value,
+    precompiled::{CodeCache, DecodeError, decode, encode},
+};
+
+const MAIN: &str = "(include lib.stck)
+(TRC Small num char)
+(fn) [ n<num> ] [ <num> ] double { n 2 * }
+[ x<Small> ] [ <num> ] { x double } 4 @
+(@double) 5 @
+\"s\" (switch) \"s\" { 1 } 'c' { 2 } { 3 }
+(fn) [ n ] pick { (ifs) { n 1 = } { 1.5 (return) } { 1 1 = } { 'z' } }
+1 pick 2 pick
+(fn) [] count { 5 \"i\" set (while) { 1 1 = } { (ifs) { \"i\" get 3 = } { (break) } { 1 1 = } { \"i\" get 1 - \"i\" set } } \"i\" get }
+count
+lib-fn
+";
+
+fn stack_of(code: &crate::Code) -> Result<Vec<Value>, Error> {
+    Ok(api::execute_raw_code(code)?.get_stack().to_vec())
+}
+
+#[test]
+fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tree = VirtualTree::new()
+        .with_file("main.stck", MAIN)
+        .with_file("lib.stck", "(fn) [] lib-fn { \"lib\" }\n");
+    let code = api::get_project_code("main.stck", &mut tree)?;
+    let bytes = encode(&code);
+    let decoded = decode(&bytes)?;
+    let reencoded = encode(&decoded);
+    test_eq!(got: reencoded, expected: bytes);
+    let expected = stack_of(&code)?;
+    let got = stack_of(&decoded)?;
+    test_eq!(got: got, expected: expected);
+    Ok(())
+}
+
+#[test]
+fn decoding_resolves_names() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tree = VirtualTree::new().with_file(
+        "main.stck",
+        "(fn) [ n ] double { n 2 * }\n[ x ] { x double } 3 @\n",
+    );
+    let code = api::get_project_code("main.stck", &mut tree)?;
+    let decoded = decode(&encode(&code))?;
+    // closures never compare equal, so the printed code is compared
+    let got = format!("{decoded:?}");
+    let expected = format!("{code:?}");
+    test_eq!(got: got, expected: expected);
+    assert!(got.contains("FnCall(\"n\", Arg(0))"), "{got}");
+    Ok(())
+}
+
+#[test]
+fn invalid_bytes() -> Result<(), Box<dyn std::error::Error>> {
+    let tokens = api::get_tokens_str("1 2 -\n", "invalid bytes", &mut crate::cache::NoCache)?;
+    let bytes = encode(&api::parse_raw_tokens(tokens)?);
+    let cut = decode(&bytes[..bytes.len() - 1]);
+    assert!(matches!(cut, Err(DecodeError::UnexpectedEnd)), "{cut:?}");
+    let not_code = decode(b"1 2 -\n");
+    assert!(
+        matches!(not_code, Err(DecodeError::NotPrecompiled)),
+        "{not_code:?}"
+    );
+    let mut newer = bytes.clone();
+    newer[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    let newer = decode(&newer);
+    assert!(
+        matches!(newer, Err(DecodeError::Format(u32::MAX))),
+        "{newer:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn deep_nesting_is_invalid() {
+    let tokens = api::get_tokens_str("1\n", "deep nesting", &mut crate::cache::NoCache).unwrap();
+    let bytes = encode(&api::parse_raw_tokens(tokens).unwrap());
+    // the immediate `1` is replaced by a million options inside each other
+    let (head, _) = bytes.split_at(bytes.len() - 9);
+    let mut deep = head.to_vec();
+    deep.extend([7, 1].repeat(1_000_000));
+    let deep = decode(&deep);
+    assert!(
+        matches!(deep, Err(DecodeError::Invalid("nesting depth"))),
+        "{deep:?}"
+    );
+}
+
+#[test]
+fn cache_follows_includes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("stck-precompiled-{}", std::process::id()));
+    let cache = CodeCache::new(&dir);
+    let mut tree = VirtualTree::new()
+        .with_file("main.stck", "(include lib.stck)\n1 double\n")
+        .with_file("lib.stck", "(fn) [ n ] double { n 2 * }\n");
+    assert!(cache.load("main.stck", &mut tree).is_none());
+    let code = cache.load_or_parse("main.stck", &mut tree)?;
+    let stack = stack_of(&code)?;
+    test_eq!(got: stack, expected: [Value::Num(2)]);
+    let loaded = cache
+        .load("main.stck", &mut tree)
+        .ok_or("script wasn't stored")?;
+    let stack = stack_of(&loaded)?;
+    test_eq!(got: stack, expected: [Value::Num(2)]);
+
+    // a change in an included file makes the script stale
+    tree.add_file("lib.stck", "(fn) [ n ] double { n 3 * }\n");
+    assert!(cache.load("main.stck", &mut tree).is_none());
+    let code = cache.load_or_parse("main.stck", &mut tree)?;
+    let stack = stack_of(&code)?;
+    test_eq!(got: stack, expected: [Value::Num(3)]);
+    assert!(cache.load("main.stck", &mut tree).is_some());
+    assert!(cache.load("lib.stck", &mut tree).is_none());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn cache_stored_from_many_threads() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("stck-precompiled-threads-{}", std::process::id()));
+    let cache = CodeCache::new(&dir);
+    let tree = VirtualTree::new()
+        .with_file("main.stck", MAIN)
+        .with_file("lib.stck", "(fn) [] lib-fn { \"lib\" }\n");
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            let (cache, mut tree) = (&cache, tree.clone());
+            scope.spawn(move || {
+                assert!(cache.load_or_parse("main.stck", &mut tree).is_ok());
+            });
+        }
+    });
+    let loaded = cache
+        .load("main.stck", &mut tree.clone())
+        .ok_or("script wasn't stored")?;
+    let expected = stack_of(&api::get_project_code("main.stck", &mut tree.clone())?)?;
+    let got = stack_of(&loaded)?;
+    test_eq!(got: got, expected: expected);
+    let leftovers = std::fs::read_dir(&dir)?.count();
+    test_eq!(got: leftovers, expected: 1);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
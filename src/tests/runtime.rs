@@ -64,3 +64,633 @@ fn closure_parent_args() -> Result<(), Error> {
     test_eq!(got: stack, expected: expected_stack);
     Ok(())
 }
+
+#[test]
+fn denied_capability() -> Result<(), Error> {
+    use crate::internals::{Capabilities, Capability};
+    let tokens = api::get_tokens_str(
+        "\"echo hi\" sh\n",
+        "test capabilities",
+        &mut CacheHelper::new(),
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+
+    let mut runtime = RuntimeContext::new();
+    runtime.execute_entire_code(&code)?;
+    test_eq!(got: runtime.get_stack(), expected: [Value::from(Ok(Value::Num(0)))]);
+
+    let policy = Capabilities::all().deny(Capability::Spawn);
+    let mut runtime = RuntimeContext::new().with_capabilities(policy);
+    let err = runtime.execute_entire_code(&code).err().map(|e| *e.kind);
+    assert!(matches!(
+        err,
+        Some(crate::RuntimeErrorKind::CapabilityDenied(Capability::Spawn))
+    ));
+    Ok(())
+}
+
+#[test]
+fn typed_hook() -> Result<(), Error> {
+    use crate::RuntimeErrorKind;
+    use std::sync::{Arc, Mutex};
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut runtime = RuntimeContext::new();
+    let seen_by_hook = Arc::clone(&seen);
+    runtime.add_typed_fn("record", move |n: isize, label: String| {
+        seen_by_hook.lock().unwrap().push(label);
+        (n * 2, n > 2)
+    });
+
+    let tokens = api::get_tokens_str("3 \"three\" record\n", "typed hook", &mut NoCache)?;
+    runtime.execute_entire_code(&api::parse_raw_tokens(tokens)?)?;
+    test_eq!(got: runtime.get_stack(), expected: [Value::Num(6), Value::Bool(true)]);
+    test_eq!(got: seen.lock().unwrap().as_slice(), expected: ["three".to_string()]);
+
+    runtime.stack.clear();
+    let tokens = api::get_tokens_str("\"three\" 3 record\n", "typed hook", &mut NoCache)?;
+    let err = runtime
+        .execute_entire_code(&api::parse_raw_tokens(tokens)?)
+        .err()
+        .map(|e| *e.kind);
+    assert!(matches!(
+        err,
+        Some(RuntimeErrorKind::WrongTypeForHook { this_arg: 0, .. })
+    ));
+
+    runtime.stack.clear();
+    let tokens = api::get_tokens_str("3 record\n", "typed hook", &mut NoCache)?;
+    let err = runtime
+        .execute_entire_code(&api::parse_raw_tokens(tokens)?)
+        .err()
+        .map(|e| *e.kind);
+    assert!(matches!(
+        err,
+        Some(RuntimeErrorKind::MissingValuesForHook { missing: 1, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn json_module() -> Result<(), Error> {
+    use crate::internals::module;
+    use std::collections::HashMap;
+    let mut runtime = RuntimeContext::new();
+    runtime.add_module(module::oficial::json_module()?);
+    let tokens = api::get_tokens_str("(require #json)\njson$parse\n", "json module", &mut NoCache)?;
+    let parse = api::parse_raw_tokens(tokens)?;
+
+    runtime.stack.push_this(
+        r#"{"name": "cfg\u00e9", "sizes": [1, -2.5e1, null], "on": true, "big": 99999999999999999999}"#
+            .to_string(),
+    );
+    runtime.execute_entire_code(&parse)?;
+    let expected = Value::from(HashMap::from([
+        ("name".to_string(), Value::from("cfg\u{e9}".to_string())),
+        (
+            "sizes".to_string(),
+            Value::from(vec![
+                Value::Num(1),
+                Value::Float(-25.0),
+                Value::Option(None),
+            ]),
+        ),
+        ("on".to_string(), Value::Bool(true)),
+        ("big".to_string(), Value::Float(1e20)),
+    ]));
+    let expected = [Value::from(Ok(expected))];
+    test_eq!(got: runtime.get_stack(), expected: expected);
+
+    runtime.stack.clear();
+    runtime.stack.push_this("[1, 2".to_string());
+    runtime.execute_entire_code(&parse)?;
+    let expected = Value::from("expected ',' or ']' in array at line 1 column 6".to_string());
+    let expected = [Value::from(Err(expected))];
+    test_eq!(got: runtime.get_stack(), expected: expected);
+
+    let tokens = api::get_tokens_str("json$stringify\n", "json module", &mut NoCache)?;
+    let stringify = api::parse_raw_tokens(tokens)?;
+    let value = Value::from(HashMap::from([
+        (
+            "b".to_string(),
+            Value::from(vec![Value::Num(1), Value::Float(2.0)]),
+        ),
+        ("a".to_string(), Value::from("say \"hi\"\n".to_string())),
+    ]));
+    for (pretty, expected) in [
+        (false, "{\"a\":\"say \\\"hi\\\"\\n\",\"b\":[1,2.0]}"),
+        (
+            true,
+            "{\n  \"a\": \"say \\\"hi\\\"\\n\",\n  \"b\": [\n    1,\n    2.0\n  ]\n}",
+        ),
+    ] {
+        runtime.stack.clear();
+        runtime.stack.push(value.clone());
+        runtime.stack.push_this(pretty);
+        runtime.execute_entire_code(&stringify)?;
+        test_eq!(
+            got: runtime.get_stack(),
+            expected: [Value::from(Ok(Value::from(expected.to_string())))]
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn debugger_steps() -> Result<(), Error> {
+    use crate::internals::{Debugger, Pause, PauseReason, Step};
+    use std::sync::{Arc, Mutex};
+    let tokens = api::get_tokens_str(
+        "(fn) [ a ] twice {\n\ta 2 *\n}\n3 twice\n1 twice\n",
+        "debugged.stck",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let pauses = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&pauses);
+    let mut steps = vec![
+        Step::Continue,
+        Step::Over,
+        Step::Out,
+        Step::Into,
+        Step::Into,
+    ];
+    let debugger = Debugger::new(move |pause: &Pause, _: &mut _| {
+        let args: Vec<_> = pause
+            .ctx
+            .get_args()
+            .into_iter()
+            .flatten()
+            .map(|(name, v)| format!("{name}={v}"))
+            .collect();
+        seen.lock().unwrap().push((
+            pause.reason,
+            pause.line(),
+            pause.depth,
+            pause.ctx.get_stack().len(),
+            args.join(" "),
+        ));
+        steps.pop().unwrap_or(Step::Continue)
+    })
+    .with_breakpoint("debugged.stck", 4);
+    let mut runtime = RuntimeContext::new().with_debugger(debugger);
+    runtime.execute_entire_code(&code)?;
+    test_eq!(got: runtime.get_stack(), expected: [Value::Num(6), Value::Num(2)]);
+    let expected = [
+        (PauseReason::Breakpoint, 4, 0, 0, String::new()),
+        (PauseReason::Step, 4, 0, 1, String::new()),
+        (PauseReason::Step, 2, 1, 0, "a=3".to_string()),
+        (PauseReason::Step, 5, 0, 1, String::new()),
+        (PauseReason::Step, 5, 0, 2, String::new()),
+    ];
+    test_eq!(got: pauses.lock().unwrap().as_slice(), expected: expected);
+    Ok(())
+}
+
+#[test]
+fn task_matches_entire_execution() -> Result<(), Error> {
+    use crate::internals::{Task, TaskState};
+    let tokens = api::get_tokens_str(
+        "
+(fn) [ n ] fat {
+    (ifs) { n 1 = } { 1 } { 0 0 = } { n 1 - fat n * }
+}
+(fn) [ n ] [ <num> ] name-len {
+    n (switch) 1 { 3 } 2 { 3 (return) 9 } { 0 }
+}
+0 \"i\" set
+(while) { 0 0 = } {
+    (ifs) { \"i\" get 5 = } { (break) }
+    \"i\" get -1 - \"i\" set
+}
+\"i\" get
+5 fat
+2 name-len
+[ a ] { a a * } 7 @
+",
+        "test task",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let mut runtime = RuntimeContext::new();
+    runtime.execute_entire_code(&code)?;
+
+    let mut task = Task::new(RuntimeContext::new(), &code);
+    let mut advances = 0;
+    let stack = loop {
+        advances += 1;
+        match task.advance(1)? {
+            TaskState::Pending => test_eq!(got: task.is_finished(), expected: false),
+            TaskState::Done(stack) => break stack,
+        }
+    };
+    test_eq!(got: stack.as_slice(), expected: runtime.get_stack());
+    test_eq!(got: advances, expected: runtime.executed_exprs() + 1);
+    assert!(task.is_finished());
+    assert!(matches!(task.advance(1), Ok(TaskState::Done(s)) if s.as_slice().is_empty()));
+    Ok(())
+}
+
+#[test]
+fn interleaved_tasks() -> Result<(), Error> {
+    use crate::internals::Task;
+    use std::sync::{Arc, Mutex};
+    let log = Arc::new(Mutex::new(String::new()));
+    let mut tasks = Vec::new();
+    for name in ["a", "b"] {
+        let tokens = api::get_tokens_str(
+            &format!("(fn) [] log {{ \"{name}\" record }}\nlog log log\n"),
+            "test interleaved tasks",
+            &mut NoCache,
+        )?;
+        let mut ctx = RuntimeContext::new();
+        let seen = Arc::clone(&log);
+        ctx.add_typed_fn("record", move |s: String| seen.lock().unwrap().push_str(&s));
+        tasks.push(Task::new(ctx, &api::parse_raw_tokens(tokens)?));
+    }
+    while !tasks.iter().all(Task::is_finished) {
+        for task in &mut tasks {
+            // "log" record
+            task.advance(3)?;
+        }
+    }
+    test_eq!(got: log.lock().unwrap().as_str(), expected: "ababab");
+    Ok(())
+}
+
+#[test]
+fn closure_hook_on_two_threads() -> Result<(), Error> {
+    use crate::internals::StckHook;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let hook = StckHook::from_closure(move |_, _| {
+        counted.fetch_add(1, Ordering::Relaxed);
+        // holds the hook long enough for the other thread to wait for it
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        Ok(())
+    });
+    let tokens = api::get_tokens_str(
+        "tick tick tick tick tick\n",
+        "test shared hook",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let results: Vec<_> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let (code, hook) = (&code, hook.clone());
+                s.spawn(move || {
+                    let mut ctx = RuntimeContext::new();
+                    ctx.add_hook("tick", hook);
+                    ctx.execute_entire_code(code).map(|_| ())
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    for result in results {
+        result?;
+    }
+    test_eq!(got: calls.load(Ordering::Relaxed), expected: 10);
+
+    // a hook that runs code calling itself is still an error
+    let mut ctx = RuntimeContext::new();
+    let inner = code.clone();
+    ctx.add_hook(
+        "tick",
+        StckHook::from_closure(move |ctx, _| {
+            ctx.execute_entire_code(&inner).map_err(|e| *e.kind)?;
+            Ok(())
+        }),
+    );
+    let err = ctx.execute_entire_code(&code).unwrap_err();
+    assert!(
+        matches!(*err.kind, crate::RuntimeErrorKind::ReentrantHook),
+        "{err:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn hook_requests_yield() -> Result<(), Error> {
+    use crate::internals::{Task, TaskState};
+    let tokens = api::get_tokens_str("1 (fn) [] f { wait 2 } f 3\n", "test yield", &mut NoCache)?;
+    let mut ctx = RuntimeContext::new();
+    ctx.add_hook(
+        "wait",
+        crate::internals::StckHook::WithError(|ctx, _| {
+            ctx.request_yield();
+            Ok(())
+        }),
+    );
+    let mut task = Task::new(ctx, &api::parse_raw_tokens(tokens)?);
+    assert!(matches!(task.advance(usize::MAX)?, TaskState::Pending));
+    test_eq!(got: task.context().get_stack(), expected: [Value::Num(1)]);
+    let done = task.advance(usize::MAX)?;
+    let expected = [Value::Num(1), Value::Num(2), Value::Num(3)];
+    assert!(matches!(done, TaskState::Done(stack) if stack.as_slice() == expected));
+    Ok(())
+}
+
+#[test]
+fn task_errors_keep_context() -> Result<(), Error> {
+    use crate::internals::Task;
+    let tokens = api::get_tokens_str(
+        "(fn) [] f {\n\t(ifs) { 0 0 = } { missing }\n}\nf\n",
+        "test task error",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let expected = RuntimeContext::new()
+        .execute_entire_code(&code)
+        .err()
+        .unwrap();
+    let mut task = Task::new(RuntimeContext::new(), &code);
+    let got = task.advance(usize::MAX).unwrap_err();
+    test_eq!(got: got.to_string(), expected: expected.to_string());
+    test_eq!(got: got.stack.len(), expected: 2);
+    assert!(task.is_finished());
+    Ok(())
+}
+
+#[test]
+fn task_paused_in_a_call_keeps_settings() -> Result<(), Error> {
+    use crate::internals::{Capabilities, Capability, Task, TaskState};
+    let tokens = api::get_tokens_str(
+        "(fn) [ a ] f { a 1 - a 2 - }\n5 f\n",
+        "test paused task",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let sandboxed = || RuntimeContext::new().with_capabilities(Capabilities::none());
+    let mut task = Task::new(sandboxed(), &code);
+    // stops inside the call to f
+    assert!(matches!(task.advance(4)?, TaskState::Pending));
+    assert!(!task.context().get_capabilities().allows(Capability::Spawn));
+    let TaskState::Done(stack) = task.advance(usize::MAX)? else {
+        panic!("task didn't finish");
+    };
+    test_eq!(got: stack.as_slice(), expected: [Value::Num(4), Value::Num(3)]);
+
+    let mut task = Task::new(sandboxed(), &code);
+    task.advance(4)?;
+    let ctx = task.into_context();
+    assert!(!ctx.get_capabilities().allows(Capability::Spawn));
+    Ok(())
+}
+
+#[test]
+fn shared_code_across_threads() -> Result<(), Error> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_send_sync::<crate::internals::Code>();
+    assert_send_sync::<crate::internals::Expr>();
+    assert_send_sync::<Value>();
+    assert_send::<RuntimeContext>();
+
+    let tokens = api::get_tokens_str(
+        "(fn) [ n ] [ <num> ] square { n n * }\n[ n ] { n square } input @\n",
+        "test threads",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let squares: Vec<_> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..8)
+            .map(|n| {
+                let code = &code;
+                s.spawn(move || {
+                    let mut ctx = RuntimeContext::new();
+                    ctx.add_typed_fn("input", move || n);
+                    ctx.execute_entire_code(code)
+                        .map(|_| ctx.get_stack().to_vec())
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    for (n, stack) in (0..8).zip(squares) {
+        let stack = stack.ok();
+        let expected = Some(vec![Value::Num(n * n)]);
+        test_eq!(got: stack, expected: expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn captured_output() -> Result<(), Error> {
+    use crate::internals::Capture;
+    let tokens = api::get_tokens_str(
+        "\"a\" print 1 debug$stack greet \"b\\n\" print\n",
+        "test output",
+        &mut NoCache,
+    )?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let (out, debug) = (Capture::new(), Capture::new());
+    let mut runtime = RuntimeContext::new()
+        .with_output(out.clone())
+        .with_debug_output(debug.clone());
+    runtime.add_hook(
+        "greet",
+        crate::internals::StckHook::WithError(|ctx, _| {
+            write!(ctx.output(), " hi ").map_err(crate::RuntimeErrorKind::Output)
+        }),
+    );
+    runtime.execute_entire_code(&code)?;
+    let printed = out.take();
+    test_eq!(got: printed, expected: "a hi b\n");
+    test_eq!(got: debug.contents(), expected: "Stack([Num(1)])\n");
+    test_eq!(got: out.contents(), expected: "");
+    Ok(())
+}
+
+#[test]
+fn host_calls() -> Result<(), Error> {
+    use crate::RuntimeErrorKind;
+    let mut runtime = execute_string(
+        "(fn) [ a<num> b<num> ] [ <num> ] sub { a b - }\n(fn) * count { stack$len }\n[ a b ] { a b - } \"on-event\" set\n",
+        "test host calls",
+    )?;
+    runtime.stack.push(Value::from("kept".to_string()));
+
+    let outputs = runtime.call_fn("sub", vec![Value::Num(5), Value::Num(2)])?;
+    test_eq!(got: outputs, expected: [Value::Num(3)]);
+    let outputs = runtime.call_fn("count", vec![Value::Num(1), Value::Num(2)])?;
+    test_eq!(got: outputs, expected: [Value::Num(1), Value::Num(2), Value::Num(2)]);
+
+    let Some(Value::Closure(handler)) = runtime.get_vars().get("on-event").cloned() else {
+        panic!("handler wasn't registered");
+    };
+    let outputs = runtime.call_closure((*handler).clone(), vec![Value::Num(7), Value::Num(3)])?;
+    test_eq!(got: outputs, expected: [Value::Num(4)]);
+    let partial = runtime.call_closure(*handler, vec![Value::Num(7)])?;
+    assert!(matches!(partial.as_slice(), [Value::Closure(_)]));
+
+    let errors: Vec<_> = [
+        runtime.call_fn("sub", vec![Value::Num(1), Value::from("2".to_string())]),
+        runtime.call_fn("sub", vec![Value::Num(1)]),
+        runtime.call_fn("sub", vec![Value::Num(1), Value::Num(2), Value::Num(3)]),
+        runtime.call_fn("print", vec![]),
+    ]
+    .into_iter()
+    .map(|res| res.err().map(|e| *e.kind))
+    .collect();
+    assert!(matches!(errors[0], Some(RuntimeErrorKind::Type(..))));
+    assert!(matches!(
+        errors[1],
+        Some(RuntimeErrorKind::UserFnMissingArgs { .. })
+    ));
+    assert!(matches!(
+        errors[2],
+        Some(RuntimeErrorKind::TooManyArgs {
+            expected: 2,
+            got: 3,
+            ..
+        })
+    ));
+    assert!(matches!(
+        errors[3],
+        Some(RuntimeErrorKind::MissingUserFunction(_))
+    ));
+    test_eq!(got: runtime.get_stack(), expected: [Value::from("kept".to_string())]);
+    Ok(())
+}
+
+#[test]
+fn control_flows() -> Result<(), Error> {
+    let ctx = execute_string(
+        "
+(fn) [ n ] classify {
+    (ifs) { n 0 = } { \"zero\" (return) } { (return) n 1 = } { \"one\" }
+    (while) { 0 0 = } {
+        (ifs) { n 2 = } { \"two\" (break) }
+        \"many\" (break)
+    }
+    \"end\"
+}
+(fn) [ r ] bubble { r (!) -1 - ok }
+(fn) [ x ] pick {
+    x (switch) 1 { \"a\" } 2 { \"b\" (return) \"c\" } { \"d\" }
+    \"!\"
+}
+0 classify 1 classify 2 classify 3 classify
+1 ok bubble \"bad\" err bubble
+1 pick 2 pick 3 pick
+(return)
+\"unreachable\"
+",
+        "test control flows",
+    )?;
+    let strs = |xs: &[&str]| {
+        xs.iter()
+            .map(|s| Value::from(s.to_string()))
+            .collect::<Vec<_>>()
+    };
+    let mut expected = strs(&["zero", "one", "many", "end", "two", "end", "many", "end"]);
+    expected.push(Value::Result(Box::new(Ok(Value::Num(2)))));
+    expected.push(Value::Result(Box::new(Err(Value::from("bad".to_string())))));
+    expected.extend(strs(&["a", "!", "b", "d", "!"]));
+    test_eq!(got: ctx.get_stack(), expected: expected.as_slice());
+    Ok(())
+}
+
+#[test]
+fn error_trace_of_nested_blocks() {
+    let Err(Error::RuntimeError(err)) = execute_string(
+        "(fn) [ n ] f {\n\t(ifs) { n 0 = } { missing } { 0 0 = } { n 1 - f }\n}\n(while) { 0 0 = } { 1 f }\n",
+        "test error trace",
+    ) else {
+        panic!("missing function was found");
+    };
+    let lines: Vec<_> = std::iter::once(&err.ctx)
+        .chain(err.get_call_stack())
+        .map(|ctx| ctx.lines.start)
+        .collect();
+    // missing, ifs, f, ifs, f, while
+    test_eq!(got: lines, expected: [2, 2, 2, 2, 4, 4]);
+}
+
+#[test]
+fn fn_scopes() -> Result<(), Error> {
+    let ctx = execute_string(
+        "
+(fn*) [] keep { \"global\" \"g\" set (fn) [] kept-fn {} }
+(fn) [] drop { \"local\" \"l\" set \"g\" get (fn) [] inner { \"inner\" } inner }
+(fn-) [] isolated { \"isolated\" \"i\" set }
+keep drop isolated
+(fn) [] inner { \"outer\" }
+inner
+",
+        "test fn scopes",
+    )?;
+    let vars: Vec<_> = ctx.get_vars().keys().map(String::as_str).collect();
+    test_eq!(got: vars, expected: ["g"]);
+    let mut fns: Vec<_> = ctx
+        .get_fns()
+        .map(|(name, _)| name)
+        .filter(|name| ["keep", "drop", "isolated", "inner", "kept-fn"].contains(name))
+        .collect();
+    fns.sort_unstable();
+    test_eq!(got: fns, expected: ["drop", "inner", "isolated", "keep"]);
+    let stack: Vec<_> = ["global", "inner", "outer"]
+        .map(|s| Value::from(s.to_string()))
+        .into();
+    test_eq!(got: ctx.get_stack(), expected: stack.as_slice());
+    Ok(())
+}
+
+#[test]
+fn shared_values_copy_on_write() -> Result<(), Error> {
+    let ctx = execute_string(
+        "
+(fn) [xs] push-one { 1 xs arr$append }
+2 arr$new arr$append \"kept\" set
+\"kept\" get push-one
+\"kept\" get
+map$new \"k\" 1 map$insert-kv \"m\" set
+\"m\" get \"k\" 2 map$insert-kv
+\"m\" get
+",
+        "test copy on write",
+    )?;
+    let map = |n| {
+        Value::from(std::collections::HashMap::from([(
+            "k".to_string(),
+            Value::Num(n),
+        )]))
+    };
+    let expected = [
+        Value::from(vec![Value::Num(2), Value::Num(1)]),
+        Value::from(vec![Value::Num(2)]),
+        map(2),
+        map(1),
+    ];
+    test_eq!(got: ctx.get_stack(), expected: expected);
+    Ok(())
+}
+
+#[test]
+fn resolved_names() -> Result<(), Error> {
+    let ctx = execute_string(
+        "
+(fn) [] two { 2 }
+(fn) [ two x ] f { [ x ] { x two } 9 @ x }
+5 7 f two
+",
+        "test resolved names",
+    )?;
+    let expected = [9, 5, 7, 2].map(Value::Num);
+    test_eq!(got: ctx.get_stack(), expected: expected);
+
+    // a function of the code that was never defined falls back to the hook of its name
+    let cont = "(while) { 1 2 = } { (fn) [] maybe { 1 } }\n(fn) [] g { maybe }\ng\n";
+    let tokens = api::get_tokens_str(cont, "test hook fallback", &mut NoCache)?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let mut runtime = RuntimeContext::new();
+    runtime.add_rust_hook(RustStckFn::new("maybe".to_string(), |ctx, _| {
+        ctx.stack.push(Value::Num(3));
+    }));
+    runtime.execute_entire_code(&code)?;
+    test_eq!(got: runtime.get_stack(), expected: [Value::Num(3)]);
+    Ok(())
+}
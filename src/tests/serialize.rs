@@ -0,0 +1,91 @@
+use super::*;
+use crate::{
+    api,
+    cache::Isolated,
+    from_value,
+    internals::{RuntimeContext, Value},
+    to_value,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rect { w: isize, h: isize },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Drawing {
+    name: String,
+    layer: Option<u8>,
+    shapes: Vec<Shape>,
+    done: Result<bool, String>,
+}
+
+#[test]
+fn rust_to_value() {
+    let drawing = Drawing {
+        name: "sketch".to_string(),
+        layer: None,
+        shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+        done: Ok(true),
+    };
+    let rect = HashMap::from([
+        ("w".to_string(), Value::Num(2)),
+        ("h".to_string(), Value::Num(3)),
+    ]);
+    let expected = Value::from(HashMap::from([
+        ("name".to_string(), Value::from("sketch".to_string())),
+        ("layer".to_string(), Value::Option(None)),
+        (
+            "shapes".to_string(),
+            Value::from(vec![
+                Value::from("Point".to_string()),
+                Value::from(HashMap::from([("Circle".to_string(), Value::Float(1.5))])),
+                Value::from(HashMap::from([("Rect".to_string(), Value::from(rect))])),
+            ]),
+        ),
+        ("done".to_string(), Value::from(Ok(Value::Bool(true)))),
+    ]));
+    let got = to_value(&drawing).unwrap();
+    test_eq!(got: got, expected: expected);
+    let back: Drawing = from_value(got).unwrap();
+    test_eq!(got: back, expected: drawing);
+}
+
+#[test]
+fn stack_round_trip() {
+    let tokens = api::get_tokens_str(
+        "arr$pack-n map$insert-kv\n",
+        "serde stack test",
+        &mut Isolated::new(),
+    )
+    .unwrap();
+    let code = api::parse_raw_tokens(tokens).unwrap();
+    let mut ctx = RuntimeContext::new();
+    ctx.stack
+        .push(to_value(&HashMap::<String, u8>::new()).unwrap());
+    ctx.stack.push(to_value("xs").unwrap());
+    ctx.stack.push(to_value(&4).unwrap());
+    ctx.stack.push(to_value(&5).unwrap());
+    ctx.stack.push(to_value(&2).unwrap());
+    ctx.execute_entire_code(&code).unwrap();
+    let out: Vec<HashMap<String, Vec<usize>>> =
+        from_value(Value::from(ctx.get_stack().to_vec())).unwrap();
+    test_eq!(got: out, expected: [HashMap::from([("xs".to_string(), vec![4, 5])])]);
+}
+
+#[test]
+fn closures_dont_serialize() {
+    let tokens =
+        api::get_tokens_str("[ x ] { x }\n", "serde closure test", &mut Isolated::new()).unwrap();
+    let code = api::parse_raw_tokens(tokens).unwrap();
+    let mut ctx = RuntimeContext::new();
+    ctx.execute_entire_code(&code).unwrap();
+    let closure = ctx.get_stack()[0].clone();
+    let err = to_value(&closure).unwrap_err();
+    test_eq!(got: err.to_string(), expected: "Closures can't be serialized");
+    assert!(from_value::<isize>(closure).is_err());
+}
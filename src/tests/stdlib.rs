@@ -0,0 +1,59 @@
+use super::*;
+use crate::{
+    api,
+    cache::{FileCacher, Isolated},
+    error::{Error, LineRange, StckError},
+    internals::{RuntimeContext, Value},
+};
+use std::path::Path;
+
+fn execute_isolated(cont: &str) -> Result<RuntimeContext, Error> {
+    let tokens = api::get_tokens_str(cont, "stdlib test", &mut Isolated::new())?;
+    let code = api::parse_raw_tokens(tokens)?;
+    let mut runtime = RuntimeContext::new();
+    runtime.execute_entire_code(&code)?;
+    Ok(runtime)
+}
+
+#[test]
+fn whole_stdlib() -> Result<(), Error> {
+    let ctx = execute_isolated("(include <std>)\n(include <std>)\n2 3 + 4 dup\n")?;
+    test_eq!(got: ctx.get_stack(), expected: [Value::Num(5), Value::Num(4), Value::Num(4)]);
+    Ok(())
+}
+
+#[test]
+fn single_module_with_its_dependencies() -> Result<(), Error> {
+    // math uses `drop` from the stack module
+    let ctx = execute_isolated("(include <std/math>)\n(include <std/arr>)\n1 2 ++ drop\n")?;
+    test_eq!(got: ctx.get_stack(), expected: [Value::Num(1)]);
+    for module in crate::stdlib::modules() {
+        execute_isolated(&format!("(include <std/{module}>)\n"))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn unknown_module() {
+    let Err(Error::Anoter(err)) = execute_isolated("(include <std/nope>)\n") else {
+        panic!("included a missing module");
+    };
+    let expected_path = Path::new("<std>/nope.stck").to_path_buf();
+    assert!(matches!(err.unspanned(), StckError::CantReadFile(p) if *p == expected_path));
+    test_eq!(got: err.span().map(|(file, _)| file), expected: Some(Path::new("stdlib test")));
+}
+
+#[test]
+fn ignored_include_is_not_read() -> Result<(), Error> {
+    let ctx =
+        execute_isolated("(pragma if MISSING)\n(include missing.stck)\n(pragma end if)\n1\n")?;
+    test_eq!(got: ctx.get_stack(), expected: [Value::Num(1)]);
+    Ok(())
+}
+
+#[test]
+fn spans_of_stdlib_files() -> Result<(), std::io::Error> {
+    let lines = Isolated::new().get_span("<std>/stck.stck", &LineRange::from_points(1, 1))?;
+    test_eq!(got: lines, expected: "(pragma if not STDLIB_STCK)");
+    Ok(())
+}
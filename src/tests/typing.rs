@@ -11,7 +11,7 @@ fn test_simple_types() -> Result<(), crate::error::RuntimeErrorKind> {
     let mut trc: TypeResolutionContext = TypeResolutionBuilder::new().into();
     let closure_sum = Value::Closure(Box::new(crate::Closure {
         trc: trc.clone(),
-        code: vec![],
+        code: vec![].into(),
         request_args: ClosurePartialArgs::convert(
             vec![
                 FnArgDef::new("a".to_string(), Some(TT::Num)),
@@ -27,12 +27,12 @@ fn test_simple_types() -> Result<(), crate::error::RuntimeErrorKind> {
 
     let values = [
         Value::Num(0),
-        Value::Str(String::new()),
-        Value::Array(vec![Value::Num(0), Value::Str(String::new())]),
+        Value::from(String::new()),
+        Value::from(vec![Value::Num(0), Value::from(String::new())]),
         closure_sum,
         Value::Option(Some(Box::new(Value::Num(0)))),
         Value::Result(Box::new(Ok(Value::Num(0)))),
-        Value::Map(HashMap::new()),
+        Value::from(HashMap::new()),
         Value::Char('a'),
         Value::Bool(false),
     ];
@@ -66,7 +66,7 @@ fn test_simple_types() -> Result<(), crate::error::RuntimeErrorKind> {
 fn test_array_type() {
     let mut trc: TypeResolutionContext = TypeResolutionBuilder::new().into();
     let arr_of_num_type = TT::Array(Box::new(TT::Num));
-    let arr_or_num = Value::Array(vec![Value::Num(3), Value::Num(0)]);
+    let arr_or_num = Value::from(vec![Value::Num(3), Value::Num(0)]);
     let type_test = trc.check(&arr_of_num_type, &arr_or_num);
 
     test_eq!(got: type_test, expected: T_OK);
@@ -81,7 +81,7 @@ fn test_closure_type() -> Result<(), crate::error::RuntimeErrorKind> {
     );
     let closure_sum = Value::Closure(Box::new(Closure {
         trc: trc.clone(),
-        code: vec![],
+        code: vec![].into(),
         request_args: ClosurePartialArgs::convert(
             vec![
                 FnArgDef::new("a".to_string(), Some(TT::Num)),
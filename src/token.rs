@@ -13,6 +13,8 @@ pub struct Context {
     current_line: usize,
     point: usize,
     chars: Vec<char>,
+    keep_layout: bool,
+    token_start: usize,
 }
 
 #[derive(Debug)]
@@ -69,14 +71,36 @@ macro_rules! matches {
 impl Context {
     fn push_token(&mut self, out: &mut Vec<Token>, token: TokenCont) {
         let span = LineRange::from_points(self.current_line, self.current_line);
+        let token = self.layout_token(token);
         out.push(Token { cont: token, span });
     }
     fn push_multiline_token(&mut self, out: &mut Vec<Token>, token: TokenCont, line_start: usize) {
         let span = LineRange::from_points(line_start, self.current_line);
+        let token = self.layout_token(token);
         out.push(Token { cont: token, span });
     }
+    // tokens that can't be written back exactly as they were read keep their source text
+    fn layout_token(&self, token: TokenCont) -> TokenCont {
+        use TokenCont::*;
+        match token {
+            Str(_) | Char(_) | Keyword(_) | FnArgs(_) if self.keep_layout => {
+                Verbatim(self.chars[self.token_start..self.point].iter().collect())
+            }
+            token => token,
+        }
+    }
+    fn push_comment(&mut self, out: &mut Vec<Token>) {
+        if self.keep_layout {
+            let end = self.point - usize::from(self.chars.get(self.point - 1) == Some(&'\n'));
+            let comment = self.chars[self.token_start + 1..end].iter().collect();
+            self.push_token(out, TokenCont::Comment(comment));
+        }
+    }
     pub fn tokenize(mut self, source: PathBuf) -> Result<TokenBlock> {
-        let tokens = self.tokenize_block()?;
+        let tokens = self.tokenize_block().map_err(|e| {
+            let line = LineRange::from_points(self.current_line, self.current_line);
+            e.spanned(&source, line)
+        })?;
         Ok(TokenBlock { source, tokens })
     }
 
@@ -87,7 +111,11 @@ impl Context {
         let mut state = Nothing;
         let mut out = Vec::new();
 
-        while let Some(ch) = self.next() {
+        while let Some(&ch) = self.next() {
+            if let Nothing = state {
+                self.token_start = self.point - 1;
+            }
+            let ch = &ch;
             state = match (state, ch) {
                 // code block
                 (Nothing, '{') => {
@@ -363,7 +391,10 @@ impl Context {
 
                 // comment
                 (Nothing, '#') => OnComment,
-                (OnComment, '\n') => Nothing,
+                (OnComment, '\n') => {
+                    self.push_comment(&mut out);
+                    Nothing
+                }
                 (OnComment, _) => OnComment,
                 (Nothing, matches!(space)) => Nothing,
 
@@ -374,7 +405,8 @@ impl Context {
         }
         if self.at_eof() {
             match state {
-                Nothing | OnComment => {}
+                Nothing => {}
+                OnComment => self.push_comment(&mut out),
                 MakeIdent(s) => {
                     self.push_token(&mut out, Ident(s));
                 }
@@ -423,6 +455,19 @@ impl Context {
             point: 0,
             chars,
             current_line: 1,
+            keep_layout: false,
+            token_start: 0,
         }
     }
+
+    /// # Keep comments and the source text of tokens
+    ///
+    /// Strings, chars, keywords and argument lists become [`TokenCont::Verbatim`], and comments
+    /// become [`TokenCont::Comment`], which the parser doesn't accept. Used by the
+    /// [formatter](crate::format)
+    #[must_use]
+    pub fn keep_layout(mut self) -> Self {
+        self.keep_layout = true;
+        self
+    }
 }
@@ -2,6 +2,7 @@ use super::*;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TypedFnPart {
@@ -69,13 +70,14 @@ impl FromStr for DefinedGenericBuilder {
 /// # Storage for defined generic types
 #[derive(Debug, Default, Clone)]
 pub(crate) struct TypeResolutionBuilder {
-    defined: HashMap<String, DefinedGeneric>,
+    // shared by every frame, since generics are rarely defined
+    defined: Arc<HashMap<String, DefinedGeneric>>,
 }
 
 impl TypeResolutionBuilder {
     pub fn new() -> Self {
         Self {
-            defined: HashMap::new(),
+            defined: Arc::default(),
         }
     }
     /// # Store a [defined generic](DefinedGenericBuilder)
@@ -83,7 +85,7 @@ impl TypeResolutionBuilder {
         &mut self,
         DefinedGenericBuilder { name, viral, allow }: DefinedGenericBuilder,
     ) {
-        self.defined.insert(name, DefinedGeneric::new(viral, allow));
+        Arc::make_mut(&mut self.defined).insert(name, DefinedGeneric::new(viral, allow));
     }
 }
 
@@ -95,7 +97,7 @@ impl TypeResolutionBuilder {
 /// `pre-processor`, which can be used to allow multiple types independently
 #[derive(Clone, Debug)]
 pub struct TypeResolutionContext {
-    defined: HashMap<String, DefinedGeneric>,
+    defined: Arc<HashMap<String, DefinedGeneric>>,
     current: HashMap<String, TypeTester>,
 }
 
@@ -465,7 +467,7 @@ impl TypeTester {
 
 #[derive(Clone, Debug)]
 pub struct TypedOutputs {
-    outputs: Vec<Option<TypeTester>>,
+    pub(crate) outputs: Vec<Option<TypeTester>>,
 }
 
 pub enum TypedOutputError {
@@ -0,0 +1,260 @@
+//! # Derive macro for converting rust types to and from stck values
+//!
+//! Used through the `derive` feature of `stck`, see `stck::convert::StckValue`
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Fields, Ident, parse_macro_input, parse_quote};
+
+/// # Implement `FromValue` and `IntoValue`
+///
+/// - Structs with named fields become maps from field names to their values. Missing
+///   `Option` fields are read as `None`
+/// - Tuple structs with a single field are the value of that field, and other tuple structs
+///   become arrays
+/// - Enums become maps where the `tag` key holds the name of the variant, next to the
+///   fields of the variant. Fields of tuple variants are keyed by their index
+///
+/// Every field must implement both conversions
+#[proc_macro_derive(StckValue)]
+pub fn derive_stck_value(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let (type_tester, from_value, into_value) = match &input.data {
+        Data::Struct(data) => (
+            struct_type_tester(&data.fields),
+            struct_from_value(&data.fields),
+            struct_into_value(&data.fields),
+        ),
+        Data::Enum(data) => {
+            let variants: Vec<_> = data
+                .variants
+                .iter()
+                .map(|v| (&v.ident, &v.fields))
+                .collect();
+            (
+                quote!(::stck::internals::TypeTester::MapAny),
+                enum_from_value(&variants),
+                enum_into_value(&variants),
+            )
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "StckValue can't be derived for unions",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let mut from_generics = input.generics.clone();
+    let mut into_generics = input.generics.clone();
+    for param in input.generics.type_params() {
+        let ident = &param.ident;
+        from_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#ident: ::stck::convert::FromValue));
+        into_generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#ident: ::stck::convert::IntoValue));
+    }
+    let (impl_generics, ty_generics, from_where) = from_generics.split_for_impl();
+    let (_, _, into_where) = into_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::stck::convert::FromValue for #name #ty_generics #from_where {
+            fn type_tester() -> ::stck::internals::TypeTester {
+                #type_tester
+            }
+            fn from_value(
+                value: ::stck::internals::Value,
+            ) -> ::std::result::Result<Self, ::stck::convert::FromValueError> {
+                #from_value
+            }
+        }
+
+        impl #impl_generics ::stck::convert::IntoValue for #name #ty_generics #into_where {
+            fn into_value(self) -> ::stck::internals::Value {
+                #into_value
+            }
+        }
+    })
+}
+
+fn struct_type_tester(fields: &Fields) -> TokenStream {
+    match fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            let ty = &fields.unnamed[0].ty;
+            quote!(<#ty as ::stck::convert::FromValue>::type_tester())
+        }
+        Fields::Unnamed(_) => quote!(::stck::internals::TypeTester::ArrayAny),
+        Fields::Named(_) | Fields::Unit => quote!(::stck::internals::TypeTester::MapAny),
+    }
+}
+
+fn struct_from_value(fields: &Fields) -> TokenStream {
+    match fields {
+        Fields::Unit => quote! {
+            ::stck::convert::derive::map(value)?;
+            ::std::result::Result::Ok(Self)
+        },
+        Fields::Named(_) => {
+            let build = build_from_map(quote!(Self), fields);
+            quote! {
+                let mut map = ::stck::convert::derive::map(value)?;
+                ::std::result::Result::Ok(#build)
+            }
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+            ::stck::convert::FromValue::from_value(value).map(Self)
+        },
+        Fields::Unnamed(fields) => {
+            let len = fields.unnamed.len();
+            let items =
+                (0..len).map(|idx| quote!(::stck::convert::derive::item(&mut items, #idx)?));
+            quote! {
+                let mut items = ::stck::convert::derive::array(value, #len)?.into_iter();
+                ::std::result::Result::Ok(Self(#(#items),*))
+            }
+        }
+    }
+}
+
+fn struct_into_value(fields: &Fields) -> TokenStream {
+    match fields {
+        Fields::Unit => quote! {
+            ::stck::internals::Value::from(::std::collections::HashMap::new())
+        },
+        Fields::Named(_) => {
+            let (pattern, inserts) = destructure(quote!(Self), fields);
+            quote! {
+                let #pattern = self;
+                let mut map = ::std::collections::HashMap::new();
+                #(#inserts)*
+                ::stck::internals::Value::from(map)
+            }
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => quote! {
+            ::stck::convert::IntoValue::into_value(self.0)
+        },
+        Fields::Unnamed(fields) => {
+            let items = (0..fields.unnamed.len()).map(syn::Index::from);
+            quote! {
+                ::stck::internals::Value::from(::std::vec![
+                    #(::stck::convert::IntoValue::into_value(self.#items)),*
+                ])
+            }
+        }
+    }
+}
+
+fn enum_from_value(variants: &[(&Ident, &Fields)]) -> TokenStream {
+    let arms = variants.iter().map(|(ident, fields)| {
+        let tag = ident.unraw().to_string();
+        let build = build_from_map(quote!(Self::#ident), fields);
+        quote!(#tag => #build,)
+    });
+    quote! {
+        let mut map = ::stck::convert::derive::map(value)?;
+        let tag: ::std::string::String =
+            ::stck::convert::derive::field(&mut map, ::stck::convert::derive::TAG)?;
+        ::std::result::Result::Ok(match tag.as_str() {
+            #(#arms)*
+            _ => return ::std::result::Result::Err(::stck::convert::derive::unknown_variant(tag)),
+        })
+    }
+}
+
+fn enum_into_value(variants: &[(&Ident, &Fields)]) -> TokenStream {
+    let arms = variants.iter().map(|(ident, fields)| {
+        let tag = ident.unraw().to_string();
+        let (pattern, inserts) = destructure(quote!(Self::#ident), fields);
+        quote! {
+            #pattern => {
+                map.insert(
+                    ::std::string::String::from(::stck::convert::derive::TAG),
+                    ::stck::internals::Value::from(::std::string::String::from(#tag)),
+                );
+                #(#inserts)*
+            }
+        }
+    });
+    quote! {
+        let mut map = ::std::collections::HashMap::new();
+        match self {
+            #(#arms)*
+        }
+        ::stck::internals::Value::from(map)
+    }
+}
+
+/// # Build the struct or variant from the fields in `map`
+fn build_from_map(path: TokenStream, fields: &Fields) -> TokenStream {
+    match fields {
+        Fields::Named(fields) => {
+            let fields = fields.named.iter().map(|f| {
+                let ident = f.ident.as_ref().expect("named field");
+                let key = ident.unraw().to_string();
+                quote!(#ident: ::stck::convert::derive::field(&mut map, #key)?)
+            });
+            quote!(#path { #(#fields),* })
+        }
+        Fields::Unnamed(fields) => {
+            let fields = (0..fields.unnamed.len()).map(|idx| {
+                let key = idx.to_string();
+                quote!(::stck::convert::derive::field(&mut map, #key)?)
+            });
+            quote!(#path(#(#fields),*))
+        }
+        Fields::Unit => path,
+    }
+}
+
+/// # Pattern binding every field, and the statements inserting them into `map`
+fn destructure(path: TokenStream, fields: &Fields) -> (TokenStream, Vec<TokenStream>) {
+    // bindings get a prefix, so they can't shadow `map`
+    let bindings: Vec<(Ident, String)> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|f| {
+                let key = f.ident.as_ref().expect("named field").unraw().to_string();
+                (format_ident!("field_{key}"), key)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|idx| (format_ident!("field_{idx}"), idx.to_string()))
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+    let idents = bindings.iter().map(|(ident, _)| ident);
+    let pattern = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#names: #idents),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#idents),*)),
+        Fields::Unit => path,
+    };
+    let inserts = bindings
+        .iter()
+        .map(|(ident, key)| {
+            quote! {
+                map.insert(
+                    ::std::string::String::from(#key),
+                    ::stck::convert::IntoValue::into_value(#ident),
+                );
+            }
+        })
+        .collect();
+    (pattern, inserts)
+}
@@ -1,4 +1,5 @@
 use stck::error::RuntimeErrorCtx;
+use stck::precompiled::CodeCache;
 use stck::prelude::*;
 use std::collections::HashSet;
 use std::fs::DirEntry;
@@ -27,15 +28,23 @@ fn parse_date(cont: &str) -> Result<Date, SError> {
     Ok(Date { day, month, year })
 }
 
-fn parse_file(dir_path: &Path, f: &DirEntry, cache: &mut CacheHelper) -> Result<Event, SError> {
+fn parse_file(
+    dir_path: &Path,
+    f: &DirEntry,
+    cache: &mut CacheHelper,
+    code_cache: Option<&CodeCache>,
+) -> Result<Event, SError> {
     let file_name = f.file_name();
     let name = file_name.to_string_lossy();
     let name = match name.strip_suffix(".stck") {
         Some(a) => a.to_string(),
         None => name.to_string(),
     };
-    let tokens = get_tokens(dir_path.join(file_name), cache)?;
-    let test = api::parse_raw_tokens(tokens)?;
+    let path = dir_path.join(file_name);
+    let test = match code_cache {
+        Some(code_cache) => code_cache.load_or_parse(path, cache)?,
+        None => api::get_project_code(path, cache)?,
+    };
     Ok(Event {
         name: name.to_string(),
         test,
@@ -76,9 +85,11 @@ fn execute() -> Result<(), SError> {
         .skip(1)
         .map(|s| parse_date(&s))
         .collect::<Result<_, _>>()?;
+    // parsed events are kept precompiled when a directory is given
+    let code_cache = std::env::var_os("CODE_CACHE_DIR").map(CodeCache::new);
     let mut cacher = CacheHelper::new();
     let events: Vec<_> = events_dir
-        .map(|e| parse_file(&events_dir_name, &e?, &mut cacher))
+        .map(|e| parse_file(&events_dir_name, &e?, &mut cacher, code_cache.as_ref()))
         .collect::<Result<_, _>>()?;
 
     let mut events_to_show: HashSet<String> = HashSet::new();
@@ -0,0 +1,155 @@
+use colored::Colorize;
+use stck::cache::FileCacher;
+use stck::internals::{Breakpoints, Debugger, Pause, PauseReason, Step};
+use stck::prelude::*;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PROMPT: &str = "debug> ";
+
+const HELP: &str = "\
+s, step         step into the next expression
+n, next         step over function and closure calls
+o, out          run until the current function returns
+c, continue     run until the next breakpoint
+st, stack       show the stack
+a, args         show the arguments of the current function
+v, vars         show the variables
+b [file:]line   add a breakpoint, on the current file by default
+d [file:]line   delete a breakpoint
+bl              list breakpoints
+h, help         show this message
+q, quit         stop the script";
+
+/// # Debugger driven from stdin
+///
+/// Pauses before the first expression, empty lines repeat the last step
+pub fn debugger() -> Debugger {
+    let mut cacher = CacheHelper::new();
+    let mut last_step = Step::Into;
+    Debugger::new(move |pause: &Pause, breakpoints: &mut Breakpoints| {
+        show_location(pause, &mut cacher);
+        loop {
+            match read_command() {
+                Ok(Some(line)) => {
+                    if let Some(step) = run_command(line.trim(), last_step, pause, breakpoints) {
+                        last_step = step;
+                        return step;
+                    }
+                }
+                Ok(None) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("Can't read debugger command: {e}");
+                    return Step::Continue;
+                }
+            }
+        }
+    })
+    .pause_on_start()
+}
+
+fn show_location(pause: &Pause, cacher: &mut CacheHelper) {
+    let location = format!("{}:{}", pause.source.display(), pause.line());
+    let reason = match pause.reason {
+        PauseReason::Breakpoint => "breakpoint at ",
+        PauseReason::Step => "",
+    };
+    println!(
+        "{reason}{} {}",
+        location.bright_blue(),
+        format!("(depth {})", pause.depth).dimmed()
+    );
+    if let Ok(span) = cacher.get_span(pause.source, &pause.expr.span) {
+        let line = span.lines().next().unwrap_or_default();
+        println!("{} {}", "|".dimmed(), line.trim());
+    }
+    println!(
+        "{} {}",
+        "->".dimmed(),
+        pause.expr.cont.to_string().bright_yellow()
+    );
+}
+
+fn read_command() -> std::io::Result<Option<String>> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{PROMPT}")?;
+    stdout.flush()?;
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+fn parse_location(arg: &str, current: &Path) -> Option<(PathBuf, usize)> {
+    match arg.rsplit_once(':') {
+        Some((file, line)) => Some((PathBuf::from(file), line.parse().ok()?)),
+        None => Some((current.to_path_buf(), arg.parse().ok()?)),
+    }
+}
+
+fn run_command(
+    line: &str,
+    last_step: Step,
+    pause: &Pause,
+    breakpoints: &mut Breakpoints,
+) -> Option<Step> {
+    let (cmd, arg) = line.split_once(' ').unwrap_or((line, ""));
+    let arg = arg.trim();
+    match cmd {
+        "" => return Some(last_step),
+        "s" | "step" => return Some(Step::Into),
+        "n" | "next" => return Some(Step::Over),
+        "o" | "out" => return Some(Step::Out),
+        "c" | "continue" => return Some(Step::Continue),
+        "st" | "stack" => {
+            let stack = pause.ctx.get_stack();
+            if stack.is_empty() {
+                println!("{}", "<empty stack>".dimmed());
+            }
+            for (idx, v) in stack.iter().enumerate() {
+                println!("{} {v}", format!("[{idx}]").dimmed());
+            }
+        }
+        "a" | "args" => match pause.ctx.get_args() {
+            Some(args) => {
+                let mut args: Vec<_> = args.collect();
+                args.sort_by_key(|(name, _)| *name);
+                for (name, value) in args {
+                    println!("{name} = {value}");
+                }
+            }
+            None => println!("{}", "<no arguments>".dimmed()),
+        },
+        "v" | "vars" => {
+            let mut vars: Vec<_> = pause.ctx.get_vars().iter().collect();
+            vars.sort_by_key(|(name, _)| *name);
+            for (name, value) in vars {
+                println!("{name} = {value}");
+            }
+        }
+        "b" | "d" => match parse_location(arg, pause.source) {
+            Some((file, line)) if cmd == "b" => {
+                if !breakpoints.add(file, line) {
+                    println!("Breakpoint already set");
+                }
+            }
+            Some((file, line)) => {
+                if !breakpoints.remove(file, line) {
+                    println!("No such breakpoint");
+                }
+            }
+            None => eprintln!("Expected `[file:]line`, got `{arg}`"),
+        },
+        "bl" => {
+            for (file, line) in breakpoints.iter() {
+                println!("{}:{line}", file.display());
+            }
+        }
+        "h" | "help" => println!("{HELP}"),
+        "q" | "quit" => std::process::exit(0),
+        other => eprintln!("Unknown command `{other}`, try help"),
+    }
+    None
+}
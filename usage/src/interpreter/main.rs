@@ -1,14 +1,131 @@
+mod debug;
+mod repl;
+
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use stck::cache::TrackingCache;
 use stck::internals::module;
 use stck::prelude::*;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[command(about = "Execute stck scripts or start a REPL")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Script to execute, starts the REPL if missing
+    file: Option<PathBuf>,
+    /// Start the REPL after executing the script
+    #[arg(short, long)]
+    interactive: bool,
+    /// Pause before the first expression and debug the script interactively
+    #[arg(short, long)]
+    debug: bool,
+    /// Report likely stack errors without executing the script
+    #[arg(short, long)]
+    check: bool,
+    /// Execute the script again whenever it or an included file changes
+    #[arg(short, long, requires = "file", conflicts_with_all = ["interactive", "check"])]
+    watch: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Format scripts in place
+    Fmt {
+        files: Vec<PathBuf>,
+        /// Don't write the files, fail if any of them isn't formatted
+        #[arg(long)]
+        check: bool,
+    },
+}
 
 fn main() -> Result<(), stck::Error> {
-    let file_path = std::env::args().nth(1).unwrap();
-    let mut file_cacher = CacheHelper::new();
-    let mut exec_ctx = RuntimeContext::new();
-    exec_ctx.add_module(module::oficial::io_module()?);
-    let code = get_project_code(file_path, &mut file_cacher)?;
-    if let Err(e) = exec_ctx.execute_entire_code(&code) {
-        println!("{e}");
+    let cli = Cli::parse();
+    if let Some(Command::Fmt { files, check }) = &cli.command {
+        if !format_files(files, *check)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(file_path) = &cli.file
+        && cli.watch
+    {
+        return watch(file_path, cli.debug);
+    }
+    let mut exec_ctx = new_context(cli.debug)?;
+    if let Some(file_path) = &cli.file {
+        let mut file_cacher = CacheHelper::new();
+        let code = get_project_code(file_path, &mut file_cacher)?;
+        if cli.check {
+            for diagnostic in stck::check::check(&code) {
+                println!("{diagnostic}");
+            }
+            return Ok(());
+        }
+        if let Err(e) = exec_ctx.execute_entire_code(&code) {
+            write!(exec_ctx.debug_output(), "{e}").map_err(stck::error::StckError::from)?;
+        }
+    }
+    if cli.file.is_none() || cli.interactive {
+        repl::run(exec_ctx).map_err(stck::error::StckError::from)?;
     }
     Ok(())
 }
+
+fn new_context(debug: bool) -> Result<RuntimeContext, stck::Error> {
+    let mut exec_ctx = RuntimeContext::new()
+        .with_output(std::io::stdout())
+        .with_debug_output(std::io::stderr());
+    exec_ctx.add_module(module::oficial::io_module()?);
+    exec_ctx.add_module(module::oficial::json_module()?);
+    if debug {
+        exec_ctx.set_debugger(Some(debug::debugger()));
+    }
+    Ok(exec_ctx)
+}
+
+/// # Execute the script every time it or a file it includes changes
+///
+/// Runs until the process is killed, each execution starts from a new context
+fn watch(file_path: &Path, debug: bool) -> Result<(), stck::Error> {
+    let mut file_cacher = TrackingCache::new();
+    loop {
+        let mut exec_ctx = new_context(debug)?;
+        let result = get_project_code(file_path, &mut file_cacher)
+            .and_then(|code| Ok(exec_ctx.execute_entire_code(&code)?));
+        if let Err(e) = result {
+            writeln!(exec_ctx.debug_output(), "{}", e.to_string().trim_end())
+                .map_err(stck::error::StckError::from)?;
+        }
+        eprintln!("{}", "Waiting for changes...".dimmed());
+        // a script that couldn't be read isn't tracked, so it's read again once it exists
+        while !file_cacher.refresh().contains(file_path)
+            && (file_cacher.is_tracked(file_path) || !file_path.is_file())
+        {
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
+
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(300);
+
+/// # Format the files, returning false if checking and some aren't formatted
+fn format_files(files: &[PathBuf], check: bool) -> Result<bool, stck::Error> {
+    let mut all_formatted = true;
+    for file in files {
+        let cont = std::fs::read_to_string(file).map_err(stck::error::StckError::from)?;
+        let formatted = stck::format::format(&cont, file)?;
+        if formatted == cont {
+            continue;
+        }
+        if check {
+            println!("{} isn't formatted", file.display());
+            all_formatted = false;
+        } else {
+            std::fs::write(file, formatted).map_err(stck::error::StckError::from)?;
+        }
+    }
+    Ok(all_formatted)
+}
@@ -0,0 +1,187 @@
+use colored::Colorize;
+use stck::prelude::*;
+use std::io::{BufRead, Write};
+
+const PROMPT: &str = "stck> ";
+const CONTINUE_PROMPT: &str = "....> ";
+const SOURCE_NAME: &str = "repl";
+
+const HELP: &str = "\
+:fns     list user-defined functions
+:vars    list variables
+:stack   show the stack
+:modules list enabled modules
+:clear   clear the stack
+:help    show this message
+:quit    exit the repl";
+
+enum Meta {
+    Fns,
+    Vars,
+    Stack,
+    Modules,
+    Clear,
+    Help,
+    Quit,
+    Unknown(String),
+}
+
+impl Meta {
+    fn parse(line: &str) -> Option<Self> {
+        let cmd = line.trim().strip_prefix(':')?;
+        Some(match cmd {
+            "fns" => Meta::Fns,
+            "vars" => Meta::Vars,
+            "stack" => Meta::Stack,
+            "modules" => Meta::Modules,
+            "clear" => Meta::Clear,
+            "help" => Meta::Help,
+            "quit" | "q" => Meta::Quit,
+            otherwise => Meta::Unknown(otherwise.to_string()),
+        })
+    }
+}
+
+/// # Count of `{` still waiting for their `}`
+///
+/// Strings, chars and comments are skipped, so braces inside them don't count
+fn open_blocks(cont: &str) -> isize {
+    enum State {
+        Code,
+        Str,
+        StrEsc,
+        Char,
+        CharEsc,
+        Comment,
+    }
+    let mut state = State::Code;
+    let mut depth = 0;
+    // `'` and `#` only start a char or comment at the start of a token, like `cl$join'`
+    let mut token_start = true;
+    for ch in cont.chars() {
+        state = match (state, ch) {
+            (State::Code, '{') => {
+                depth += 1;
+                State::Code
+            }
+            (State::Code, '}') => {
+                depth -= 1;
+                State::Code
+            }
+            (State::Code, '"') => State::Str,
+            (State::Code, '\'') if token_start => State::Char,
+            (State::Code, '#') if token_start => State::Comment,
+            (State::Code, _) => State::Code,
+            (State::Str, '\\') => State::StrEsc,
+            (State::Str, '"') => State::Code,
+            (State::Str | State::StrEsc, _) => State::Str,
+            (State::Char, '\\') => State::CharEsc,
+            (State::Char, '\'') => State::Code,
+            (State::Char | State::CharEsc, _) => State::Char,
+            (State::Comment, '\n') => State::Code,
+            (State::Comment, _) => State::Comment,
+        };
+        token_start = ch.is_whitespace() || matches!(ch, '(' | ')' | '{' | '}' | '[' | ']');
+    }
+    depth
+}
+
+fn print_stack(ctx: &RuntimeContext) {
+    let stack = ctx.get_stack();
+    if stack.is_empty() {
+        println!("{}", "<empty stack>".dimmed());
+        return;
+    }
+    for (idx, v) in stack.iter().enumerate() {
+        println!("{} {v}", format!("[{idx}]").dimmed());
+    }
+}
+
+fn run_meta(meta: Meta, ctx: &mut RuntimeContext) -> bool {
+    match meta {
+        Meta::Fns => {
+            let mut fns: Vec<_> = ctx.get_fns().collect();
+            fns.sort_by_key(|(name, _)| *name);
+            for (name, args) in fns {
+                println!("{} consuming {args}", name.bright_yellow());
+            }
+        }
+        Meta::Vars => {
+            let mut vars: Vec<_> = ctx.get_vars().iter().collect();
+            vars.sort_by_key(|(name, _)| *name);
+            for (name, value) in vars {
+                println!("{name} = {value}");
+            }
+        }
+        Meta::Modules => {
+            for module in ctx.get_modules() {
+                println!("{module}");
+            }
+        }
+        Meta::Stack => print_stack(ctx),
+        Meta::Clear => ctx.stack.clear(),
+        Meta::Help => println!("{HELP}"),
+        Meta::Quit => return false,
+        Meta::Unknown(cmd) => {
+            eprintln!("Unknown command `:{cmd}`, try :help");
+        }
+    }
+    true
+}
+
+fn run_entry(entry: &str, ctx: &mut RuntimeContext, cacher: &mut CacheHelper) {
+    let code = get_tokens_str(entry, SOURCE_NAME, cacher).and_then(parse_raw_tokens);
+    let code = match code {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    if let Err(e) = ctx.execute_entire_code(&code) {
+        // the error display goes along the script's debug output
+        let _ = write!(ctx.debug_output(), "{e}");
+    }
+    print_stack(ctx);
+}
+
+/// # Read-eval-print loop
+///
+/// Every entry is executed in the same [runtime context](RuntimeContext), so functions,
+/// variables and the stack are kept between entries
+pub fn run(mut ctx: RuntimeContext) -> std::io::Result<()> {
+    let mut cacher = CacheHelper::new();
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout();
+    let mut entry = String::new();
+    loop {
+        let prompt = if entry.is_empty() {
+            PROMPT
+        } else {
+            CONTINUE_PROMPT
+        };
+        write!(stdout, "{prompt}")?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        if let Some(meta) = Meta::parse(&line).filter(|_| entry.is_empty()) {
+            if !run_meta(meta, &mut ctx) {
+                return Ok(());
+            }
+            continue;
+        }
+        entry.push_str(&line);
+        if !entry.ends_with('\n') {
+            entry.push('\n');
+        }
+        if open_blocks(&entry) > 0 {
+            continue;
+        }
+        run_entry(&entry, &mut ctx, &mut cacher);
+        entry.clear();
+    }
+}
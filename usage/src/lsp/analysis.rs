@@ -0,0 +1,146 @@
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use stck::cache::CacheHelper;
+use stck::error::{Error, LineRange};
+use stck::internals::{Code, Expr, ExprCont, FnArgDef, FnArgs, KeywordKind};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// # A function defined in the document or in one of its includes
+pub struct FnInfo {
+    pub name: String,
+    pub source: PathBuf,
+    pub span: LineRange,
+    pub signature: String,
+}
+
+/// # What is known about a document after running the parser on it
+#[derive(Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub fns: Vec<FnInfo>,
+    /// Line of each `(include)` in the document and the file it resolved to
+    pub includes: Vec<(usize, PathBuf)>,
+}
+
+impl Analysis {
+    pub fn run(path: &Path, text: &str) -> Self {
+        let mut analysis = Analysis::default();
+        let code = stck::api::get_tokens_str(text, path, &mut CacheHelper::new())
+            .and_then(stck::api::parse_raw_tokens);
+        let code = match code {
+            Ok(code) => code,
+            Err(e) => {
+                analysis.diagnostics.push(error_diagnostic(path, text, &e));
+                return analysis;
+            }
+        };
+        analysis.collect(&code, code.iter(), true);
+        for diagnostic in stck::check::check(&code) {
+            if diagnostic.source == path {
+                analysis.diagnostics.push(Diagnostic {
+                    range: line_range(&diagnostic.span, text),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    source: Some("stck".to_string()),
+                    message: diagnostic.kind.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+        analysis
+    }
+
+    pub fn fns_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a FnInfo> {
+        self.fns.iter().filter(move |f| f.name == name)
+    }
+
+    fn collect<'a>(&mut self, code: &Code, exprs: impl Iterator<Item = &'a Expr>, top: bool) {
+        for expr in exprs {
+            match &expr.cont {
+                ExprCont::IncludedCode(included) => {
+                    if top {
+                        self.includes
+                            .push((expr.span.start(), included.source().to_path_buf()));
+                    }
+                    self.collect(included, included.iter(), false);
+                }
+                ExprCont::Keyword(KeywordKind::FnDef {
+                    name,
+                    scope,
+                    code: body,
+                    args,
+                    out_args,
+                }) => {
+                    let mut signature = format!("(fn{scope}) {}", display_args(args));
+                    if let Some(outs) = out_args {
+                        signature = format!("{signature} {}", display_arg_defs(outs));
+                    }
+                    self.fns.push(FnInfo {
+                        name: name.clone(),
+                        source: code.source().to_path_buf(),
+                        span: expr.span.clone(),
+                        signature: format!("{signature} {name}"),
+                    });
+                    self.collect(code, body.iter(), false);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn display_args(args: &FnArgs) -> String {
+    match args {
+        FnArgs::AllStack => "*".to_string(),
+        FnArgs::Args(args) => display_arg_defs(args),
+    }
+}
+
+fn display_arg_defs(args: &[FnArgDef]) -> String {
+    let mut out = String::from("[ ");
+    for arg in args {
+        out.push_str(arg.get_name());
+        if let Some(tt) = arg.get_type() {
+            let _ = write!(out, "<{tt}>");
+        }
+        out.push(' ');
+    }
+    out.push(']');
+    out
+}
+
+fn error_diagnostic(path: &Path, text: &str, error: &Error) -> Diagnostic {
+    let (span, message) = match error {
+        Error::Anoter(e) => (e.span(), e.unspanned().to_string()),
+        Error::RuntimeError(e) => (None, e.to_string()),
+    };
+    // errors from included files are shown on the first line of the document, with their location
+    let (range, message) = match span {
+        Some((file, span)) if file == path => (line_range(span, text), message),
+        _ => (Range::default(), error.to_string()),
+    };
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("stck".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// # Convert a span of one-based lines into a range covering those lines
+pub fn line_range(span: &LineRange, text: &str) -> Range {
+    let start = span.start().saturating_sub(1);
+    let end = span.end().max(span.start()).saturating_sub(1);
+    let end_len = text
+        .lines()
+        .nth(end)
+        .map_or(0, |l| l.encode_utf16().count());
+    Range {
+        start: Position::new(to_u32(start), 0),
+        end: Position::new(to_u32(end), to_u32(end_len)),
+    }
+}
+
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
@@ -0,0 +1,58 @@
+mod analysis;
+mod server;
+#[cfg(test)]
+mod tests;
+
+use lsp_server::{Connection, Message, Notification};
+use lsp_types::notification::{Notification as _, PublishDiagnostics};
+use lsp_types::{
+    CompletionOptions, HoverProviderCapability, OneOf, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind,
+};
+use server::{BoxError, Server};
+
+fn main() -> Result<(), BoxError> {
+    colored::control::set_override(false);
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["(".to_string(), "#".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    main_loop(&connection)?;
+    // the io threads finish once the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// # Handle messages until shutdown
+fn main_loop(connection: &Connection) -> Result<(), BoxError> {
+    let mut server = Server::new();
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.request(req)))?;
+            }
+            Message::Notification(not) => {
+                if let Some(params) = server.notification(not)? {
+                    let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                    connection.sender.send(Message::Notification(not))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use crate::analysis::{Analysis, line_range};
+use lsp_server::{ErrorCode, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind,
+    Position, PublishDiagnosticsParams, Range, TextDocumentPositionParams, Uri,
+};
+use stck::internals::BUILTINS;
+use stck::internals::module::{Module, oficial};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub type BoxError = Box<dyn std::error::Error + Sync + Send>;
+
+struct Document {
+    text: String,
+    analysis: Analysis,
+}
+
+/// # Open documents and what the server answers about them
+///
+/// Doesn't know about the connection, messages are given to it and it returns the answers
+pub struct Server {
+    documents: HashMap<Uri, Document>,
+    modules: Vec<Module>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        let modules = [oficial::io_module(), oficial::json_module()];
+        Server {
+            documents: HashMap::new(),
+            modules: modules.into_iter().filter_map(Result::ok).collect(),
+        }
+    }
+
+    pub fn request(&self, req: Request) -> Response {
+        let result = match req.method.as_str() {
+            GotoDefinition::METHOD => serde_json::from_value(req.params)
+                .map(|params| serde_json::to_value(self.definition(params))),
+            HoverRequest::METHOD => serde_json::from_value(req.params)
+                .map(|params| serde_json::to_value(self.hover(params))),
+            Completion::METHOD => serde_json::from_value(req.params)
+                .map(|params| serde_json::to_value(self.completion(params))),
+            _ => {
+                return Response::new_err(
+                    req.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request {}", req.method),
+                );
+            }
+        };
+        match result {
+            Ok(Ok(value)) => Response::new_ok(req.id, value),
+            Ok(Err(e)) | Err(e) => {
+                Response::new_err(req.id, ErrorCode::InvalidParams as i32, e.to_string())
+            }
+        }
+    }
+
+    /// # Update the documents, returning the diagnostics to publish
+    pub fn notification(
+        &mut self,
+        not: Notification,
+    ) -> Result<Option<PublishDiagnosticsParams>, BoxError> {
+        let (uri, text) = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                (params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                let Some(change) = params.content_changes.into_iter().last() else {
+                    return Ok(None);
+                };
+                (params.text_document.uri, change.text)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                self.documents.remove(&params.text_document.uri);
+                let params = PublishDiagnosticsParams::new(params.text_document.uri, vec![], None);
+                return Ok(Some(params));
+            }
+            _ => return Ok(None),
+        };
+        let analysis = Analysis::run(&uri_to_path(&uri), &text);
+        let diagnostics = analysis.diagnostics.clone();
+        self.documents
+            .insert(uri.clone(), Document { text, analysis });
+        Ok(Some(PublishDiagnosticsParams::new(uri, diagnostics, None)))
+    }
+
+    pub fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let doc = self.documents.get(&text_document.uri)?;
+        let line = position.line as usize + 1;
+        if let Some((_, file)) = doc.analysis.includes.iter().find(|(l, _)| *l == line) {
+            let location = Location::new(path_to_uri(file)?, Range::default());
+            return Some(GotoDefinitionResponse::Scalar(location));
+        }
+        let word = word_at(&doc.text, position)?;
+        let locations: Vec<_> = doc
+            .analysis
+            .fns_named(word)
+            .filter_map(|f| {
+                let text = if f.source == uri_to_path(&text_document.uri) {
+                    doc.text.clone()
+                } else {
+                    std::fs::read_to_string(&f.source).unwrap_or_default()
+                };
+                Some(Location::new(
+                    path_to_uri(&f.source)?,
+                    line_range(&f.span, &text),
+                ))
+            })
+            .collect();
+        (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+    }
+
+    pub fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let doc = self.documents.get(&text_document.uri)?;
+        let word = word_at(&doc.text, position)?;
+        let mut lines = Vec::new();
+        for f in doc.analysis.fns_named(word) {
+            lines.push(format!(
+                "```stck\n{}\n```\ndefined at {}:{}",
+                f.signature,
+                f.source.display(),
+                f.span.start()
+            ));
+        }
+        if BUILTINS.contains(&word) {
+            lines.push(format!("`{word}` builtin"));
+        }
+        for module in &self.modules {
+            if module.fn_names().any(|f| f == word) {
+                lines.push(format!("`{word}` from `(require {})`", module.name()));
+            }
+        }
+        (!lines.is_empty()).then(|| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: lines.join("\n\n---\n\n"),
+            }),
+            range: None,
+        })
+    }
+
+    pub fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let doc = self.documents.get(&text_document.uri)?;
+        let line = doc
+            .text
+            .lines()
+            .nth(position.line as usize)
+            .unwrap_or_default();
+        let before: String = line.chars().take(position.character as usize).collect();
+        let item = |label: &str, kind, detail: String| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            detail: Some(detail),
+            ..Default::default()
+        };
+        if before.trim_start().starts_with("(require") {
+            let items = self
+                .modules
+                .iter()
+                .map(|m| item(m.name(), CompletionItemKind::MODULE, "module".to_string()))
+                .collect();
+            return Some(CompletionResponse::Array(items));
+        }
+        let builtins = BUILTINS
+            .iter()
+            .map(|b| item(b, CompletionItemKind::FUNCTION, "builtin".to_string()));
+        let user_fns = doc
+            .analysis
+            .fns
+            .iter()
+            .map(|f| item(&f.name, CompletionItemKind::FUNCTION, f.signature.clone()));
+        let module_fns = self.modules.iter().flat_map(|m| {
+            m.fn_names()
+                .map(|f| item(f, CompletionItemKind::FUNCTION, m.name().to_string()))
+        });
+        Some(CompletionResponse::Array(
+            builtins.chain(user_fns).chain(module_fns).collect(),
+        ))
+    }
+}
+
+/// # Identifier under the cursor
+///
+/// Identifiers are anything between whitespace and delimiters, so `arr$len` and `.-` are whole
+fn word_at(text: &str, position: Position) -> Option<&str> {
+    let line = text.lines().nth(position.line as usize)?;
+    let is_delim = |c: char| c.is_whitespace() || "()[]{}<>\"'".contains(c);
+    // the position is in utf-16 code units
+    let mut units = 0;
+    let cursor = line
+        .char_indices()
+        .find(|(_, c)| {
+            units += c.len_utf16();
+            units > position.character as usize
+        })
+        .map_or(line.len(), |(idx, _)| idx);
+    let start = line[..cursor].rfind(is_delim).map_or(0, |idx| idx + 1);
+    let end = line[cursor..]
+        .find(is_delim)
+        .map_or(line.len(), |idx| cursor + idx);
+    (start < end).then(|| &line[start..end])
+}
+
+fn uri_to_path(uri: &Uri) -> PathBuf {
+    let path = uri.path().as_estr().decode();
+    match path.into_string() {
+        Ok(path) => PathBuf::from(path.as_ref()),
+        Err(_) => PathBuf::from(uri.path().as_str()),
+    }
+}
+
+fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = std::path::absolute(path).ok()?;
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~$&+,;=@:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    Uri::from_str(&uri).ok()
+}
@@ -0,0 +1,120 @@
+use crate::server::Server;
+use lsp_server::Notification;
+use lsp_types::notification::{DidOpenTextDocument, Notification as _};
+use lsp_types::{
+    CompletionParams, CompletionResponse, Diagnostic, DiagnosticSeverity,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, HoverContents,
+    HoverParams, PartialResultParams, Position, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Uri, WorkDoneProgressParams,
+};
+use std::str::FromStr;
+
+const DOUBLE: &str = "(fn) [ n<num> ] double { n 2 * }\n1 double\n";
+
+fn uri() -> Uri {
+    Uri::from_str("file:///stck-lsp-test/main.stck").unwrap()
+}
+
+// the document only lives in memory, like an unsaved buffer of the editor
+fn open(text: &str) -> (Server, Vec<Diagnostic>) {
+    let mut server = Server::new();
+    let params = DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(uri(), "stck".to_string(), 1, text.to_string()),
+    };
+    let not = Notification::new(DidOpenTextDocument::METHOD.to_string(), params);
+    let published = server.notification(not).unwrap().unwrap();
+    assert_eq!(published.uri, uri());
+    (server, published.diagnostics)
+}
+
+fn at(line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams::new(
+        TextDocumentIdentifier::new(uri()),
+        Position::new(line, character),
+    )
+}
+
+fn complete(server: &Server, line: u32, character: u32) -> Vec<String> {
+    let params = CompletionParams {
+        text_document_position: at(line, character),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        context: None,
+    };
+    match server.completion(params) {
+        Some(CompletionResponse::Array(items)) => items.into_iter().map(|i| i.label).collect(),
+        other => panic!("expected completion items, got {other:?}"),
+    }
+}
+
+#[test]
+fn diagnostics() {
+    let (_, diagnostics) = open(DOUBLE);
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+    let (_, diagnostics) = open("1 2\n(fn) [ a ] broken {\n  a\n(fn) [] \n");
+    let [error] = diagnostics.as_slice() else {
+        panic!("expected one diagnostic, got {diagnostics:?}");
+    };
+    assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+
+    let (_, diagnostics) = open("1\n-\n");
+    let [warning] = diagnostics.as_slice() else {
+        panic!("expected one diagnostic, got {diagnostics:?}");
+    };
+    assert_eq!(warning.severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(warning.range.start.line, 1);
+}
+
+#[test]
+fn definition() {
+    let (server, _) = open(DOUBLE);
+    let params = GotoDefinitionParams {
+        text_document_position_params: at(1, 4),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    };
+    let Some(GotoDefinitionResponse::Array(locations)) = server.definition(params) else {
+        panic!("expected the definition of double");
+    };
+    let [location] = locations.as_slice() else {
+        panic!("expected one location, got {locations:?}");
+    };
+    assert_eq!(location.uri, uri());
+    assert_eq!(location.range.start.line, 0);
+}
+
+#[test]
+fn hover() {
+    let (server, _) = open(DOUBLE);
+    let hover_at = |line, character| {
+        let params = HoverParams {
+            text_document_position_params: at(line, character),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        match server.hover(params).map(|h| h.contents) {
+            Some(HoverContents::Markup(markup)) => Some(markup.value),
+            Some(other) => panic!("expected markdown, got {other:?}"),
+            None => None,
+        }
+    };
+    let double = hover_at(1, 4).unwrap();
+    assert!(double.contains("[ n<num> ]"), "{double}");
+    let builtin = hover_at(0, 30).unwrap();
+    assert!(builtin.contains("`*` builtin"), "{builtin}");
+    assert_eq!(hover_at(1, 0), None);
+}
+
+#[test]
+fn completion() {
+    let (server, _) = open("(require #io)\n1 dou\n");
+    let modules = complete(&server, 0, 9);
+    assert!(modules.iter().any(|m| m == "#io"), "{modules:?}");
+    assert!(!modules.iter().any(|m| m == "print"), "{modules:?}");
+
+    let (server, _) = open(DOUBLE);
+    let names = complete(&server, 1, 2);
+    for name in ["double", "print", "*"] {
+        assert!(names.iter().any(|n| n == name), "{name} in {names:?}");
+    }
+}